@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE issue_delivery_queue ADD COLUMN n_retries SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE issue_delivery_queue ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
+
+ALTER TABLE newsletter_issues ADD COLUMN delivery_started_at timestamptz NULL;
+
+CREATE TABLE issue_delivery_log (
+   newsletter_issue_id uuid NOT NULL
+     REFERENCES newsletter_issues (newsletter_issue_id),
+   subscriber_email TEXT NOT NULL,
+   outcome TEXT NOT NULL,
+   n_retries SMALLINT NOT NULL,
+   completed_at timestamptz NOT NULL,
+   PRIMARY KEY(newsletter_issue_id, subscriber_email)
+);
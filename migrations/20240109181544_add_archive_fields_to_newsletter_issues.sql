@@ -0,0 +1,20 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE newsletter_issues ADD COLUMN slug TEXT NULL;
+    ALTER TABLE newsletter_issues
+        ADD COLUMN hidden_from_archive BOOLEAN NOT NULL DEFAULT false;
+    -- Backfill `slug` for historical issues
+    UPDATE newsletter_issues
+        SET slug = concat_ws(
+            '-',
+            NULLIF(
+                trim(BOTH '-' FROM left(regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g'), 60)),
+                ''
+            ),
+            left(newsletter_issue_id::text, 8)
+        )
+        WHERE slug IS NULL;
+    -- Make `slug` mandatory and unique
+    ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+    ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
+COMMIT;
@@ -0,0 +1,10 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE newsletter_issues ADD COLUMN published_at_tz timestamptz NULL;
+    -- Backfill from the textual `now()` values stored so far
+    UPDATE newsletter_issues
+        SET published_at_tz = published_at::timestamptz;
+    ALTER TABLE newsletter_issues ALTER COLUMN published_at_tz SET NOT NULL;
+    ALTER TABLE newsletter_issues DROP COLUMN published_at;
+    ALTER TABLE newsletter_issues RENAME COLUMN published_at_tz TO published_at;
+COMMIT;
@@ -0,0 +1,11 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues
+    ADD COLUMN title_search tsvector
+    GENERATED ALWAYS AS (to_tsvector('english', title)) STORED;
+
+CREATE INDEX newsletter_issues_title_search_idx
+    ON newsletter_issues USING GIN (title_search);
+CREATE INDEX newsletter_issues_published_at_idx
+    ON newsletter_issues (published_at, newsletter_issue_id);
+CREATE INDEX newsletter_issues_title_idx
+    ON newsletter_issues (title, newsletter_issue_id);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX subscriptions_subscribed_at_idx
+    ON subscriptions (subscribed_at, id);
+CREATE INDEX subscriptions_status_subscribed_at_idx
+    ON subscriptions (status, subscribed_at, id);
+CREATE INDEX subscriptions_email_trgm_idx
+    ON subscriptions USING GIN (email gin_trgm_ops);
+CREATE INDEX subscriptions_name_trgm_idx
+    ON subscriptions USING GIN (name gin_trgm_ops);
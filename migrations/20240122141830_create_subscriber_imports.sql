@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE subscriber_imports (
+    import_id uuid PRIMARY KEY,
+    imported_status TEXT NOT NULL,
+    n_imported INT NOT NULL DEFAULT 0,
+    n_duplicates INT NOT NULL DEFAULT 0,
+    n_rejected INT NOT NULL DEFAULT 0,
+    n_confirmation_emails INT NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE subscriber_import_rejections (
+    import_id uuid NOT NULL
+        REFERENCES subscriber_imports (import_id) ON DELETE CASCADE,
+    row_number INT NOT NULL,
+    email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    PRIMARY KEY (import_id, row_number)
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE subscriber_data_requests (
+    request_id uuid PRIMARY KEY,
+    subscriber_id uuid NOT NULL,
+    kind TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL DEFAULT now()
+);
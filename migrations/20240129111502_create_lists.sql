@@ -0,0 +1,41 @@
+-- Add migration script here
+BEGIN;
+    CREATE TABLE lists (
+        list_id uuid PRIMARY KEY,
+        slug TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        is_default BOOLEAN NOT NULL DEFAULT false,
+        created_at timestamptz NOT NULL DEFAULT now()
+    );
+    -- At most one list receives subscribers that do not pick one
+    CREATE UNIQUE INDEX lists_single_default_idx ON lists (is_default) WHERE is_default;
+
+    INSERT INTO lists (list_id, slug, name, is_default)
+    VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', true);
+
+    CREATE TABLE list_memberships (
+        list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+        subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        status TEXT NOT NULL,
+        subscribed_at timestamptz NOT NULL DEFAULT now(),
+        PRIMARY KEY (list_id, subscriber_id)
+    );
+    CREATE INDEX list_memberships_subscriber_idx ON list_memberships (subscriber_id);
+
+    -- Everybody subscribed so far joined the one and only list
+    INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+    SELECT l.list_id, s.id, s.status, s.subscribed_at
+    FROM subscriptions s, lists l
+    WHERE l.is_default;
+
+    -- Which list a confirmation token confirms, if any in particular
+    ALTER TABLE subscription_tokens
+        ADD COLUMN list_id uuid NULL REFERENCES lists (list_id) ON DELETE CASCADE;
+
+    CREATE TABLE newsletter_issue_lists (
+        newsletter_issue_id uuid NOT NULL
+            REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+        list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+        PRIMARY KEY (newsletter_issue_id, list_id)
+    );
+COMMIT;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE subscriptions
+    ADD COLUMN digest_frequency TEXT NOT NULL DEFAULT 'immediate',
+    ADD COLUMN paused_until timestamptz NULL;
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE email_change_requests (
+    change_token TEXT PRIMARY KEY,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    requested_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,27 @@
+-- Add migration script here
+BEGIN;
+    CREATE TABLE subscriber_tags (
+        subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (subscriber_id, tag)
+    );
+    CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
+
+    CREATE TABLE subscriber_attributes (
+        subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (subscriber_id, key)
+    );
+    CREATE INDEX subscriber_attributes_key_value_idx ON subscriber_attributes (key, value);
+
+    CREATE TABLE segments (
+        segment_id uuid PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        definition JSONB NOT NULL,
+        created_at timestamptz NOT NULL DEFAULT now()
+    );
+
+    ALTER TABLE newsletter_issues
+        ADD COLUMN segment_id uuid NULL REFERENCES segments (segment_id) ON DELETE SET NULL;
+COMMIT;
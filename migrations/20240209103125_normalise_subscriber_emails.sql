@@ -0,0 +1,90 @@
+-- Add migration script here
+BEGIN;
+    -- Every subscriber, the subscriber they are merged into and the address that one keeps.
+    -- Addresses are trimmed and get a lowercase domain. Among addresses that only differ by
+    -- case, the best status wins, then the earliest subscription.
+    CREATE TEMPORARY TABLE merged_subscribers ON COMMIT DROP AS
+    SELECT
+        id,
+        email,
+        first_value(id) OVER w AS survivor_id,
+        first_value(normalised_email) OVER w AS survivor_email
+    FROM (
+        SELECT
+            *,
+            substring(btrim(email) from '^(.*)@[^@]*$')
+                || '@' || lower(substring(btrim(email) from '@([^@]*)$')) AS normalised_email
+        FROM subscriptions
+    ) s
+    WINDOW w AS (
+        PARTITION BY lower(normalised_email)
+        ORDER BY
+            CASE status WHEN 'confirmed' THEN 0 WHEN 'pending_confirmation' THEN 1 ELSE 2 END,
+            subscribed_at,
+            id
+    );
+
+    -- Deliveries are matched to subscribers by address, so queued ones move to the kept
+    -- address. A merged subscriber gets each issue once.
+    WITH moved AS (
+        DELETE FROM issue_delivery_queue q
+        USING merged_subscribers m
+        WHERE q.subscriber_email = m.email AND m.email <> m.survivor_email
+        RETURNING q.newsletter_issue_id, m.survivor_email, q.n_retries, q.execute_after
+    )
+    INSERT INTO issue_delivery_queue
+        (newsletter_issue_id, subscriber_email, n_retries, execute_after)
+    SELECT DISTINCT ON (newsletter_issue_id, survivor_email)
+        newsletter_issue_id, survivor_email, n_retries, execute_after
+    FROM moved
+    ORDER BY newsletter_issue_id, survivor_email, execute_after
+    ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING;
+
+    -- A merged subscriber is confirmed on every list any of the duplicates confirmed
+    INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+    SELECT DISTINCT ON (m.survivor_id, lm.list_id)
+        lm.list_id, m.survivor_id, lm.status, lm.subscribed_at
+    FROM list_memberships lm
+    JOIN merged_subscribers m ON m.id = lm.subscriber_id AND m.id <> m.survivor_id
+    ORDER BY m.survivor_id, lm.list_id, lm.status = 'confirmed' DESC, lm.subscribed_at
+    ON CONFLICT (list_id, subscriber_id) DO UPDATE
+    SET status = EXCLUDED.status, subscribed_at = EXCLUDED.subscribed_at
+    WHERE EXCLUDED.status = 'confirmed' AND list_memberships.status <> 'confirmed';
+
+    INSERT INTO subscriber_tags (subscriber_id, tag)
+    SELECT m.survivor_id, t.tag
+    FROM subscriber_tags t
+    JOIN merged_subscribers m ON m.id = t.subscriber_id AND m.id <> m.survivor_id
+    ON CONFLICT DO NOTHING;
+
+    -- The kept subscriber's own attributes take precedence
+    INSERT INTO subscriber_attributes (subscriber_id, key, value)
+    SELECT m.survivor_id, a.key, a.value
+    FROM subscriber_attributes a
+    JOIN merged_subscribers m ON m.id = a.subscriber_id AND m.id <> m.survivor_id
+    ON CONFLICT DO NOTHING;
+
+    UPDATE subscription_tokens t SET subscriber_id = m.survivor_id
+    FROM merged_subscribers m
+    WHERE t.subscriber_id = m.id AND m.id <> m.survivor_id;
+
+    UPDATE email_change_requests r SET subscriber_id = m.survivor_id
+    FROM merged_subscribers m
+    WHERE r.subscriber_id = m.id AND m.id <> m.survivor_id;
+
+    UPDATE subscriber_data_requests r SET subscriber_id = m.survivor_id
+    FROM merged_subscribers m
+    WHERE r.subscriber_id = m.id AND m.id <> m.survivor_id;
+
+    DELETE FROM subscriptions s
+    USING merged_subscribers m
+    WHERE s.id = m.id AND m.id <> m.survivor_id;
+
+    UPDATE subscriptions s SET email = m.survivor_email
+    FROM merged_subscribers m
+    WHERE s.id = m.id AND s.email <> m.survivor_email;
+
+    -- Addresses that only differ by case belong to the same subscriber
+    ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+    CREATE UNIQUE INDEX subscriptions_email_lower_idx ON subscriptions (lower(email));
+COMMIT;
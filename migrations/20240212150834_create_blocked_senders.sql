@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE blocked_senders (
+    -- A lowercase domain, or a lowercase address when it contains an @
+    entry TEXT PRIMARY KEY,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
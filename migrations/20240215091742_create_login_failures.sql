@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Failed logins, counted per username and per client IP. Keys are `username:<name>` or
+-- `ip:<address>`, so that unknown usernames are counted like existing ones.
+CREATE TABLE login_failures(
+    key TEXT NOT NULL PRIMARY KEY,
+    n_failures INT NOT NULL,
+    last_failure_at timestamptz NOT NULL,
+    blocked_until timestamptz NULL
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Users who enabled two-factor authentication have a TOTP secret. The last time step a
+-- code was accepted for is kept so that a code cannot be used twice.
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT NULL;
+
+-- One-time codes for logging in without the authenticator, stored as SHA-256 hashes
+CREATE TABLE recovery_codes(
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
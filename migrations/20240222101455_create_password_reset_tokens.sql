@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Password reset links are sent to this address
+ALTER TABLE users ADD COLUMN email TEXT NULL;
+-- Sessions authenticated before this time are no longer accepted
+ALTER TABLE users ADD COLUMN sessions_valid_after timestamptz NULL;
+
+-- Only the SHA-256 hash of a reset token is stored
+CREATE TABLE password_reset_tokens(
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Existing users keep being able to do everything
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+-- Deactivated users cannot log in until they are reactivated
+ALTER TABLE users ADD COLUMN deactivated_at timestamptz NULL;
+ALTER TABLE users ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+
+-- Invitations to become an admin user. Only the SHA-256 hash of the token is stored.
+CREATE TABLE user_invitations(
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    invited_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    accepted_at timestamptz NULL
+);
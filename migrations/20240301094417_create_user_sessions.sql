@@ -0,0 +1,12 @@
+-- Add migration script here
+-- The logged in sessions of admin users. Session data lives in Redis; a session whose
+-- row is gone is logged out on its next request.
+CREATE TABLE user_sessions(
+    session_id uuid NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    ip TEXT NOT NULL,
+    user_agent TEXT NULL
+);
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Subscribers are told apart by the address `SubscriberEmail::parse_with` stores, so that
+-- local parts only stop being case-sensitive when `lowercase_local_part` is set.
+-- Addresses stored before it was set are lowercased by `zerotoprod normalise-emails`.
+BEGIN;
+    DROP INDEX subscriptions_email_lower_idx;
+    ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_email_key UNIQUE (email);
+COMMIT;
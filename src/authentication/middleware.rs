@@ -1,30 +1,43 @@
+use anyhow::Context;
 use axum::{
     body::Body,
     extract::{FromRequestParts, Request, State},
-    http::StatusCode,
+    http::{Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
-    RequestExt,
+    Extension, RequestExt,
 };
 use axum_flash::Flash;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
-use crate::{session_state::TypedSession, utils::e500, AppState};
+use crate::{domain::UserRole, session_state::TypedSession, utils::e500, AppState};
 
+/// The logged in user, and their role as of this request.
 #[derive(Copy, Clone, Debug)]
-pub struct UserId(Uuid);
+pub struct UserId {
+    user_id: Uuid,
+    role: UserRole,
+}
+
+impl UserId {
+    pub fn role(&self) -> UserRole {
+        self.role
+    }
+}
 
 impl std::fmt::Display for UserId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.user_id.fmt(f)
     }
 }
 
 impl Deref for UserId {
     type Target = Uuid;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.user_id
     }
 }
 
@@ -42,10 +55,97 @@ pub async fn reject_anonymous_users(
         .map_err(e500)?;
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
-            request.extensions_mut().insert(UserId(user_id));
+            let session_id = session.get_session_id().map_err(e500)?;
+            let authenticated_at = session.get_authenticated_at().map_err(e500)?;
+            let role = match session_id {
+                Some(session_id) => current_role(
+                    &state.pg_connection_pool,
+                    user_id,
+                    session_id,
+                    authenticated_at,
+                )
+                .await
+                .map_err(e500)?,
+                None => None,
+            };
+            let Some(role) = role else {
+                session.log_out();
+                let flash = flash.error("Session expired!");
+                return Ok((flash, Redirect::to("/login")).into_response());
+            };
+            request.extensions_mut().insert(UserId { user_id, role });
             let response = next.run(request).await;
             Ok(response)
         }
         None => Ok((flash.error("Session expired!"), Redirect::to("/login")).into_response()),
     }
 }
+
+/// The role of the session's user, unless the session is no longer valid: the user was
+/// deleted or deactivated, the session was logged out remotely, or all the user's
+/// sessions were revoked after it was authenticated. Records the session as seen.
+#[tracing::instrument(name = "Check the session is current", skip(pg_pool))]
+async fn current_role(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+    authenticated_at: Option<DateTime<Utc>>,
+) -> anyhow::Result<Option<UserRole>> {
+    let row = sqlx::query!(
+        r#"
+        WITH seen AS (
+            UPDATE user_sessions SET last_seen_at = now()
+            WHERE session_id = $2 AND user_id = $1
+            RETURNING user_id
+        )
+        SELECT u.role, u.sessions_valid_after FROM users u
+        JOIN seen ON seen.user_id = u.user_id
+        WHERE u.deactivated_at IS NULL
+        "#,
+        user_id,
+        session_id,
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to look up the user's sessions")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    if let Some(valid_after) = row.sessions_valid_after {
+        if authenticated_at.is_none_or(|at| at < valid_after) {
+            return Ok(None);
+        }
+    }
+    UserRole::parse(&row.role)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Lets only owners through. Use after `reject_anonymous_users`.
+pub async fn require_owner(user: Extension<UserId>, request: Request, next: Next) -> Response {
+    if user.role() < UserRole::Owner {
+        return forbidden();
+    }
+    next.run(request).await
+}
+
+/// Viewers can look at pages but not submit changes. Use after `reject_anonymous_users`.
+pub async fn reject_viewer_changes(
+    user: Extension<UserId>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let read_only = matches!(*request.method(), Method::GET | Method::HEAD);
+    if !read_only && user.role() < UserRole::Editor {
+        return forbidden();
+    }
+    next.run(request).await
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        "You are not allowed to do this. Ask an owner for a different role.",
+    )
+        .into_response()
+}
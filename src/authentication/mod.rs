@@ -1,6 +1,27 @@
 mod middleware;
+mod one_time_token;
 mod password;
+mod password_policy;
+mod sessions;
+mod throttling;
+mod two_factor;
 
-pub use middleware::reject_anonymous_users;
 pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{reject_anonymous_users, reject_viewer_changes, require_owner};
+pub use one_time_token::{generate_one_time_token, hash_one_time_token};
+pub use password::{
+    change_password, create_user, reset_password, validate_credentials, AuthError, Credentials,
+    NewUser,
+};
+pub use password_policy::{PasswordPolicy, PasswordPolicyError};
+pub use sessions::{
+    active_sessions, revoke_other_sessions, revoke_session, start_session, user_agent,
+    ActiveSession,
+};
+pub use throttling::{
+    clear_login_failures, login_failure_keys, login_is_blocked, record_login_failure,
+};
+pub use two_factor::{
+    disable_two_factor, enable_two_factor, generate_recovery_codes, two_factor_enabled,
+    unused_recovery_codes, verify_second_factor, Totp,
+};
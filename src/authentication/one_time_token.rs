@@ -0,0 +1,16 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// A random token for links that can be used once, like password resets and invitations.
+/// Only its hash is stored, see [`hash_one_time_token`].
+pub fn generate_one_time_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+pub fn hash_one_time_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
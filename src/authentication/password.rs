@@ -3,8 +3,10 @@ use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, Salt
 use argon2::{Algorithm, Argon2, Params, Version};
 use secrecy::ExposeSecret;
 use secrecy::Secret;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::UserRole;
 use crate::telemetry::spawn_blocking_with_tracing;
 
 #[derive(thiserror::Error, Debug)]
@@ -20,13 +22,20 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pg_pool))]
+/// Checks the credentials and returns the user they belong to. A password hash computed
+/// with outdated parameters is replaced by one computed with `hashing`.
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pg_pool, hashing))]
 pub async fn validate_credentials(
     pg_pool: &PgPool,
     credentials: Credentials,
+    hashing: &PasswordHashingSettings,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new("$argon2id$v=19$m=15000,t=2,p=1$gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno".to_string());
+    // Unknown usernames take as long to check as known ones
+    let mut expected_password_hash = Secret::new(format!(
+        "$argon2id$v=19$m={},t={},p={}$gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno",
+        hashing.memory_kib, hashing.iterations, hashing.parallelism,
+    ));
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pg_pool).await?
@@ -35,15 +44,29 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let hashing = *hashing;
+    let (verified_hash, rehashed) = spawn_blocking_with_tracing(move || {
+        verify_password_hash(&expected_password_hash, &credentials.password)?;
+        let rehashed = if needs_rehash(&expected_password_hash, &hashing) {
+            Some(compute_password_hash(credentials.password, &hashing)?)
+        } else {
+            None
+        };
+        Ok::<_, AuthError>((expected_password_hash, rehashed))
     })
     .await
     .context("Failed to spawn blocking task.")??;
 
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+    if let Some(rehashed) = rehashed {
+        // Logging in does not depend on the new hash being stored
+        if let Err(e) = replace_password_hash(pg_pool, user_id, &verified_hash, &rehashed).await {
+            tracing::warn!(error.cause_chain = ?e, "Failed to store a rehashed password");
+        }
+    }
+    Ok(user_id)
 }
 
 #[tracing::instrument(
@@ -51,8 +74,8 @@ pub async fn validate_credentials(
     skip(expected_password_hash, password_candidate)
 )]
 pub fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
+    expected_password_hash: &Secret<String>,
+    password_candidate: &Secret<String>,
 ) -> Result<(), AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
@@ -65,6 +88,51 @@ pub fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials)
 }
 
+/// Whether `password_hash` was computed with another algorithm, version or cost
+/// parameters than those new hashes are computed with.
+fn needs_rehash(password_hash: &Secret<String>, hashing: &PasswordHashingSettings) -> bool {
+    let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+        return true;
+    };
+    if password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(&password_hash) {
+        Ok(params) => {
+            params.m_cost() != hashing.memory_kib
+                || params.t_cost() != hashing.iterations
+                || params.p_cost() != hashing.parallelism
+        }
+        Err(_) => true,
+    }
+}
+
+/// Stores a new hash of the same password, unless the password changed in the meantime.
+#[tracing::instrument(name = "Replace password hash", skip_all, fields(%user_id))]
+async fn replace_password_hash(
+    pg_pool: &PgPool,
+    user_id: uuid::Uuid,
+    old_hash: &Secret<String>,
+    new_hash: &Secret<String>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $3
+        WHERE user_id = $1 AND password_hash = $2
+        "#,
+        user_id,
+        old_hash.expose_secret(),
+        new_hash.expose_secret(),
+    )
+    .execute(pg_pool)
+    .await
+    .context("Failed to replace the password hash.")?;
+    Ok(())
+}
+
 #[tracing::instrument(name = "Get stored credentials", skip(username, pg_pool))]
 pub async fn get_stored_credentials(
     username: &str,
@@ -74,7 +142,7 @@ pub async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND deactivated_at IS NULL
         "#,
         username,
     )
@@ -85,15 +153,18 @@ pub async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Change password", skip(pg_pool, password))]
+#[tracing::instrument(name = "Change password", skip(pg_pool, password, hashing))]
 pub async fn change_password(
     pg_pool: &PgPool,
     user_id: uuid::Uuid,
     password: Secret<String>,
+    hashing: &PasswordHashingSettings,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
+    let hashing = *hashing;
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
     sqlx::query!(
         r#"
         UPDATE users
@@ -109,14 +180,134 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> anyhow::Result<Secret<String>> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+/// Sets a new password and ends every session of the user, as part of `transaction` so
+/// that the caller can tie it to whatever authorised the reset.
+#[tracing::instrument(name = "Reset password", skip(transaction, password, hashing))]
+pub async fn reset_password(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    let hashing = *hashing;
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1, sessions_valid_after = $2
+        WHERE user_id = $3
+        "#,
+        password_hash.expose_secret(),
+        chrono::Utc::now(),
+        user_id
     )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to reset user's password in the database.")?;
+    sqlx::query!(r#"DELETE FROM user_sessions WHERE user_id = $1"#, user_id)
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the user's sessions.")?;
+    Ok(())
+}
+
+pub struct NewUser {
+    pub username: String,
+    pub password: Secret<String>,
+    pub email: Option<String>,
+    pub role: UserRole,
+}
+
+/// Stores a new admin user. Returns `None` if the username is taken.
+#[tracing::instrument(
+    name = "Create user",
+    skip(executor, user, hashing),
+    fields(username = %user.username)
+)]
+pub async fn create_user(
+    executor: impl PgExecutor<'_>,
+    user: NewUser,
+    hashing: &PasswordHashingSettings,
+) -> Result<Option<uuid::Uuid>, anyhow::Error> {
+    let password = user.password;
+    let hashing = *hashing;
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING user_id
+        "#,
+        uuid::Uuid::new_v4(),
+        user.username,
+        password_hash.expose_secret(),
+        user.email,
+        user.role.as_str(),
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to store a new user in the database.")
+}
+
+fn compute_password_hash(
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> anyhow::Result<Secret<String>> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, hashing.params()?)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{compute_password_hash, needs_rehash, verify_password_hash};
+    use crate::configuration::PasswordHashingSettings;
+
+    const HASHING: PasswordHashingSettings = PasswordHashingSettings {
+        memory_kib: 8192,
+        iterations: 2,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn hashes_computed_with_the_configured_parameters_are_kept() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let hash = compute_password_hash(password.clone(), &HASHING).unwrap();
+
+        assert!(!needs_rehash(&hash, &HASHING));
+        assert!(verify_password_hash(&hash, &password).is_ok());
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_or_algorithms_are_replaced() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let hash = compute_password_hash(password, &HASHING).unwrap();
+        let stronger = PasswordHashingSettings {
+            iterations: 3,
+            ..HASHING
+        };
+        assert!(needs_rehash(&hash, &stronger));
+
+        let argon2i = Secret::new(
+            "$argon2i$v=19$m=8192,t=2,p=1$gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+                .to_string(),
+        );
+        assert!(needs_rehash(&argon2i, &HASHING));
+        let version_16 = Secret::new(
+            "$argon2id$v=16$m=8192,t=2,p=1$gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+                .to_string(),
+        );
+        assert!(needs_rehash(&version_16, &HASHING));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::configuration::PasswordPolicySettings;
+
+/// Usernames shorter than this are not looked for in passwords.
+const MIN_USERNAME_MATCH: usize = 3;
+/// Common passwords shorter than this are not looked for inside longer passwords.
+const MIN_DICTIONARY_MATCH: usize = 4;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PasswordPolicyError {
+    #[error("Passwords must be at least {0} characters long.")]
+    TooShort(usize),
+    #[error("Passwords must be at most {0} characters long.")]
+    TooLong(usize),
+    #[error("Passwords must not contain your username.")]
+    ContainsUsername,
+    #[error("This password is one of the most common passwords. Please choose another one.")]
+    Common,
+    #[error(
+        "This password is too easy to guess. Try a longer passphrase of several unrelated words."
+    )]
+    TooWeak,
+}
+
+/// Decides which passwords admin users may choose: long enough, not built around their
+/// username, not one of a list of common passwords and hard enough to guess.
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    min_strength: u8,
+    common_passwords: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    /// Reads the common passwords from the file named in the settings, one per line.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn from_settings(settings: &PasswordPolicySettings) -> anyhow::Result<Self> {
+        let path = Path::new(&settings.common_passwords_file);
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the common passwords in {path:?}"))?;
+        Ok(Self::new(settings, contents.lines()))
+    }
+
+    pub fn new<'a>(
+        settings: &PasswordPolicySettings,
+        common_passwords: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let common_passwords = common_passwords
+            .into_iter()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Self {
+            min_length: settings.min_length,
+            max_length: settings.max_length,
+            min_strength: settings.min_strength,
+            common_passwords,
+        }
+    }
+
+    pub fn check(
+        &self,
+        password: &Secret<String>,
+        username: &str,
+    ) -> Result<(), PasswordPolicyError> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PasswordPolicyError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(PasswordPolicyError::TooLong(self.max_length));
+        }
+        let lowercase = password.to_lowercase();
+        let username = username.trim().to_lowercase();
+        if username.chars().count() >= MIN_USERNAME_MATCH && lowercase.contains(&username) {
+            return Err(PasswordPolicyError::ContainsUsername);
+        }
+        if self.common_passwords.contains(&lowercase) {
+            return Err(PasswordPolicyError::Common);
+        }
+        if self.strength(password) < self.min_strength {
+            return Err(PasswordPolicyError::TooWeak);
+        }
+        Ok(())
+    }
+
+    /// A score from 0 to 4 after the one of zxcvbn, from an estimate of how many guesses
+    /// an attacker would need: common passwords inside the password cost as much as
+    /// picking one from the list, repeated characters and runs like `abc` or `123` are
+    /// almost free, and any other character costs a pick from the character classes used.
+    pub fn strength(&self, password: &str) -> u8 {
+        let chars = password.chars().collect::<Vec<_>>();
+        let lowercase = chars
+            .iter()
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect::<Vec<_>>();
+        let dictionary_bits = (self.common_passwords.len().max(1) as f64).log2();
+        let pool_bits = (pool_size(&chars) as f64).log2();
+
+        let mut bits = 0.0;
+        let mut i = 0;
+        while i < chars.len() {
+            if let Some(length) = self.longest_common_password_at(&lowercase[i..]) {
+                let capitalised = chars[i..i + length].iter().any(|c| c.is_uppercase());
+                bits += dictionary_bits + if capitalised { 1.0 } else { 0.0 };
+                i += length;
+                continue;
+            }
+            let predictable = i > 0 && {
+                let (previous, current) = (chars[i - 1] as i64, chars[i] as i64);
+                (previous - current).abs() <= 1
+            };
+            bits += if predictable { 1.0 } else { pool_bits };
+            i += 1;
+        }
+        // 10^3, 10^6, 10^8 and 10^10 guesses, zxcvbn's thresholds
+        match bits {
+            b if b < 10.0 => 0,
+            b if b < 20.0 => 1,
+            b if b < 26.6 => 2,
+            b if b < 33.3 => 3,
+            _ => 4,
+        }
+    }
+
+    fn longest_common_password_at(&self, chars: &[char]) -> Option<usize> {
+        (MIN_DICTIONARY_MATCH..=chars.len()).rev().find(|length| {
+            self.common_passwords
+                .contains(&chars[..*length].iter().collect::<String>())
+        })
+    }
+}
+
+/// How many characters an attacker has to try for each position, given the classes of
+/// characters the password uses.
+fn pool_size(chars: &[char]) -> u32 {
+    let mut pool = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        pool += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    pool.max(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+
+    use super::{PasswordPolicy, PasswordPolicyError};
+    use crate::configuration::PasswordPolicySettings;
+
+    fn policy() -> PasswordPolicy {
+        let settings = PasswordPolicySettings {
+            min_length: 12,
+            max_length: 64,
+            min_strength: 3,
+            common_passwords_file: String::new(),
+        };
+        let common = "# comment\npassword\nqwerty\nletmein\nsunshine\nPassword1234\n";
+        PasswordPolicy::new(&settings, common.lines())
+    }
+
+    fn check(password: &str) -> Result<(), PasswordPolicyError> {
+        policy().check(&Secret::new(password.to_string()), "ursula")
+    }
+
+    #[test]
+    fn lengths_are_counted_in_characters() {
+        assert_err_eq!(check("short"), PasswordPolicyError::TooShort(12));
+        assert_err_eq!(check(&"x".repeat(65)), PasswordPolicyError::TooLong(64));
+        assert_ok!(check("éèêëēėęîïíīįìô"));
+    }
+
+    #[test]
+    fn passwords_containing_the_username_are_rejected() {
+        assert_err_eq!(
+            check("my name is URSULA, hi"),
+            PasswordPolicyError::ContainsUsername
+        );
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_regardless_of_case() {
+        let policy = policy();
+        let password = Secret::new("PASSWORD1234".to_string());
+        assert_err_eq!(policy.check(&password, "ab"), PasswordPolicyError::Common);
+    }
+
+    #[test]
+    fn predictable_passwords_are_too_weak() {
+        for password in [
+            "aaaaaaaaaaaaaaaa",
+            "abcdefghijklmnop",
+            "123456789012345",
+            "Sunshine12345678",
+            "qwertyqwertyqwerty",
+            "letmein-password",
+        ] {
+            assert_err_eq!(check(password), PasswordPolicyError::TooWeak, "{password}");
+        }
+    }
+
+    #[test]
+    fn passphrases_and_random_passwords_are_accepted() {
+        for password in [
+            "correct horse battery staple",
+            "Tr0ub4dor&3-xyz",
+            "h7$kQ9!mZ2pL",
+            "9f2c1d3e-4b5a-4c6d-8e7f-0a1b2c3d4e5f",
+        ] {
+            assert_ok!(check(password), "{password}");
+        }
+    }
+
+    #[test]
+    fn scores_grow_with_unpredictability() {
+        let policy = policy();
+        assert_eq!(policy.strength("password"), 0);
+        assert_eq!(policy.strength("zzzzzz"), 0);
+        assert_eq!(policy.strength("Kx9#mQ2!vB7@nR4$"), 4);
+    }
+}
@@ -0,0 +1,131 @@
+use std::net::IpAddr;
+
+use anyhow::Context;
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::session_state::{TypedSession, SESSION_INACTIVITY_TIMEOUT_SECONDS};
+
+/// User agents longer than this are cut short before being stored.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+/// A logged in session, as listed to its user.
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub ip: String,
+    pub user_agent: Option<String>,
+}
+
+pub fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect())
+}
+
+/// Records a new session for the user and logs `session` in to it.
+#[tracing::instrument(name = "Start a session", skip(pg_pool, session, user_agent))]
+pub async fn start_session(
+    pg_pool: &PgPool,
+    session: &TypedSession,
+    user_id: Uuid,
+    ip: IpAddr,
+    user_agent: Option<String>,
+) -> anyhow::Result<()> {
+    let session_id = Uuid::new_v4();
+    let mut transaction = pg_pool.begin().await?;
+    // Sessions that expired from Redis are not listed anymore
+    sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1 AND last_seen_at < now() - make_interval(secs => $2)
+        "#,
+        user_id,
+        SESSION_INACTIVITY_TIMEOUT_SECONDS as f64,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete expired sessions")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (session_id, user_id, created_at, last_seen_at, ip, user_agent)
+        VALUES ($1, $2, now(), now(), $3, $4)
+        "#,
+        session_id,
+        user_id,
+        ip.to_string(),
+        user_agent,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the session")?;
+    transaction.commit().await?;
+
+    session.renew();
+    session.insert_user_id(user_id, session_id)
+}
+
+/// The user's sessions, most recently used first.
+#[tracing::instrument(name = "List sessions", skip(pg_pool))]
+pub async fn active_sessions(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+) -> anyhow::Result<Vec<ActiveSession>> {
+    sqlx::query_as!(
+        ActiveSession,
+        r#"
+        SELECT session_id, created_at, last_seen_at, ip, user_agent
+        FROM user_sessions
+        WHERE user_id = $1 AND last_seen_at >= now() - make_interval(secs => $2)
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        SESSION_INACTIVITY_TIMEOUT_SECONDS as f64,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .context("Failed to list sessions")
+}
+
+/// Logs out one of the user's sessions. Returns whether it existed.
+#[tracing::instrument(name = "Revoke a session", skip(pg_pool))]
+pub async fn revoke_session(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> anyhow::Result<bool> {
+    let result = sqlx::query!(
+        r#"DELETE FROM user_sessions WHERE user_id = $1 AND session_id = $2"#,
+        user_id,
+        session_id,
+    )
+    .execute(pg_pool)
+    .await
+    .context("Failed to revoke the session")?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Logs out every session of the user but `keep`. Returns how many were logged out.
+#[tracing::instrument(name = "Revoke other sessions", skip(pg_pool))]
+pub async fn revoke_other_sessions(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> anyhow::Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE user_id = $1 AND session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep,
+    )
+    .execute(pg_pool)
+    .await
+    .context("Failed to revoke sessions")?;
+    Ok(result.rows_affected())
+}
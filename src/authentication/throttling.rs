@@ -0,0 +1,130 @@
+use std::net::IpAddr;
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::configuration::LoginThrottlingSettings;
+
+/// The counters a login attempt is checked against. Unknown usernames are counted like
+/// existing ones, so that throttling does not reveal which accounts exist.
+pub fn login_failure_keys(username: &str, ip: IpAddr) -> [String; 2] {
+    [format!("username:{username}"), format!("ip:{ip}")]
+}
+
+#[tracing::instrument(name = "Check whether logins are blocked", skip(pg_pool))]
+pub async fn login_is_blocked(pg_pool: &PgPool, keys: &[String]) -> anyhow::Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM login_failures WHERE key = ANY($1) AND blocked_until > now()
+        ) as "blocked!"
+        "#,
+        keys,
+    )
+    .fetch_one(pg_pool)
+    .await
+    .context("Failed to look up login failures")
+}
+
+/// Counts a failed login against every key and blocks further attempts once there have
+/// been too many. Failures older than a lockout are forgotten.
+#[tracing::instrument(name = "Record a failed login", skip(pg_pool, settings))]
+pub async fn record_login_failure(
+    pg_pool: &PgPool,
+    keys: &[String],
+    settings: &LoginThrottlingSettings,
+) -> anyhow::Result<()> {
+    let memory = (settings.lockout_minutes * 60) as f64;
+    for key in keys {
+        let n_failures = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_failures (key, n_failures, last_failure_at)
+            VALUES ($1, 1, now())
+            ON CONFLICT (key) DO UPDATE
+            SET n_failures = CASE
+                    WHEN login_failures.last_failure_at < now() - make_interval(secs => $2)
+                    THEN 1
+                    ELSE login_failures.n_failures + 1
+                END,
+                last_failure_at = now()
+            RETURNING n_failures
+            "#,
+            key,
+            memory,
+        )
+        .fetch_one(pg_pool)
+        .await
+        .context("Failed to count a failed login")?;
+
+        if let Some(block_for) = settings.block_for(n_failures) {
+            tracing::warn!(key, n_failures, "Blocking logins after repeated failures");
+            sqlx::query!(
+                r#"
+                UPDATE login_failures
+                SET blocked_until = now() + make_interval(secs => $2)
+                WHERE key = $1
+                "#,
+                key,
+                block_for.num_seconds() as f64,
+            )
+            .execute(pg_pool)
+            .await
+            .context("Failed to block logins")?;
+        }
+    }
+    Ok(())
+}
+
+/// Forgets the failures of a username once its owner has logged in.
+#[tracing::instrument(name = "Clear login failures", skip(pg_pool))]
+pub async fn clear_login_failures(pg_pool: &PgPool, key: &str) -> anyhow::Result<()> {
+    sqlx::query!(r#"DELETE FROM login_failures WHERE key = $1"#, key)
+        .execute(pg_pool)
+        .await
+        .context("Failed to clear login failures")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::configuration::LoginThrottlingSettings;
+
+    const SETTINGS: LoginThrottlingSettings = LoginThrottlingSettings {
+        free_attempts: 3,
+        base_delay_seconds: 2,
+        max_failures: 10,
+        lockout_minutes: 15,
+    };
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for n_failures in 1..=3 {
+            assert_eq!(SETTINGS.block_for(n_failures), None);
+        }
+    }
+
+    #[test]
+    fn the_delay_doubles_after_each_further_failure() {
+        assert_eq!(SETTINGS.block_for(4), Some(Duration::seconds(2)));
+        assert_eq!(SETTINGS.block_for(5), Some(Duration::seconds(4)));
+        assert_eq!(SETTINGS.block_for(9), Some(Duration::seconds(64)));
+    }
+
+    #[test]
+    fn too_many_failures_lock_out() {
+        assert_eq!(SETTINGS.block_for(10), Some(Duration::minutes(15)));
+        assert_eq!(SETTINGS.block_for(1000), Some(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn the_delay_never_exceeds_a_lockout() {
+        let settings = LoginThrottlingSettings {
+            max_failures: 100,
+            ..SETTINGS
+        };
+        assert_eq!(settings.block_for(20), Some(Duration::minutes(15)));
+        assert_eq!(settings.block_for(99), Some(Duration::minutes(15)));
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::Url;
+use secrecy::{ExposeSecret, SecretVec};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: usize = 6;
+/// Codes of the neighbouring time steps are accepted too, to allow for clock drift.
+const ALLOWED_DRIFT: i64 = 1;
+const SECRET_BYTES: usize = 20;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Time-based one-time passwords (RFC 6238) as generated by authenticator apps: six
+/// digits derived from a shared secret and the current 30 second time step.
+pub struct Totp(SecretVec<u8>);
+
+impl Totp {
+    pub fn generate() -> Self {
+        let mut secret = vec![0; SECRET_BYTES];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self(SecretVec::new(secret))
+    }
+
+    /// Parses a secret as shown to users: base32, case and spaces do not matter.
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        let secret = secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .collect::<String>()
+            .to_uppercase();
+        let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+        (!secret.is_empty()).then(|| Self(SecretVec::new(secret)))
+    }
+
+    pub fn to_base32(&self) -> String {
+        BASE32_NOPAD.encode(self.0.expose_secret())
+    }
+
+    pub fn step(time: DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(STEP_SECONDS)
+    }
+
+    pub fn code(&self, step: i64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.0.expose_secret())
+            .expect("HMAC can take a key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+        format!(
+            "{:0width$}",
+            binary % 10_u32.pow(DIGITS as u32),
+            width = DIGITS
+        )
+    }
+
+    /// The time step `code` was generated for, unless it is wrong or the step is not after
+    /// `last_step`, the step of the last code accepted, so that codes cannot be replayed.
+    pub fn verify(&self, code: &str, now: DateTime<Utc>, last_step: Option<i64>) -> Option<i64> {
+        let code = code.trim().replace(' ', "");
+        if code.len() != DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let current = Self::step(now);
+        (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT)
+            .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+            .find(|step| self.code(*step) == code)
+    }
+
+    /// The URI that authenticator apps read from a QR code.
+    pub fn otpauth_uri(&self, issuer: &str, account: &str) -> String {
+        let mut uri = Url::parse("otpauth://totp/").expect("A valid URL");
+        uri.set_path(&format!("{issuer}:{account}"));
+        uri.query_pairs_mut()
+            .append_pair("secret", &self.to_base32())
+            .append_pair("issuer", issuer)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &DIGITS.to_string())
+            .append_pair("period", &STEP_SECONDS.to_string());
+        uri.to_string()
+    }
+}
+
+/// Codes like `k3j9x-p2m4q`, to be written down by users when they enable two-factor
+/// authentication. Each can replace a TOTP code once.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0; 6];
+            rng.fill_bytes(&mut bytes);
+            let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let code = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
+
+#[tracing::instrument(
+    name = "Check whether two-factor authentication is enabled",
+    skip(pg_pool)
+)]
+pub async fn two_factor_enabled(pg_pool: &PgPool, user_id: Uuid) -> anyhow::Result<bool> {
+    let enabled = sqlx::query_scalar!(
+        r#"SELECT totp_secret IS NOT NULL as "enabled!" FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to look up two-factor authentication")?;
+    Ok(enabled.unwrap_or(false))
+}
+
+/// Stores the secret the user's authenticator was set up with, `step` being the step of
+/// the code that proved it, and replaces the recovery codes.
+#[tracing::instrument(
+    name = "Enable two-factor authentication",
+    skip(pg_pool, totp, recovery_codes)
+)]
+pub async fn enable_two_factor(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    totp: &Totp,
+    step: i64,
+    recovery_codes: &[String],
+) -> anyhow::Result<()> {
+    let mut transaction = pg_pool.begin().await?;
+    sqlx::query!(
+        r#"UPDATE users SET totp_secret = $2, totp_last_step = $3 WHERE user_id = $1"#,
+        user_id,
+        totp.to_base32(),
+        step,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the TOTP secret")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete old recovery codes")?;
+    let code_hashes = recovery_codes
+        .iter()
+        .map(|code| hash_recovery_code(code))
+        .collect::<Vec<_>>();
+    sqlx::query!(
+        r#"
+        INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &code_hashes,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store recovery codes")?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pg_pool))]
+pub async fn disable_two_factor(pg_pool: &PgPool, user_id: Uuid) -> anyhow::Result<()> {
+    let mut transaction = pg_pool.begin().await?;
+    sqlx::query!(
+        r#"UPDATE users SET totp_secret = NULL, totp_last_step = NULL WHERE user_id = $1"#,
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the TOTP secret")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete recovery codes")?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Count unused recovery codes", skip(pg_pool))]
+pub async fn unused_recovery_codes(pg_pool: &PgPool, user_id: Uuid) -> anyhow::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT count(*) as "count!" FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id,
+    )
+    .fetch_one(pg_pool)
+    .await
+    .context("Failed to count recovery codes")
+}
+
+/// Checks a TOTP code or an unused recovery code. Either is only accepted once.
+#[tracing::instrument(name = "Verify second factor", skip(pg_pool, code))]
+pub async fn verify_second_factor(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> anyhow::Result<bool> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_last_step FROM users WHERE user_id = $1"#,
+        user_id,
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to look up the TOTP secret")?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let totp = row
+        .totp_secret
+        .as_deref()
+        .and_then(Totp::from_base32)
+        .context("Two-factor authentication is not enabled")?;
+
+    if let Some(step) = totp.verify(code, Utc::now(), row.totp_last_step) {
+        // Guards against the same code being submitted twice concurrently
+        let result = sqlx::query!(
+            r#"
+            UPDATE users SET totp_last_step = $2
+            WHERE user_id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+            "#,
+            user_id,
+            step,
+        )
+        .execute(pg_pool)
+        .await
+        .context("Failed to record the TOTP code as used")?;
+        return Ok(result.rows_affected() == 1);
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(pg_pool)
+    .await
+    .context("Failed to record the recovery code as used")?;
+    Ok(result.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{generate_recovery_codes, hash_recovery_code, Totp};
+
+    /// The SHA-1 secret of the RFC 6238 test vectors
+    fn rfc_totp() -> Totp {
+        Totp::from_base32(&data_encoding::BASE32_NOPAD.encode(b"12345678901234567890")).unwrap()
+    }
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        let totp = rfc_totp();
+        for (timestamp, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            let step = Totp::step(Utc.timestamp_opt(timestamp, 0).unwrap());
+            assert_eq!(totp.code(step), code);
+        }
+    }
+
+    #[test]
+    fn codes_of_neighbouring_steps_are_accepted_once() {
+        let totp = rfc_totp();
+        let now = Utc.timestamp_opt(1111111109, 0).unwrap();
+        let step = Totp::step(now);
+
+        assert_eq!(totp.verify("081804", now, None), Some(step));
+        assert_eq!(totp.verify(&totp.code(step - 1), now, None), Some(step - 1));
+        assert_eq!(totp.verify(&totp.code(step + 1), now, None), Some(step + 1));
+        assert_eq!(totp.verify(&totp.code(step - 2), now, None), None);
+        assert_eq!(totp.verify("081804", now, Some(step)), None);
+        assert_eq!(totp.verify("not a code", now, None), None);
+    }
+
+    #[test]
+    fn secrets_are_read_regardless_of_case_and_spacing() {
+        let totp = Totp::generate();
+        let shown = totp.to_base32().to_lowercase();
+        let (head, tail) = shown.split_at(8);
+
+        let parsed = Totp::from_base32(&format!("{head} {tail}")).unwrap();
+
+        assert_eq!(parsed.to_base32(), totp.to_base32());
+        assert!(Totp::from_base32("not base32!").is_none());
+    }
+
+    #[test]
+    fn the_otpauth_uri_names_the_issuer_and_account() {
+        let totp = rfc_totp();
+
+        let uri = totp.otpauth_uri("Newsletter", "ursula le guin");
+
+        assert!(uri.starts_with("otpauth://totp/Newsletter:ursula%20le%20guin?secret="));
+        assert!(uri.contains("&issuer=Newsletter"));
+    }
+
+    #[test]
+    fn recovery_codes_are_distinct_and_hashed_loosely() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), 10);
+        assert!(codes.iter().all(|code| code.len() == 11));
+        let code = &codes[0];
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&code.to_uppercase().replace('-', " "))
+        );
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    captcha::CaptchaVerifier,
+    configuration::SignupSettings,
+    domain::{FormToken, FormTokenError},
+    routes::error_chain_fmt,
+    HmacSecret,
+};
+
+/// Guards the public subscribe form: every submission must carry a form token of the
+/// right age and, when a CAPTCHA is configured, a solved CAPTCHA.
+pub struct BotProtection {
+    min_form_age: Duration,
+    max_form_age: Duration,
+    captcha: Option<Box<dyn CaptchaVerifier>>,
+}
+
+#[derive(thiserror::Error)]
+pub enum BotProtectionError {
+    #[error("Please reload the page and submit the form again.")]
+    InvalidForm(#[source] FormTokenError),
+    #[error("The form was submitted too quickly. Please try again.")]
+    TooFast,
+    #[error("The form has expired. Please reload the page and try again.")]
+    Expired,
+    #[error("Please complete the CAPTCHA.")]
+    CaptchaFailed,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for BotProtectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<FormTokenError> for BotProtectionError {
+    fn from(e: FormTokenError) -> Self {
+        match e {
+            FormTokenError::TooFast => Self::TooFast,
+            FormTokenError::Expired => Self::Expired,
+            e => Self::InvalidForm(e),
+        }
+    }
+}
+
+impl BotProtection {
+    pub fn new(settings: &SignupSettings) -> Self {
+        Self {
+            min_form_age: Duration::seconds(settings.min_form_seconds),
+            max_form_age: Duration::seconds(settings.max_form_seconds),
+            captcha: settings.captcha.clone().map(|captcha| captcha.verifier()),
+        }
+    }
+
+    pub fn has_captcha(&self) -> bool {
+        self.captcha.is_some()
+    }
+
+    /// A token for a form rendered now.
+    pub fn form_token(&self, secret: &HmacSecret) -> String {
+        FormToken::issue(Utc::now(), secret)
+    }
+
+    #[tracing::instrument(name = "Check a subscribe form for bots", skip_all)]
+    pub async fn check(
+        &self,
+        form_token: &str,
+        captcha_response: &str,
+        secret: &HmacSecret,
+    ) -> Result<(), BotProtectionError> {
+        FormToken::verify(form_token, secret, self.min_form_age, self.max_form_age)?;
+        if let Some(captcha) = &self.captcha {
+            if !captcha.verify(captcha_response).await? {
+                return Err(BotProtectionError::CaptchaFailed);
+            }
+        }
+        Ok(())
+    }
+}
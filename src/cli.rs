@@ -0,0 +1,143 @@
+use std::io::BufRead;
+
+use secrecy::Secret;
+
+use crate::{
+    authentication::{create_user, NewUser, PasswordPolicy},
+    configuration::Settings,
+    domain::{SubscriberEmail, UserRole},
+    email_normalisation::normalise_stored_emails,
+    startup::get_connection_pool,
+};
+
+pub const CREATE_USER_USAGE: &str =
+    "Usage: zerotoprod create-user <username> [--role owner|editor|viewer] [--email <address>]
+The password is read from the first line of standard input.";
+
+/// The arguments of `zerotoprod create-user`, which adds an admin user, for instance the
+/// first owner of a new deployment.
+#[derive(Debug)]
+pub struct CreateUserArgs {
+    pub username: String,
+    pub role: UserRole,
+    pub email: Option<SubscriberEmail>,
+}
+
+impl CreateUserArgs {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut username = None;
+        let mut role = UserRole::Owner;
+        let mut email = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--role" => {
+                    let value = args.next().ok_or("--role needs a value.")?;
+                    role = UserRole::parse(value)?;
+                }
+                "--email" => {
+                    let value = args.next().ok_or("--email needs a value.")?;
+                    email = Some(SubscriberEmail::parse(value.clone())?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}.")),
+                name if username.is_none() => username = Some(name.trim().to_string()),
+                extra => return Err(format!("Unexpected argument {extra}.")),
+            }
+        }
+        let username = username
+            .filter(|username| !username.is_empty())
+            .ok_or("A username is required.")?;
+        Ok(Self {
+            username,
+            role,
+            email,
+        })
+    }
+}
+
+/// Creates the user described by `args`. Passwords are read from standard input, rather
+/// than taken as an argument, so that they stay out of the shell history.
+pub async fn create_user_command(configuration: Settings, args: &[String]) -> anyhow::Result<()> {
+    let args =
+        CreateUserArgs::parse(args).map_err(|e| anyhow::anyhow!("{e}\n{CREATE_USER_USAGE}"))?;
+    let policy = PasswordPolicy::from_settings(&configuration.password_policy)?;
+
+    eprint!("Password for {}: ", args.username);
+    let mut password = String::new();
+    std::io::stdin().lock().read_line(&mut password)?;
+    let password = Secret::new(password.trim_end_matches(['\r', '\n']).to_string());
+    policy.check(&password, &args.username)?;
+
+    let pg_pool = get_connection_pool(&configuration.database);
+    let user = NewUser {
+        username: args.username.clone(),
+        password,
+        email: args.email.map(|email| email.as_ref().to_string()),
+        role: args.role,
+    };
+    match create_user(&pg_pool, user, &configuration.password_hashing).await? {
+        Some(user_id) => {
+            println!("Created {} {} ({user_id}).", args.role, args.username);
+            Ok(())
+        }
+        None => anyhow::bail!("The username {} is already taken.", args.username),
+    }
+}
+
+/// Rewrites stored addresses that predate the current normalisation settings. Run it once
+/// after upgrading, and again after setting `lowercase_local_part`.
+pub async fn normalise_emails_command(configuration: Settings) -> anyhow::Result<()> {
+    let pg_pool = get_connection_pool(&configuration.database);
+    let report =
+        normalise_stored_emails(&pg_pool, configuration.application.email_normalisation).await?;
+    println!(
+        "Rewrote {} address(es) and merged {} duplicate subscriber(s).",
+        report.rewritten, report.merged
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::CreateUserArgs;
+    use crate::domain::UserRole;
+
+    fn parse(args: &[&str]) -> Result<CreateUserArgs, String> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        CreateUserArgs::parse(&args)
+    }
+
+    #[test]
+    fn users_are_owners_without_an_email_by_default() {
+        let args = assert_ok!(parse(&["ursula"]));
+        assert_eq!(args.username, "ursula");
+        assert_eq!(args.role, UserRole::Owner);
+        assert!(args.email.is_none());
+    }
+
+    #[test]
+    fn the_role_and_email_can_be_given_in_any_order() {
+        let args = assert_ok!(parse(&[
+            "--email",
+            "ursula@example.com",
+            "ursula",
+            "--role",
+            "editor"
+        ]));
+        assert_eq!(args.username, "ursula");
+        assert_eq!(args.role, UserRole::Editor);
+        assert_eq!(args.email.unwrap().as_ref(), "ursula@example.com");
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert_err!(parse(&[]));
+        assert_err!(parse(&["ursula", "le guin"]));
+        assert_err!(parse(&["ursula", "--role", "admin"]));
+        assert_err!(parse(&["ursula", "--role"]));
+        assert_err!(parse(&["ursula", "--email", "not an email"]));
+        assert_err!(parse(&["ursula", "--force"]));
+    }
+}
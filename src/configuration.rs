@@ -1,11 +1,14 @@
+use crate::captcha::CaptchaSettings;
 use crate::email_client::EmailClient;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
+use std::net::IpAddr;
+use std::time::Duration;
 
-use crate::domain::{ApplicationBaseUrl, Subscriber};
+use crate::domain::{ApplicationBaseUrl, EmailNormalisation, Subscriber};
 
 #[derive(Deserialize, Clone)]
 pub struct Settings {
@@ -14,6 +17,11 @@ pub struct Settings {
     pub telemetry: TelemetrySettings,
     pub email: EmailSettings,
     pub redis: RedisSettings,
+    pub signup: SignupSettings,
+    pub rate_limits: RateLimitSettings,
+    pub login_throttling: LoginThrottlingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub password_hashing: PasswordHashingSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -34,6 +42,8 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: ApplicationBaseUrl,
     pub hmac_secret: Secret<String>,
+    #[serde(default)]
+    pub email_normalisation: EmailNormalisation,
 }
 
 #[derive(Deserialize, Clone)]
@@ -56,6 +66,120 @@ pub struct RedisSettings {
     pub uri: Secret<String>,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct SignupSettings {
+    pub disposable_domains_file: String,
+    /// Subscribe forms posted sooner than this after being rendered are from bots.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_form_seconds: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_form_seconds: i64,
+    #[serde(default)]
+    pub captcha: Option<CaptchaSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RateLimitSettings {
+    /// Prepended to every Redis key, so that several deployments can share a Redis.
+    pub key_prefix: String,
+    /// Reverse proxies whose `X-Forwarded-For` header tells who the client is.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    pub login: RouteLimits,
+    pub subscriptions: RouteLimits,
+}
+
+/// The limits applied to a group of routes. Only form submissions count against them.
+#[derive(Deserialize, Clone, Default)]
+pub struct RouteLimits {
+    #[serde(default)]
+    pub per_ip: Option<Quota>,
+    /// Keyed on the `email` field of submitted forms.
+    #[serde(default)]
+    pub per_email: Option<Quota>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Quota {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub requests: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl Quota {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+}
+
+/// Failed logins are counted per username and per client IP. After `free_attempts`
+/// failures further attempts must wait, twice as long after each new failure, and after
+/// `max_failures` they are locked out until the counter expires or an admin unlocks them.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct LoginThrottlingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub free_attempts: i32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_delay_seconds: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures: i32,
+    /// How long a lockout lasts, and how long failures are remembered.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_minutes: i64,
+}
+
+impl LoginThrottlingSettings {
+    /// How long to turn away attempts after the `n_failures`-th failure in a row.
+    pub fn block_for(&self, n_failures: i32) -> Option<chrono::Duration> {
+        let lockout = chrono::Duration::minutes(self.lockout_minutes);
+        if n_failures >= self.max_failures {
+            return Some(lockout);
+        }
+        let n_delayed = n_failures - self.free_attempts;
+        if n_delayed <= 0 {
+            return None;
+        }
+        let delay = 2_i64
+            .checked_pow(n_delayed as u32 - 1)
+            .and_then(|factor| self.base_delay_seconds.checked_mul(factor))
+            .map_or(lockout, chrono::Duration::seconds);
+        Some(delay.min(lockout))
+    }
+}
+
+/// What admin passwords must look like. `min_strength` is a score from 0 (guessed in a
+/// few attempts) to 4 (very hard to guess), see [`crate::authentication::PasswordPolicy`].
+#[derive(Deserialize, Clone, Debug)]
+pub struct PasswordPolicySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_length: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_length: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_strength: u8,
+    pub common_passwords_file: String,
+}
+
+/// Argon2id cost parameters for new password hashes. Stored hashes computed with other
+/// parameters are replaced the next time their user logs in.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> anyhow::Result<argon2::Params> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid password hashing parameters: {e}"))
+    }
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine current directory");
     let configuration_directory = base_path.join("configuration");
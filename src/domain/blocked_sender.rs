@@ -0,0 +1,118 @@
+use std::fmt;
+
+use super::SubscriberEmail;
+
+/// A domain, or a single address, that may not be used to subscribe. Blocking a domain
+/// also blocks its subdomains.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockedSender {
+    Domain(String),
+    Address(String),
+}
+
+impl AsRef<str> for BlockedSender {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Domain(domain) => domain,
+            Self::Address(address) => address,
+        }
+    }
+}
+
+impl fmt::Display for BlockedSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl BlockedSender {
+    pub fn parse(s: String) -> Result<Self, String> {
+        let s = s.trim();
+        if s.contains('@') {
+            let email = SubscriberEmail::parse(s.to_owned())?;
+            return Ok(Self::Address(email.as_ref().to_lowercase()));
+        }
+        match idna::domain_to_ascii(s) {
+            Ok(domain) if is_valid_domain(&domain) => Ok(Self::Domain(domain)),
+            _ => Err(format!("{s} is not a valid domain or email address.")),
+        }
+    }
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// The domain of `email` followed by each of its parent domains, so that blocking
+/// `example.com` also catches `mail.example.com`.
+pub fn domain_suffixes(email: &SubscriberEmail) -> Vec<String> {
+    let domain = email
+        .as_ref()
+        .rsplit_once('@')
+        .map_or("", |(_, domain)| domain);
+    let mut suffixes = vec![domain.to_owned()];
+    let mut rest = domain;
+    while let Some((_, parent)) = rest.split_once('.') {
+        suffixes.push(parent.to_owned());
+        rest = parent;
+    }
+    suffixes
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+
+    use super::domain_suffixes;
+    use crate::domain::{BlockedSender, SubscriberEmail};
+
+    #[test]
+    fn domains_are_lowercased() {
+        assert_eq!(
+            BlockedSender::parse(" Mailinator.COM ".into()),
+            Ok(BlockedSender::Domain("mailinator.com".into()))
+        );
+    }
+
+    #[test]
+    fn internationalised_domains_are_stored_as_punycode() {
+        assert_eq!(
+            BlockedSender::parse("bücher.example".into()),
+            Ok(BlockedSender::Domain("xn--bcher-kva.example".into()))
+        );
+    }
+
+    #[test]
+    fn addresses_are_lowercased() {
+        assert_eq!(
+            BlockedSender::parse("Spammer@Example.com".into()),
+            Ok(BlockedSender::Address("spammer@example.com".into()))
+        );
+    }
+
+    #[test]
+    fn malformed_entries_are_rejected() {
+        assert_err!(BlockedSender::parse("".into()));
+        assert_err!(BlockedSender::parse("exa mple.com".into()));
+        assert_err!(BlockedSender::parse("example..com".into()));
+        assert_err!(BlockedSender::parse("@example.com".into()));
+    }
+
+    #[test]
+    fn suffixes_run_from_the_full_domain_to_the_top_level_domain() {
+        let email = SubscriberEmail::parse("ursula@mail.example.com".into()).unwrap();
+        assert_eq!(
+            domain_suffixes(&email),
+            vec!["mail.example.com", "example.com", "com"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+
+/// How often a subscriber wants to hear from us. Issues for subscribers on a digest are
+/// held back until the start of the next day or week (UTC), then sent together in one email.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigestFrequency {
+    Immediate,
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub const ALL: [Self; 3] = [Self::Immediate, Self::Daily, Self::Weekly];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|frequency| frequency.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a valid digest frequency."))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Immediate => "As soon as an issue is published",
+            Self::Daily => "Once a day",
+            Self::Weekly => "Once a week",
+        }
+    }
+
+    /// When an issue published at `published_at` should go out: straight away, or at the
+    /// next midnight, or the next Monday midnight, for digests.
+    pub fn next_delivery(&self, published_at: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = published_at
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc();
+        match self {
+            Self::Immediate => published_at,
+            Self::Daily => midnight + Duration::days(1),
+            Self::Weekly => {
+                let days_since_monday = published_at.weekday().num_days_from_monday() as i64;
+                midnight + Duration::days(7 - days_since_monday)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DigestFrequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use claims::{assert_err, assert_ok_eq};
+
+    use crate::domain::DigestFrequency;
+
+    #[test]
+    fn every_frequency_parses_from_its_own_name() {
+        for frequency in DigestFrequency::ALL {
+            assert_ok_eq!(DigestFrequency::parse(frequency.as_str()), frequency);
+        }
+    }
+
+    #[test]
+    fn unknown_frequencies_are_rejected() {
+        assert_err!(DigestFrequency::parse("hourly"));
+        assert_err!(DigestFrequency::parse(""));
+    }
+
+    #[test]
+    fn digests_go_out_at_the_start_of_the_next_period() {
+        // A Wednesday
+        let published_at: DateTime<Utc> = "2024-01-31T10:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            DigestFrequency::Immediate.next_delivery(published_at),
+            published_at
+        );
+        assert_eq!(
+            DigestFrequency::Daily
+                .next_delivery(published_at)
+                .to_rfc3339(),
+            "2024-02-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            DigestFrequency::Weekly
+                .next_delivery(published_at)
+                .to_rfc3339(),
+            "2024-02-05T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn weekly_digests_published_on_a_monday_wait_for_the_next_one() {
+        let published_at: DateTime<Utc> = "2024-02-05T00:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            DigestFrequency::Weekly
+                .next_delivery(published_at)
+                .to_rfc3339(),
+            "2024-02-12T00:00:00+00:00"
+        );
+    }
+}
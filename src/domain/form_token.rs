@@ -0,0 +1,130 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::HmacSecret;
+
+const PURPOSE: &str = "subscribe_form";
+
+/// A signed timestamp embedded in the subscribe form. People take a few seconds to fill
+/// the form in while bots post straight away, and stale forms are turned away. Encoded as
+/// `base64(subscribe_form:issued_at).base64(tag)`.
+pub struct FormToken;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum FormTokenError {
+    #[error("The form token is malformed.")]
+    Malformed,
+    #[error("The form token signature is invalid.")]
+    InvalidSignature,
+    #[error("The form was submitted too quickly.")]
+    TooFast,
+    #[error("The form has expired.")]
+    Expired,
+}
+
+impl FormToken {
+    pub fn issue(issued_at: DateTime<Utc>, secret: &HmacSecret) -> String {
+        let payload = format!("{PURPOSE}:{}", issued_at.timestamp());
+        let tag = secret.sign(payload.as_bytes());
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(tag)
+        )
+    }
+
+    /// Returns when the form was issued if it is between `min_age` and `max_age` old.
+    pub fn verify(
+        token: &str,
+        secret: &HmacSecret,
+        min_age: Duration,
+        max_age: Duration,
+    ) -> Result<DateTime<Utc>, FormTokenError> {
+        let (payload, tag) = token.split_once('.').ok_or(FormTokenError::Malformed)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| FormTokenError::Malformed)?;
+        let tag = URL_SAFE_NO_PAD
+            .decode(tag)
+            .map_err(|_| FormTokenError::Malformed)?;
+        if !secret.verify(&payload, &tag) {
+            return Err(FormTokenError::InvalidSignature);
+        }
+
+        let payload = String::from_utf8(payload).map_err(|_| FormTokenError::Malformed)?;
+        let issued_at = payload
+            .strip_prefix(PURPOSE)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .ok_or(FormTokenError::InvalidSignature)?
+            .parse()
+            .ok()
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .ok_or(FormTokenError::Malformed)?;
+        let age = Utc::now() - issued_at;
+        if age < min_age {
+            return Err(FormTokenError::TooFast);
+        }
+        if age > max_age {
+            return Err(FormTokenError::Expired);
+        }
+        Ok(issued_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+
+    use super::{FormToken, FormTokenError};
+    use crate::HmacSecret;
+
+    fn secret(s: &str) -> HmacSecret {
+        HmacSecret(Secret::new(s.to_string()))
+    }
+
+    fn verify(token: &str, secret: &HmacSecret) -> Result<(), FormTokenError> {
+        FormToken::verify(token, secret, Duration::seconds(3), Duration::hours(1)).map(|_| ())
+    }
+
+    #[test]
+    fn a_form_submitted_in_time_is_accepted() {
+        let token = FormToken::issue(Utc::now() - Duration::seconds(10), &secret("secret"));
+
+        assert_ok!(verify(&token, &secret("secret")));
+    }
+
+    #[test]
+    fn a_form_submitted_too_quickly_is_rejected() {
+        let token = FormToken::issue(Utc::now(), &secret("secret"));
+
+        assert_err_eq!(verify(&token, &secret("secret")), FormTokenError::TooFast);
+    }
+
+    #[test]
+    fn a_stale_form_is_rejected() {
+        let token = FormToken::issue(Utc::now() - Duration::hours(2), &secret("secret"));
+
+        assert_err_eq!(verify(&token, &secret("secret")), FormTokenError::Expired);
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = FormToken::issue(Utc::now() - Duration::seconds(10), &secret("secret"));
+
+        assert_err_eq!(
+            verify(&token, &secret("other")),
+            FormTokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn garbage_is_malformed() {
+        assert_err_eq!(verify("", &secret("secret")), FormTokenError::Malformed);
+        assert_err_eq!(
+            verify("not a token", &secret("secret")),
+            FormTokenError::Malformed
+        );
+    }
+}
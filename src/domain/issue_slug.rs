@@ -0,0 +1,81 @@
+use std::fmt;
+
+use uuid::Uuid;
+
+const MAX_TITLE_LENGTH: usize = 60;
+
+#[derive(Debug, Clone)]
+pub struct IssueSlug(String);
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for IssueSlug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl IssueSlug {
+    /// Builds a URL-safe slug out of the issue title. The first characters of the
+    /// issue id are appended so that issues sharing a title get distinct slugs.
+    pub fn generate(title: &str, newsletter_issue_id: Uuid) -> Self {
+        let mut slug = String::with_capacity(MAX_TITLE_LENGTH);
+        for c in title.chars().flat_map(char::to_lowercase) {
+            if slug.len() >= MAX_TITLE_LENGTH {
+                break;
+            }
+            if c.is_ascii_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+
+        let id = newsletter_issue_id.simple().to_string();
+        if slug.is_empty() {
+            Self(id[..8].to_owned())
+        } else {
+            Self(format!("{slug}-{}", &id[..8]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::domain::IssueSlug;
+
+    fn issue_id() -> Uuid {
+        Uuid::parse_str("8f2e1c3a-0000-4000-8000-000000000000").unwrap()
+    }
+
+    #[test]
+    fn title_is_lowercased_and_hyphenated() {
+        let slug = IssueSlug::generate("Hello, World! Issue #3", issue_id());
+        assert_eq!(slug.as_ref(), "hello-world-issue-3-8f2e1c3a");
+    }
+
+    #[test]
+    fn non_ascii_characters_are_dropped() {
+        let slug = IssueSlug::generate("Café über alles", issue_id());
+        assert_eq!(slug.as_ref(), "caf-ber-alles-8f2e1c3a");
+    }
+
+    #[test]
+    fn long_titles_are_truncated() {
+        let slug = IssueSlug::generate(&"a".repeat(200), issue_id());
+        assert_eq!(slug.as_ref(), format!("{}-8f2e1c3a", "a".repeat(60)));
+    }
+
+    #[test]
+    fn titles_without_usable_characters_fall_back_to_the_id() {
+        let slug = IssueSlug::generate("!!! ???", issue_id());
+        assert_eq!(slug.as_ref(), "8f2e1c3a");
+    }
+}
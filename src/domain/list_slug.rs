@@ -0,0 +1,73 @@
+use std::fmt;
+
+const MAX_LENGTH: usize = 50;
+
+/// The identifier of a mailing list in public URLs, e.g. `/subscriptions?list=weekly`.
+#[derive(Debug, Clone)]
+pub struct ListSlug(String);
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ListSlug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ListSlug {
+    pub fn parse(s: String) -> Result<Self, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= MAX_LENGTH
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !s.starts_with('-')
+            && !s.ends_with('-');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!(
+                "{s} is not a valid list slug. Use up to {MAX_LENGTH} lowercase letters, digits and hyphens."
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::ListSlug;
+
+    #[test]
+    fn lowercase_words_joined_by_hyphens_are_valid() {
+        assert_ok!(ListSlug::parse("weekly-digest-2024".into()));
+    }
+
+    #[test]
+    fn empty_slugs_are_rejected() {
+        assert_err!(ListSlug::parse("".into()));
+    }
+
+    #[test]
+    fn uppercase_and_punctuation_are_rejected() {
+        assert_err!(ListSlug::parse("Weekly".into()));
+        assert_err!(ListSlug::parse("weekly digest".into()));
+        assert_err!(ListSlug::parse("weekly/digest".into()));
+    }
+
+    #[test]
+    fn leading_or_trailing_hyphens_are_rejected() {
+        assert_err!(ListSlug::parse("-weekly".into()));
+        assert_err!(ListSlug::parse("weekly-".into()));
+    }
+
+    #[test]
+    fn long_slugs_are_rejected() {
+        assert_ok!(ListSlug::parse("a".repeat(50)));
+        assert_err!(ListSlug::parse("a".repeat(51)));
+    }
+}
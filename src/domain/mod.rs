@@ -1,9 +1,29 @@
 mod application_base_url;
+mod blocked_sender;
+mod digest_frequency;
+mod form_token;
+mod issue_slug;
+mod list_slug;
+mod pause_period;
+mod segment;
 mod subscriber;
+mod subscriber_attributes;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_token;
+mod user_role;
 
 pub use application_base_url::ApplicationBaseUrl;
+pub use blocked_sender::{domain_suffixes, BlockedSender};
+pub use digest_frequency::DigestFrequency;
+pub use form_token::{FormToken, FormTokenError};
+pub use issue_slug::IssueSlug;
+pub use list_slug::ListSlug;
+pub use pause_period::PausePeriod;
+pub use segment::{SegmentCondition, SegmentDefinition};
 pub use subscriber::Subscriber;
-pub use subscriber_email::SubscriberEmail;
+pub use subscriber_attributes::{AttributeKey, SubscriberAttributes, SubscriberTag};
+pub use subscriber_email::{EmailNormalisation, SubscriberEmail};
 pub use subscriber_name::SubscriberName;
+pub use subscriber_token::{SubscriberToken, TokenError, TokenPurpose};
+pub use user_role::UserRole;
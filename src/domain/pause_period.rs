@@ -0,0 +1,84 @@
+use chrono::{DateTime, Duration, Months, Utc};
+
+/// How long a subscriber can pause their emails for. Issues published while they are
+/// paused are not sent to them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PausePeriod {
+    OneWeek,
+    OneMonth,
+    ThreeMonths,
+}
+
+impl PausePeriod {
+    pub const ALL: [Self; 3] = [Self::OneWeek, Self::OneMonth, Self::ThreeMonths];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|period| period.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a valid pause period."))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OneWeek => "1w",
+            Self::OneMonth => "1m",
+            Self::ThreeMonths => "3m",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::OneWeek => "For a week",
+            Self::OneMonth => "For a month",
+            Self::ThreeMonths => "For three months",
+        }
+    }
+
+    /// When a pause starting at `from` ends.
+    pub fn until(&self, from: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::OneWeek => from + Duration::weeks(1),
+            Self::OneMonth => from + Months::new(1),
+            Self::ThreeMonths => from + Months::new(3),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use claims::{assert_err, assert_ok_eq};
+
+    use crate::domain::PausePeriod;
+
+    #[test]
+    fn every_period_parses_from_its_own_name() {
+        for period in PausePeriod::ALL {
+            assert_ok_eq!(PausePeriod::parse(period.as_str()), period);
+        }
+    }
+
+    #[test]
+    fn unknown_periods_are_rejected() {
+        assert_err!(PausePeriod::parse("forever"));
+    }
+
+    #[test]
+    fn pauses_end_after_the_chosen_period() {
+        let from: DateTime<Utc> = "2024-01-31T10:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            PausePeriod::OneWeek.until(from).to_rfc3339(),
+            "2024-02-07T10:00:00+00:00"
+        );
+        assert_eq!(
+            PausePeriod::OneMonth.until(from).to_rfc3339(),
+            "2024-02-29T10:00:00+00:00"
+        );
+        assert_eq!(
+            PausePeriod::ThreeMonths.until(from).to_rfc3339(),
+            "2024-04-30T10:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{AttributeKey, SubscriberTag};
+
+/// One test a subscriber must pass to be part of a segment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SegmentCondition {
+    /// Subscribed on or after the date.
+    SubscribedAfter {
+        date: NaiveDate,
+    },
+    /// Subscribed before the date.
+    SubscribedBefore {
+        date: NaiveDate,
+    },
+    HasTag {
+        tag: SubscriberTag,
+    },
+    AttributeEquals {
+        key: AttributeKey,
+        value: String,
+    },
+}
+
+impl SegmentCondition {
+    fn describe(&self) -> String {
+        match self {
+            Self::SubscribedAfter { date } => format!("subscribed on or after {date}"),
+            Self::SubscribedBefore { date } => format!("subscribed before {date}"),
+            Self::HasTag { tag } => format!("tagged {tag}"),
+            Self::AttributeEquals { key, value } => format!("{key} is {value}"),
+        }
+    }
+}
+
+/// A stored filter narrowing an issue down to some of the confirmed subscribers of its
+/// lists. Subscribers must meet every condition. Stored as JSON in `segments.definition`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentDefinition {
+    conditions: Vec<SegmentCondition>,
+}
+
+impl SegmentDefinition {
+    pub fn new(conditions: Vec<SegmentCondition>) -> Result<Self, String> {
+        if conditions.is_empty() {
+            return Err("A segment needs at least one condition.".into());
+        }
+        let after = conditions.iter().find_map(|c| match c {
+            SegmentCondition::SubscribedAfter { date } => Some(date),
+            _ => None,
+        });
+        let before = conditions.iter().find_map(|c| match c {
+            SegmentCondition::SubscribedBefore { date } => Some(date),
+            _ => None,
+        });
+        if let (Some(after), Some(before)) = (after, before) {
+            if after >= before {
+                return Err(format!(
+                    "Nobody can subscribe both on or after {after} and before {before}."
+                ));
+            }
+        }
+        Ok(Self { conditions })
+    }
+
+    pub fn conditions(&self) -> &[SegmentCondition] {
+        &self.conditions
+    }
+
+    /// A readable summary, e.g. "tagged vip and locale is fr".
+    pub fn describe(&self) -> String {
+        self.conditions
+            .iter()
+            .map(SegmentCondition::describe)
+            .collect::<Vec<_>>()
+            .join(" and ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::{AttributeKey, SegmentCondition, SegmentDefinition, SubscriberTag};
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn a_segment_needs_a_condition() {
+        assert_err!(SegmentDefinition::new(vec![]));
+    }
+
+    #[test]
+    fn a_date_range_must_not_be_empty() {
+        assert_err!(SegmentDefinition::new(vec![
+            SegmentCondition::SubscribedAfter {
+                date: date("2024-02-01")
+            },
+            SegmentCondition::SubscribedBefore {
+                date: date("2024-01-01")
+            },
+        ]));
+    }
+
+    #[test]
+    fn definitions_round_trip_through_json() {
+        let definition = assert_ok!(SegmentDefinition::new(vec![
+            SegmentCondition::SubscribedAfter {
+                date: date("2024-01-01")
+            },
+            SegmentCondition::HasTag {
+                tag: SubscriberTag::parse("vip").unwrap()
+            },
+            SegmentCondition::AttributeEquals {
+                key: AttributeKey::parse("locale").unwrap(),
+                value: "fr".into()
+            },
+        ]));
+
+        let json = serde_json::to_value(&definition).unwrap();
+        assert_eq!(json["conditions"][1]["type"], "has_tag");
+        assert_eq!(
+            serde_json::from_value::<SegmentDefinition>(json).unwrap(),
+            definition
+        );
+        assert_eq!(
+            definition.describe(),
+            "subscribed on or after 2024-01-01 and tagged vip and locale is fr"
+        );
+    }
+
+    #[test]
+    fn stored_tags_are_validated_when_loaded() {
+        let json = serde_json::json!({
+            "conditions": [{ "type": "has_tag", "tag": "not a tag" }]
+        });
+
+        assert_err!(serde_json::from_value::<SegmentDefinition>(json));
+    }
+}
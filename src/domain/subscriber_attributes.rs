@@ -0,0 +1,196 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+const MAX_NAME_LENGTH: usize = 50;
+const MAX_VALUE_LENGTH: usize = 200;
+
+/// Tags and attribute keys are lowercased, and limited to letters, digits, `-` and `_`.
+fn parse_name(s: &str, what: &str) -> Result<String, String> {
+    let name = s.trim().to_lowercase();
+    let is_valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if is_valid {
+        Ok(name)
+    } else {
+        Err(format!(
+            "{s} is not a valid {what}. Use up to {MAX_NAME_LENGTH} letters, digits, hyphens and underscores."
+        ))
+    }
+}
+
+/// A label an admin puts on subscribers, e.g. `vip`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        parse_name(s, "tag").map(Self)
+    }
+}
+
+/// The name of a custom subscriber attribute, e.g. `locale`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AttributeKey(String);
+
+impl AttributeKey {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        parse_name(s, "attribute name").map(Self)
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SubscriberTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<String> for SubscriberTag {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<SubscriberTag> for String {
+    fn from(name: SubscriberTag) -> Self {
+        name.0
+    }
+}
+
+impl AsRef<str> for AttributeKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AttributeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<String> for AttributeKey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<AttributeKey> for String {
+    fn from(name: AttributeKey) -> Self {
+        name.0
+    }
+}
+
+/// The tags and custom attributes of a subscriber, as edited by an admin.
+#[derive(Debug, PartialEq)]
+pub struct SubscriberAttributes {
+    pub tags: Vec<SubscriberTag>,
+    pub attributes: Vec<(AttributeKey, String)>,
+}
+
+impl SubscriberAttributes {
+    /// Parses comma-separated tags and one `key=value` attribute per line. Duplicate tags
+    /// are dropped and a key set twice keeps its last value.
+    pub fn parse(tags: &str, attributes: &str) -> Result<Self, String> {
+        let mut parsed_tags: Vec<SubscriberTag> = Vec::new();
+        for tag in tags.split(',').filter(|t| !t.trim().is_empty()) {
+            let tag = SubscriberTag::parse(tag)?;
+            if !parsed_tags.contains(&tag) {
+                parsed_tags.push(tag);
+            }
+        }
+
+        let mut parsed_attributes: Vec<(AttributeKey, String)> = Vec::new();
+        for line in attributes.lines().filter(|l| !l.trim().is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{line} is not a key=value pair."))?;
+            let key = AttributeKey::parse(key)?;
+            let value = value.trim();
+            if value.is_empty() || value.chars().count() > MAX_VALUE_LENGTH {
+                return Err(format!(
+                    "The value of {key} must have between 1 and {MAX_VALUE_LENGTH} characters."
+                ));
+            }
+            parsed_attributes.retain(|(k, _)| *k != key);
+            parsed_attributes.push((key, value.to_string()));
+        }
+
+        Ok(Self {
+            tags: parsed_tags,
+            attributes: parsed_attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use crate::domain::{AttributeKey, SubscriberAttributes, SubscriberTag};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        let tag = assert_ok!(SubscriberTag::parse(" Early-Adopter "));
+        assert_eq!(tag.as_ref(), "early-adopter");
+    }
+
+    #[test]
+    fn tags_with_spaces_or_punctuation_are_rejected() {
+        assert_err!(SubscriberTag::parse("early adopter"));
+        assert_err!(SubscriberTag::parse("vip!"));
+        assert_err!(SubscriberTag::parse(""));
+        assert_err!(SubscriberTag::parse(&"a".repeat(51)));
+    }
+
+    #[test]
+    fn attribute_keys_follow_the_same_rules() {
+        assert_ok!(AttributeKey::parse("signup_source"));
+        assert_err!(AttributeKey::parse("signup source"));
+    }
+
+    #[test]
+    fn tags_and_attributes_are_parsed_from_form_text() {
+        let parsed = assert_ok!(SubscriberAttributes::parse(
+            "vip, beta, VIP,",
+            "locale = fr\n\ncompany=Acme, Inc.\nlocale=en-GB\n"
+        ));
+
+        let tags: Vec<_> = parsed.tags.iter().map(|t| t.as_ref()).collect();
+        assert_eq!(tags, ["vip", "beta"]);
+        let attributes: Vec<_> = parsed
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.as_ref(), v.as_str()))
+            .collect();
+        assert_eq!(attributes, [("company", "Acme, Inc."), ("locale", "en-GB")]);
+    }
+
+    #[test]
+    fn attribute_lines_without_a_value_are_rejected() {
+        assert_err!(SubscriberAttributes::parse("", "locale"));
+        assert_err!(SubscriberAttributes::parse("", "locale="));
+    }
+
+    #[test]
+    fn blank_input_means_no_tags_or_attributes() {
+        let parsed = assert_ok!(SubscriberAttributes::parse(" ", "\n"));
+        assert!(parsed.tags.is_empty());
+        assert!(parsed.attributes.is_empty());
+    }
+}
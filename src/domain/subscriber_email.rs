@@ -46,12 +46,40 @@ impl<'de> Deserialize<'de> for SubscriberEmail {
     }
 }
 
+/// How addresses typed by people are rewritten before they are stored.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct EmailNormalisation {
+    /// Mail servers may treat `Alice` and `alice` as different mailboxes, so the part
+    /// before the `@` is only lowercased when asked to. Subscribers are told apart by the
+    /// stored address, so without it `Alice@example.com` and `alice@example.com` are two
+    /// subscribers. Run `zerotoprod normalise-emails` after setting it.
+    #[serde(default)]
+    pub lowercase_local_part: bool,
+}
+
 impl SubscriberEmail {
+    /// Parses an address, trimming it and writing its domain in lowercase ASCII.
     pub fn parse(s: String) -> Result<Self, String> {
-        if validate_email(&s) {
-            Ok(Self(s))
+        Self::parse_with(s, EmailNormalisation::default())
+    }
+
+    pub fn parse_with(s: String, normalisation: EmailNormalisation) -> Result<Self, String> {
+        let invalid = || format!("{s} is not a valid subscriber email.");
+        let Some((local_part, domain)) = s.trim().rsplit_once('@') else {
+            return Err(invalid());
+        };
+        // Internationalised domains are stored in their punycode form
+        let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+        let local_part = if normalisation.lowercase_local_part {
+            local_part.to_lowercase()
+        } else {
+            local_part.to_owned()
+        };
+        let email = format!("{local_part}@{domain}");
+        if validate_email(&email) {
+            Ok(Self(email))
         } else {
-            Err(format!("{s} is not a valid subscriber email."))
+            Err(invalid())
         }
     }
 }
@@ -62,7 +90,7 @@ mod tests {
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
 
-    use crate::domain::SubscriberEmail;
+    use crate::domain::{EmailNormalisation, SubscriberEmail};
 
     #[test]
     fn empty_string_is_rejected() {
@@ -82,6 +110,40 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let email = SubscriberEmail::parse("  ursula@domain.com \n".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@domain.com");
+    }
+
+    #[test]
+    fn the_domain_is_lowercased() {
+        let email = SubscriberEmail::parse("Ursula@Domain.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula@domain.com");
+    }
+
+    #[test]
+    fn internationalised_domains_are_stored_as_punycode() {
+        let email = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn the_local_part_is_lowercased_on_request() {
+        let normalisation = EmailNormalisation {
+            lowercase_local_part: true,
+        };
+        let email =
+            SubscriberEmail::parse_with("Ursula@Domain.com".to_string(), normalisation).unwrap();
+        assert_eq!(email.as_ref(), "ursula@domain.com");
+    }
+
+    #[test]
+    fn invalid_domains_are_rejected() {
+        let email = "ursula@exa mple.com".to_string();
+        assert_err!(SubscriberEmail::parse(email));
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmailFixture(String);
 
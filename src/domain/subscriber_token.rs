@@ -0,0 +1,209 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::HmacSecret;
+
+/// What a signed subscriber link lets its holder do. Tokens issued for one purpose are
+/// rejected for any other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenPurpose {
+    DataAccess,
+    Preferences,
+}
+
+impl TokenPurpose {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::DataAccess => "data_access",
+            Self::Preferences => "preferences",
+        }
+    }
+}
+
+/// A stateless, time-limited token identifying a subscriber, signed with the
+/// application's HMAC secret. Encoded as `base64(purpose:subscriber_id:expiry).base64(tag)`.
+#[derive(Debug, PartialEq)]
+pub struct SubscriberToken {
+    pub subscriber_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TokenError {
+    #[error("The link is malformed.")]
+    Malformed,
+    #[error("The link signature is invalid.")]
+    InvalidSignature,
+    #[error("The link has expired.")]
+    Expired,
+}
+
+impl SubscriberToken {
+    pub fn issue(
+        subscriber_id: Uuid,
+        purpose: TokenPurpose,
+        valid_for: Duration,
+        secret: &HmacSecret,
+    ) -> String {
+        let expires_at = (Utc::now() + valid_for).timestamp();
+        let payload = format!("{}:{subscriber_id}:{expires_at}", purpose.as_str());
+        let tag = secret.sign(payload.as_bytes());
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(tag)
+        )
+    }
+
+    pub fn verify(
+        token: &str,
+        purpose: TokenPurpose,
+        secret: &HmacSecret,
+    ) -> Result<Self, TokenError> {
+        let (payload, tag) = token.split_once('.').ok_or(TokenError::Malformed)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| TokenError::Malformed)?;
+        let tag = URL_SAFE_NO_PAD
+            .decode(tag)
+            .map_err(|_| TokenError::Malformed)?;
+        if !secret.verify(&payload, &tag) {
+            return Err(TokenError::InvalidSignature);
+        }
+
+        let payload = String::from_utf8(payload).map_err(|_| TokenError::Malformed)?;
+        let mut parts = payload.split(':');
+        let (Some(token_purpose), Some(subscriber_id), Some(expires_at), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(TokenError::Malformed);
+        };
+        if token_purpose != purpose.as_str() {
+            return Err(TokenError::InvalidSignature);
+        }
+        let subscriber_id = subscriber_id.parse().map_err(|_| TokenError::Malformed)?;
+        let expires_at = expires_at
+            .parse()
+            .ok()
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .ok_or(TokenError::Malformed)?;
+        if expires_at < Utc::now() {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(Self {
+            subscriber_id,
+            expires_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{SubscriberToken, TokenError, TokenPurpose};
+    use crate::HmacSecret;
+
+    fn secret(s: &str) -> HmacSecret {
+        HmacSecret(Secret::new(s.to_string()))
+    }
+
+    #[test]
+    fn a_valid_token_is_accepted() {
+        let subscriber_id = Uuid::new_v4();
+        let token = SubscriberToken::issue(
+            subscriber_id,
+            TokenPurpose::DataAccess,
+            Duration::hours(1),
+            &secret("secret"),
+        );
+
+        let verified = assert_ok!(SubscriberToken::verify(
+            &token,
+            TokenPurpose::DataAccess,
+            &secret("secret")
+        ));
+        assert_eq!(verified.subscriber_id, subscriber_id);
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = SubscriberToken::issue(
+            Uuid::new_v4(),
+            TokenPurpose::DataAccess,
+            Duration::hours(1),
+            &secret("secret"),
+        );
+
+        assert_err_eq!(
+            SubscriberToken::verify(&token, TokenPurpose::DataAccess, &secret("other")),
+            TokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn a_tampered_token_is_rejected() {
+        let token = SubscriberToken::issue(
+            Uuid::new_v4(),
+            TokenPurpose::DataAccess,
+            Duration::hours(1),
+            &secret("secret"),
+        );
+        let (_, tag) = token.split_once('.').unwrap();
+        let forged = format!(
+            "{}.{tag}",
+            base64::Engine::encode(
+                &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+                format!("data_access:{}:9999999999", Uuid::new_v4())
+            )
+        );
+
+        assert_err_eq!(
+            SubscriberToken::verify(&forged, TokenPurpose::DataAccess, &secret("secret")),
+            TokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let token = SubscriberToken::issue(
+            Uuid::new_v4(),
+            TokenPurpose::DataAccess,
+            Duration::hours(-1),
+            &secret("secret"),
+        );
+
+        assert_err_eq!(
+            SubscriberToken::verify(&token, TokenPurpose::DataAccess, &secret("secret")),
+            TokenError::Expired
+        );
+    }
+
+    #[test]
+    fn a_token_issued_for_another_purpose_is_rejected() {
+        let token = SubscriberToken::issue(
+            Uuid::new_v4(),
+            TokenPurpose::Preferences,
+            Duration::hours(1),
+            &secret("secret"),
+        );
+
+        assert_err_eq!(
+            SubscriberToken::verify(&token, TokenPurpose::DataAccess, &secret("secret")),
+            TokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn garbage_is_malformed() {
+        assert_err_eq!(
+            SubscriberToken::verify("not a token", TokenPurpose::DataAccess, &secret("s")),
+            TokenError::Malformed
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+
+/// What an admin user may do. Viewers can only look, editors can also write and publish
+/// issues and manage subscribers, owners can also manage users and lockouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UserRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl UserRole {
+    pub const ALL: [Self; 3] = [Self::Owner, Self::Editor, Self::Viewer];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a valid role."))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Editor => "editor",
+            Self::Owner => "owner",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Viewer => "Viewer - read-only access",
+            Self::Editor => "Editor - can publish issues and manage subscribers",
+            Self::Owner => "Owner - can also manage users",
+        }
+    }
+}
+
+impl fmt::Display for UserRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use crate::domain::UserRole;
+
+    #[test]
+    fn every_role_parses_from_its_own_name() {
+        for role in UserRole::ALL {
+            assert_ok_eq!(UserRole::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(UserRole::parse("admin"));
+        assert_err!(UserRole::parse("Owner"));
+    }
+
+    #[test]
+    fn roles_are_ordered_by_what_they_allow() {
+        assert!(UserRole::Viewer < UserRole::Editor);
+        assert!(UserRole::Editor < UserRole::Owner);
+    }
+}
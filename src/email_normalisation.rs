@@ -0,0 +1,229 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::{EmailNormalisation, SubscriberEmail};
+
+/// What `normalise_stored_emails` changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct NormalisedEmails {
+    /// Subscribers whose address was rewritten.
+    pub rewritten: u64,
+    /// Subscribers merged into another one with the same address once normalised.
+    pub merged: u64,
+}
+
+#[derive(Debug)]
+struct StoredSubscriber {
+    id: Uuid,
+    email: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl StoredSubscriber {
+    /// The subscriber that ranks first is kept when two are merged: the best status wins,
+    /// then the earliest subscription, as in the migration that normalised addresses.
+    fn rank(&self) -> (u8, DateTime<Utc>, Uuid) {
+        let status = match self.status.as_str() {
+            "confirmed" => 0,
+            "pending_confirmation" => 1,
+            _ => 2,
+        };
+        (status, self.subscribed_at, self.id)
+    }
+}
+
+/// Rewrites stored addresses as `SubscriberEmail::parse_with` would write them now: the
+/// SQL migration that normalised addresses could only lowercase domains, so
+/// `x@bücher.example` stored before it would otherwise never match a new
+/// `x@xn--bcher-kva.example`, and `X@example.com` stored before `lowercase_local_part`
+/// was set would never match a new `x@example.com`. A subscriber whose rewritten address
+/// is already taken is merged into the other one. Running it again changes nothing.
+#[tracing::instrument(name = "Normalise stored subscriber emails", skip(pg_pool))]
+pub async fn normalise_stored_emails(
+    pg_pool: &PgPool,
+    normalisation: EmailNormalisation,
+) -> anyhow::Result<NormalisedEmails> {
+    let mut transaction = pg_pool
+        .begin()
+        .await
+        .context("Failed to begin a transaction")?;
+    let stored = sqlx::query_as!(
+        StoredSubscriber,
+        r#"
+        SELECT id, email, status, subscribed_at FROM subscriptions
+        WHERE email ~ '[^[:ascii:]]' OR ($1 AND email <> lower(email))
+        ORDER BY subscribed_at, id
+        FOR UPDATE
+        "#,
+        normalisation.lowercase_local_part,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to fetch addresses that may not be normalised")?;
+
+    let mut report = NormalisedEmails::default();
+    for subscriber in stored {
+        let email = match SubscriberEmail::parse_with(subscriber.email.clone(), normalisation) {
+            Ok(email) if email.as_ref() != subscriber.email => email,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!(error.message = %e, "Cannot normalise a stored address");
+                continue;
+            }
+        };
+        let existing = sqlx::query_as!(
+            StoredSubscriber,
+            r#"
+            SELECT id, email, status, subscribed_at FROM subscriptions
+            WHERE email = $1 AND id <> $2
+            FOR UPDATE
+            "#,
+            email.as_ref(),
+            subscriber.id,
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to look for a subscriber with the normalised address")?;
+
+        let survivor = match existing {
+            None => subscriber,
+            Some(other) => {
+                let (survivor, duplicate) = if other.rank() <= subscriber.rank() {
+                    (other, subscriber)
+                } else {
+                    (subscriber, other)
+                };
+                merge_subscriber(&mut transaction, &duplicate, &survivor)
+                    .await
+                    .context("Failed to merge subscribers")?;
+                report.merged += 1;
+                survivor
+            }
+        };
+        if survivor.email != email.as_ref() {
+            rewrite_email(&mut transaction, &survivor, email.as_ref())
+                .await
+                .context("Failed to rewrite an address")?;
+            report.rewritten += 1;
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the normalised addresses")?;
+    Ok(report)
+}
+
+async fn rewrite_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &StoredSubscriber,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2 WHERE id = $1"#,
+        subscriber.id,
+        email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE issue_delivery_queue SET subscriber_email = $2 WHERE subscriber_email = $1"#,
+        subscriber.email,
+        email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Moves what `duplicate` has over to `survivor`, then deletes it.
+async fn merge_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    duplicate: &StoredSubscriber,
+    survivor: &StoredSubscriber,
+) -> Result<(), sqlx::Error> {
+    // A merged subscriber gets each issue once
+    sqlx::query!(
+        r#"
+        WITH moved AS (
+            DELETE FROM issue_delivery_queue WHERE subscriber_email = $1
+            RETURNING newsletter_issue_id, n_retries, execute_after
+        )
+        INSERT INTO issue_delivery_queue
+            (newsletter_issue_id, subscriber_email, n_retries, execute_after)
+        SELECT newsletter_issue_id, $2, n_retries, execute_after FROM moved
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING
+        "#,
+        duplicate.email,
+        survivor.email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    // The survivor is confirmed on every list either of them confirmed
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT list_id, $2, status, subscribed_at FROM list_memberships
+        WHERE subscriber_id = $1
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = EXCLUDED.status, subscribed_at = EXCLUDED.subscribed_at
+        WHERE EXCLUDED.status = 'confirmed' AND list_memberships.status <> 'confirmed'
+        "#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $2, tag FROM subscriber_tags WHERE subscriber_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    // The survivor's own attributes take precedence
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT $2, key, value FROM subscriber_attributes WHERE subscriber_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE subscription_tokens SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE email_change_requests SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE subscriber_data_requests SET subscriber_id = $2 WHERE subscriber_id = $1"#,
+        duplicate.id,
+        survivor.id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, duplicate.id)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(())
+}
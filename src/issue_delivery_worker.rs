@@ -1,8 +1,9 @@
+use std::fmt::Write;
 use std::time::Duration;
 
 use crate::{
     configuration::Settings,
-    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    domain::{ApplicationBaseUrl, DigestFrequency, Subscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
     startup::get_connection_pool,
 };
@@ -10,11 +11,29 @@ use sqlx::{PgPool, Postgres, Transaction};
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
+const MAX_RETRIES: i16 = 3;
+const RETRY_BACKOFF_SECS: i32 = 10;
+
 pub enum ExecutionOutcome {
     TaskCompleted,
     EmptyQueue,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum DeliveryOutcome {
+    Sent,
+    Failed,
+}
+
+impl DeliveryOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+        }
+    }
+}
+
 #[tracing::instrument(
     skip_all,
     fields(newsletter_issue_id=tracing::field::Empty, subscriber_email=tracing::field::Empty),
@@ -23,54 +42,81 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &ApplicationBaseUrl,
 ) -> anyhow::Result<ExecutionOutcome> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let Some((mut transaction, tasks)) = dequeue_tasks(pool).await? else {
         return Ok(ExecutionOutcome::EmptyQueue);
-    }
-    let (transaction, issue_id, email, name) = task.unwrap();
+    };
+    let task = &tasks[0];
 
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
+        .record("newsletter_issue_id", display(task.newsletter_issue_id))
+        .record("subscriber_email", display(&task.subscriber_email));
+
+    for task in &tasks {
+        mark_delivery_started(&mut transaction, task.newsletter_issue_id).await?;
+    }
 
     match (
-        SubscriberEmail::parse(email.clone()),
-        SubscriberName::parse(name),
+        SubscriberEmail::parse(task.subscriber_email.clone()),
+        SubscriberName::parse(task.subscriber_name.clone()),
     ) {
         (Ok(email), Ok(name)) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(&Subscriber { name, email }, &issue.title, &issue.content)
+            let (subject, html_content) = compose_email(pool, &tasks, base_url).await?;
+            match email_client
+                .send_email(&Subscriber { name, email }, &subject, &html_content)
                 .await
             {
-                tracing::error!("Failed to deliver issue to a confirmed subscriber. Skipping. {e}",);
+                Ok(()) => complete_tasks(transaction, &tasks, DeliveryOutcome::Sent).await?,
+                Err(e) if task.n_retries < MAX_RETRIES => {
+                    tracing::warn!(
+                        "Failed to deliver issue to a confirmed subscriber. Retrying later. {e}",
+                    );
+                    retry_tasks(transaction, &tasks).await?;
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to deliver issue to a confirmed subscriber. Giving up. {e}",
+                    );
+                    complete_tasks(transaction, &tasks, DeliveryOutcome::Failed).await?;
+                }
             }
         }
         other => {
             tracing::error!(
                     "Skipping a confirmed subscriber. Their stored contact details are invalid. {other:?}",
                 );
+            complete_tasks(transaction, &tasks, DeliveryOutcome::Failed).await?;
         }
     }
 
-    delete_task(transaction, issue_id, &email).await?;
-
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    subscriber_name: String,
+    digest_frequency: String,
+    n_retries: i16,
+}
+
+/// Locks a due delivery. For subscribers on a digest, every other issue due for them is
+/// locked along with it, so that they all go out in one email.
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
+async fn dequeue_tasks(
     pg_pool: &PgPool,
-) -> anyhow::Result<Option<(PgTransaction, Uuid, String, String)>> {
+) -> anyhow::Result<Option<(PgTransaction, Vec<DeliveryTask>)>> {
     let mut transaction = pg_pool.begin().await?;
-    let r = sqlx::query!(
+    let r = sqlx::query_as!(
+        DeliveryTask,
         r#"
-        SELECT newsletter_issue_id, subscriber_email, name as subscriber_name
+        SELECT newsletter_issue_id, subscriber_email, name as subscriber_name, digest_frequency, n_retries
         FROM issue_delivery_queue as a INNER JOIN subscriptions as b
         ON a.subscriber_email=b.email
+        WHERE execute_after <= now()
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
@@ -78,65 +124,209 @@ async fn dequeue_task(
     )
     .fetch_optional(&mut *transaction)
     .await?;
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-            r.subscriber_name,
-        )))
-    } else {
-        Ok(None)
+    let Some(task) = r else {
+        return Ok(None);
+    };
+
+    let mut tasks = vec![];
+    if task.digest_frequency != DigestFrequency::Immediate.as_str() {
+        tasks = sqlx::query_as!(
+            DeliveryTask,
+            r#"
+            SELECT newsletter_issue_id, subscriber_email, name as subscriber_name, digest_frequency, n_retries
+            FROM issue_delivery_queue as a INNER JOIN subscriptions as b
+            ON a.subscriber_email=b.email
+            WHERE
+                a.subscriber_email = $1 AND
+                newsletter_issue_id <> $2 AND
+                execute_after <= now()
+            FOR UPDATE
+            SKIP LOCKED
+            "#,
+            task.subscriber_email,
+            task.newsletter_issue_id,
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
     }
+    tasks.insert(0, task);
+    Ok(Some((transaction, tasks)))
 }
 
 #[tracing::instrument(skip_all)]
-async fn delete_task(
-    mut transaction: PgTransaction,
+async fn mark_delivery_started(
+    transaction: &mut PgTransaction,
     issue_id: Uuid,
-    email: &str,
 ) -> anyhow::Result<()> {
     sqlx::query!(
         r#"
-        DELETE FROM issue_delivery_queue
+        UPDATE newsletter_issues
+        SET delivery_started_at = now()
         WHERE
             newsletter_issue_id = $1 AND
-            subscriber_email = $2
+            delivery_started_at IS NULL
         "#,
         issue_id,
-        email
     )
-    .execute(&mut *transaction)
+    .execute(&mut **transaction)
     .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn complete_tasks(
+    mut transaction: PgTransaction,
+    tasks: &[DeliveryTask],
+    outcome: DeliveryOutcome,
+) -> anyhow::Result<()> {
+    for task in tasks {
+        sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_log (
+                newsletter_issue_id,
+                subscriber_email,
+                outcome,
+                n_retries,
+                completed_at
+            )
+            VALUES ($1, $2, $3, $4, now())
+            ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+            SET
+                outcome = EXCLUDED.outcome,
+                n_retries = EXCLUDED.n_retries,
+                completed_at = EXCLUDED.completed_at
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email,
+            outcome.as_str(),
+            task.n_retries
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn retry_tasks(mut transaction: PgTransaction, tasks: &[DeliveryTask]) -> anyhow::Result<()> {
+    for task in tasks {
+        let n_retries = task.n_retries + 1;
+        let backoff_secs = RETRY_BACKOFF_SECS * 2i32.pow(task.n_retries as u32);
+        sqlx::query!(
+            r#"
+            UPDATE issue_delivery_queue
+            SET
+                n_retries = $3,
+                execute_after = now() + make_interval(secs => $4)
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email,
+            n_retries,
+            backoff_secs as f64
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
     transaction.commit().await?;
     Ok(())
 }
 
+/// The subject and content of the email for `tasks`. A single issue is sent as it is; a
+/// digest lists its issues one after the other, oldest first.
+async fn compose_email(
+    pg_pool: &PgPool,
+    tasks: &[DeliveryTask],
+    base_url: &ApplicationBaseUrl,
+) -> anyhow::Result<(String, String)> {
+    let issue_ids = tasks
+        .iter()
+        .map(|task| task.newsletter_issue_id)
+        .collect::<Vec<_>>();
+    let issues = get_issues(pg_pool, &issue_ids).await?;
+    if let [issue] = issues.as_slice() {
+        return Ok((issue.title.clone(), issue.html_content(base_url)?));
+    }
+
+    let subject = format!(
+        "Your {} digest: {} new issues",
+        tasks[0].digest_frequency,
+        issues.len()
+    );
+    let mut html_content = String::new();
+    for issue in &issues {
+        let title = askama::filters::escape(askama::Html, &issue.title)?;
+        write!(
+            html_content,
+            "<h2>{title}</h2>{}<hr />",
+            issue.html_content(base_url)?
+        )?;
+    }
+    Ok((subject, html_content))
+}
+
 struct NewsletterIssue {
     title: String,
     content: String,
+    slug: String,
+    hidden_from_archive: bool,
+}
+
+impl NewsletterIssue {
+    /// The issue content, followed by a link to its public archive page when it has one.
+    fn html_content(&self, base_url: &ApplicationBaseUrl) -> anyhow::Result<String> {
+        if self.hidden_from_archive {
+            return Ok(self.content.clone());
+        }
+        let archive_link = base_url.join(&format!("archive/{}", self.slug))?;
+        Ok(format!(
+            "{}<p><a href=\"{}\">View in browser</a></p>",
+            self.content,
+            archive_link.as_str()
+        ))
+    }
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(pg_pool: &PgPool, issue_id: Uuid) -> anyhow::Result<NewsletterIssue> {
-    let issue = sqlx::query_as!(
+async fn get_issues(pg_pool: &PgPool, issue_ids: &[Uuid]) -> anyhow::Result<Vec<NewsletterIssue>> {
+    let issues = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, content
+        SELECT title, content, slug, hidden_from_archive
         FROM newsletter_issues
         WHERE
-            newsletter_issue_id = $1
+            newsletter_issue_id = ANY($1)
+        ORDER BY published_at
         "#,
-        issue_id
+        issue_ids
     )
-    .fetch_one(pg_pool)
+    .fetch_all(pg_pool)
     .await?;
-    Ok(issue)
+    Ok(issues)
 }
 
-async fn worker_loop(pg_pool: PgPool, email_client: EmailClient) -> anyhow::Result<()> {
+async fn worker_loop(
+    pg_pool: PgPool,
+    email_client: EmailClient,
+    base_url: ApplicationBaseUrl,
+) -> anyhow::Result<()> {
     loop {
-        match try_execute_task(&pg_pool, &email_client).await {
+        match try_execute_task(&pg_pool, &email_client, &base_url).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -151,5 +341,10 @@ async fn worker_loop(pg_pool: PgPool, email_client: EmailClient) -> anyhow::Resu
 pub async fn run_worker_until_stopped(configuration: Settings) -> anyhow::Result<()> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email.client()?;
-    worker_loop(connection_pool, email_client).await
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+    )
+    .await
 }
@@ -1,19 +1,34 @@
 use std::sync::Arc;
 
+use authentication::PasswordPolicy;
 use axum::extract::FromRef;
-use domain::ApplicationBaseUrl;
+use bot_protection::BotProtection;
+use configuration::{LoginThrottlingSettings, PasswordHashingSettings};
+use domain::{ApplicationBaseUrl, EmailNormalisation};
 use email_client::EmailClient;
-use secrecy::Secret;
+use hmac::{Hmac, Mac};
+use rate_limit::RateLimiter;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use signup_policy::SignupPolicy;
 use sqlx::PgPool;
 
 pub mod authentication;
+pub mod bot_protection;
+pub mod captcha;
+pub mod cli;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_normalisation;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod mailing_lists;
+pub mod rate_limit;
 pub mod routes;
+pub mod segments;
 pub mod session_state;
+pub mod signup_policy;
 pub mod startup;
 pub mod telemetry;
 pub mod utils;
@@ -21,6 +36,25 @@ pub mod utils;
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+impl HmacSecret {
+    fn mac(&self, message: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.0.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(message);
+        mac
+    }
+
+    /// HMAC-SHA256 tag of `message`.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.mac(message).finalize().into_bytes().to_vec()
+    }
+
+    /// Checks `tag` against `message` in constant time.
+    pub fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        self.mac(message).verify_slice(tag).is_ok()
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub pg_connection_pool: PgPool,
@@ -28,6 +62,13 @@ pub struct AppState {
     pub application_base_url: Arc<ApplicationBaseUrl>,
     pub hmac_secret: Arc<HmacSecret>,
     pub flash_config: axum_flash::Config,
+    pub email_normalisation: EmailNormalisation,
+    pub signup_policy: Arc<SignupPolicy>,
+    pub bot_protection: Arc<BotProtection>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub login_throttling: LoginThrottlingSettings,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub password_hashing: PasswordHashingSettings,
 }
 
 impl FromRef<AppState> for axum_flash::Config {
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+/// A mailing list people can subscribe to. Membership, and its confirmation status, is
+/// tracked per list in `list_memberships`.
+#[derive(Debug, Clone)]
+pub struct MailingList {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// The list used when a subscriber or an issue does not name one.
+#[tracing::instrument(name = "Get the default mailing list", skip(executor))]
+pub async fn get_default_list(executor: impl PgExecutor<'_>) -> Result<MailingList, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT list_id, slug, name, is_default FROM lists WHERE is_default"#,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+#[tracing::instrument(name = "Get a mailing list", skip(executor))]
+pub async fn get_list_by_slug(
+    executor: impl PgExecutor<'_>,
+    slug: &str,
+) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT list_id, slug, name, is_default FROM lists WHERE slug = $1"#,
+        slug,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Resolves an optional slug from a form or query string: no slug means the default list.
+pub async fn find_list(
+    pg_pool: &PgPool,
+    slug: Option<&str>,
+) -> Result<Option<MailingList>, sqlx::Error> {
+    match slug.filter(|s| !s.is_empty()) {
+        Some(slug) => get_list_by_slug(pg_pool, slug).await,
+        None => get_default_list(pg_pool).await.map(Some),
+    }
+}
+
+#[tracing::instrument(name = "Get all mailing lists", skip(executor))]
+pub async fn get_all_lists(executor: impl PgExecutor<'_>) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"
+        SELECT list_id, slug, name, is_default
+        FROM lists
+        ORDER BY is_default DESC, name
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// A subscriber's status on one list.
+#[derive(Debug, Serialize)]
+pub struct Membership {
+    pub slug: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get a subscriber's list memberships", skip(executor))]
+pub async fn get_memberships(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Vec<Membership>, sqlx::Error> {
+    sqlx::query_as!(
+        Membership,
+        r#"
+        SELECT l.slug, l.name, m.status, m.subscribed_at
+        FROM list_memberships m
+        JOIN lists l USING (list_id)
+        WHERE m.subscriber_id = $1
+        ORDER BY l.is_default DESC, l.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(executor)
+    .await
+}
@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
 
+use zerotoprod::cli::{create_user_command, normalise_emails_command};
 use zerotoprod::configuration::get_configuration;
 use zerotoprod::issue_delivery_worker::run_worker_until_stopped;
 use zerotoprod::startup::Application;
@@ -10,6 +11,15 @@ use zerotoprod::telemetry::init_subscriber;
 async fn main() -> anyhow::Result<()> {
     let configuration = get_configuration().expect("Failed to read configuration.");
 
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some((command, args)) = args.split_first() {
+        return match command.as_str() {
+            "create-user" => create_user_command(configuration, args).await,
+            "normalise-emails" => normalise_emails_command(configuration).await,
+            other => Err(anyhow::anyhow!("Unknown command {other}.")),
+        };
+    }
+
     init_subscriber(
         "zerotoprod".into(),
         "info".into(),
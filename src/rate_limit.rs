@@ -0,0 +1,385 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tower_sessions::fred::{
+    clients::RedisClient,
+    interfaces::KeysInterface,
+    types::{Expiration, SetOptions},
+};
+
+use crate::{
+    configuration::{Quota, RateLimitSettings, RouteLimits},
+    AppState,
+};
+
+/// Form bodies are read to find the submitted email. Subscription forms are tiny.
+const MAX_FORM_SIZE: usize = 64 * 1024;
+/// How long to wait for Redis before counting in memory instead.
+const REDIS_TIMEOUT: Duration = Duration::from_millis(500);
+/// The most keys counted in memory. Past it, expired windows are swept and then the
+/// windows closest to ending are forgotten to make room for new keys.
+const MAX_MEMORY_KEYS: usize = 10_000;
+
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Fixed-window request counters, kept in Redis so that they are shared by every
+/// instance of the application. Counting falls back to this process's memory while Redis
+/// cannot be reached.
+pub struct RateLimiter {
+    redis: Option<RedisClient>,
+    key_prefix: String,
+    trusted_proxies: Vec<IpAddr>,
+    memory: Mutex<HashMap<String, (u32, Instant)>>,
+}
+
+impl RateLimiter {
+    pub fn new(redis: Option<RedisClient>, settings: &RateLimitSettings) -> Self {
+        Self {
+            redis,
+            key_prefix: settings.key_prefix.clone(),
+            trusted_proxies: settings.trusted_proxies.clone(),
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request against `key` and tells whether it is within `quota`.
+    pub async fn hit(&self, key: &str, quota: &Quota) -> Decision {
+        let key = format!("{}:{key}", self.key_prefix);
+        if let Some(redis) = &self.redis {
+            match tokio::time::timeout(REDIS_TIMEOUT, hit_redis(redis, &key, quota)).await {
+                Ok(Ok(decision)) => return decision,
+                Ok(Err(e)) => tracing::warn!(
+                    error.message = %e,
+                    "Failed to count a request in Redis. Counting in memory instead.",
+                ),
+                Err(_) => tracing::warn!("Redis timed out. Counting a request in memory instead."),
+            }
+        }
+        self.hit_memory(key, quota, Instant::now())
+    }
+
+    fn hit_memory(&self, key: String, quota: &Quota, now: Instant) -> Decision {
+        let mut counters = self.memory.lock().unwrap();
+        if counters.len() >= MAX_MEMORY_KEYS && !counters.contains_key(&key) {
+            counters.retain(|_, (_, window_end)| *window_end > now);
+            if counters.len() >= MAX_MEMORY_KEYS {
+                let oldest = counters
+                    .iter()
+                    .min_by_key(|(_, (_, window_end))| *window_end)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    counters.remove(&oldest);
+                }
+            }
+        }
+        let (count, window_end) = counters
+            .entry(key)
+            .and_modify(|(count, window_end)| {
+                if *window_end <= now {
+                    *count = 0;
+                    *window_end = now + quota.window();
+                }
+            })
+            .or_insert((0, now + quota.window()));
+        *count += 1;
+        if *count > quota.requests {
+            Decision::Limited {
+                retry_after: *window_end - now,
+            }
+        } else {
+            Decision::Allowed
+        }
+    }
+
+    /// The address of the client. Requests relayed by a trusted proxy are attributed to
+    /// the nearest untrusted address in `X-Forwarded-For`.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+        let forwarded_for = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        let mut client = peer;
+        for ip in forwarded_for.into_iter().rev() {
+            client = ip;
+            if !self.trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// The address of the client making the request, see [`RateLimiter::client_ip`].
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(Self(
+            state.rate_limiter.client_ip(peer.ip(), &parts.headers),
+        ))
+    }
+}
+
+async fn hit_redis(
+    redis: &RedisClient,
+    key: &str,
+    quota: &Quota,
+) -> Result<Decision, tower_sessions::fred::error::RedisError> {
+    // Starting the window separately from counting means a crash between the two
+    // commands cannot leave a counter that never expires.
+    redis
+        .set::<(), _, _>(
+            key,
+            0,
+            Some(Expiration::EX(quota.window_seconds as i64)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await?;
+    let count: u64 = redis.incr(key).await?;
+    if count <= quota.requests as u64 {
+        return Ok(Decision::Allowed);
+    }
+    let ttl: i64 = redis.ttl(key).await?;
+    let retry_after = u64::try_from(ttl).unwrap_or(quota.window_seconds);
+    Ok(Decision::Limited {
+        retry_after: Duration::from_secs(retry_after),
+    })
+}
+
+/// The state of a rate limiting layer: the limits of one group of routes.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+    scope: &'static str,
+    limits: RouteLimits,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Arc<RateLimiter>, scope: &'static str, limits: RouteLimits) -> Self {
+        Self {
+            limiter,
+            scope,
+            limits,
+        }
+    }
+}
+
+/// Rejects form submissions beyond the route group's limits with `429 Too Many Requests`.
+/// Use with `axum::middleware::from_fn_with_state`.
+pub async fn rate_limit(
+    State(rate_limit): State<RateLimit>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let RateLimit {
+        limiter,
+        scope,
+        limits,
+    } = rate_limit;
+
+    if let Some(quota) = &limits.per_ip {
+        let ip = limiter.client_ip(peer.ip(), request.headers());
+        let key = format!("{scope}:ip:{ip}");
+        if let Decision::Limited { retry_after } = limiter.hit(&key, quota).await {
+            tracing::warn!(%ip, scope, "Rate limited a client");
+            return too_many_requests(retry_after);
+        }
+    }
+
+    let request = match &limits.per_email {
+        Some(quota) => {
+            let (parts, body) = request.into_parts();
+            let Ok(bytes) = to_bytes(body, MAX_FORM_SIZE).await else {
+                return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+            };
+            if let Some(email) = submitted_email(&bytes) {
+                let key = format!("{scope}:email:{email}");
+                if let Decision::Limited { retry_after } = limiter.hit(&key, quota).await {
+                    tracing::warn!(scope, "Rate limited an email address");
+                    return too_many_requests(retry_after);
+                }
+            }
+            Request::from_parts(parts, Body::from(bytes))
+        }
+        None => request,
+    };
+    next.run(request).await
+}
+
+fn submitted_email(form: &[u8]) -> Option<String> {
+    serde_urlencoded::from_bytes::<Vec<(String, String)>>(form)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| name == "email")
+        .map(|(_, email)| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty())
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs().max(1);
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        "Too many requests. Please try again later.",
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::IpAddr,
+        time::{Duration, Instant},
+    };
+
+    use axum::http::HeaderMap;
+
+    use super::{Decision, RateLimiter, MAX_MEMORY_KEYS};
+    use crate::configuration::{Quota, RateLimitSettings, RouteLimits};
+
+    fn limiter(trusted_proxies: &[&str]) -> RateLimiter {
+        let settings = RateLimitSettings {
+            key_prefix: "test".into(),
+            trusted_proxies: trusted_proxies
+                .iter()
+                .map(|ip| ip.parse().unwrap())
+                .collect(),
+            login: RouteLimits::default(),
+            subscriptions: RouteLimits::default(),
+        };
+        RateLimiter::new(None, &settings)
+    }
+
+    const QUOTA: Quota = Quota {
+        requests: 2,
+        window_seconds: 60,
+    };
+
+    #[test]
+    fn requests_beyond_the_quota_are_limited_until_the_window_ends() {
+        let limiter = limiter(&[]);
+        let start = Instant::now();
+
+        assert_eq!(
+            limiter.hit_memory("k".into(), &QUOTA, start),
+            Decision::Allowed
+        );
+        assert_eq!(
+            limiter.hit_memory("k".into(), &QUOTA, start),
+            Decision::Allowed
+        );
+        assert_eq!(
+            limiter.hit_memory("k".into(), &QUOTA, start + Duration::from_secs(15)),
+            Decision::Limited {
+                retry_after: Duration::from_secs(45)
+            }
+        );
+        assert_eq!(
+            limiter.hit_memory("k".into(), &QUOTA, start + Duration::from_secs(60)),
+            Decision::Allowed
+        );
+    }
+
+    #[test]
+    fn keys_are_counted_separately() {
+        let limiter = limiter(&[]);
+        let now = Instant::now();
+
+        limiter.hit_memory("a".into(), &QUOTA, now);
+        limiter.hit_memory("a".into(), &QUOTA, now);
+
+        assert_eq!(
+            limiter.hit_memory("b".into(), &QUOTA, now),
+            Decision::Allowed
+        );
+    }
+
+    #[test]
+    fn the_oldest_keys_are_forgotten_once_memory_is_full() {
+        let limiter = limiter(&[]);
+        let start = Instant::now();
+        for i in 0..MAX_MEMORY_KEYS {
+            let now = start + Duration::from_millis(i as u64);
+            limiter.hit_memory(i.to_string(), &QUOTA, now);
+        }
+
+        let now = start + Duration::from_secs(30);
+        limiter.hit_memory("new".into(), &QUOTA, now);
+        limiter.hit_memory("1".into(), &QUOTA, now);
+
+        let counters = limiter.memory.lock().unwrap();
+        assert_eq!(counters.len(), MAX_MEMORY_KEYS);
+        assert!(counters.contains_key("new"));
+        assert!(!counters.contains_key("0"));
+        assert_eq!(counters["1"].0, 2);
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let limiter = limiter(&["10.0.0.1"]);
+        let peer: IpAddr = "203.0.113.9".parse().unwrap();
+
+        assert_eq!(
+            limiter.client_ip(peer, &forwarded_for("198.51.100.7")),
+            peer
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped_from_the_right() {
+        let limiter = limiter(&["10.0.0.1", "10.0.0.2"]);
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+
+        assert_eq!(
+            limiter.client_ip(peer, &forwarded_for("1.2.3.4, 198.51.100.7, 10.0.0.2")),
+            "198.51.100.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(limiter.client_ip(peer, &HeaderMap::new()), peer);
+    }
+}
@@ -0,0 +1,98 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    domain::BlockedSender,
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/blocklist.html")]
+struct Blocklist {
+    msg: String,
+    entries: Vec<BlocklistEntry>,
+}
+
+struct BlocklistEntry {
+    entry: String,
+    created_at: DateTime<Utc>,
+}
+
+pub async fn blocklist(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+) -> Result<Response, StatusCode> {
+    let entries = get_blocklist(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((flash_messages, Blocklist { msg, entries }).into_response())
+}
+
+#[tracing::instrument(name = "Get blocked senders", skip(pg_pool))]
+async fn get_blocklist(pg_pool: &PgPool) -> Result<Vec<BlocklistEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        BlocklistEntry,
+        r#"SELECT entry, created_at FROM blocked_senders ORDER BY entry"#,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct BlocklistForm {
+    entry: String,
+}
+
+#[tracing::instrument(name = "Block a sender", skip(state, flash, form))]
+pub async fn block_sender(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<BlocklistForm>,
+) -> Result<Response, StatusCode> {
+    let entry = match BlockedSender::parse(form.0.entry) {
+        Ok(entry) => entry,
+        Err(e) => return Ok((flash.error(e), Redirect::to("/admin/blocklist")).into_response()),
+    };
+
+    let result = sqlx::query!(
+        r#"INSERT INTO blocked_senders (entry) VALUES ($1) ON CONFLICT DO NOTHING"#,
+        entry.as_ref(),
+    )
+    .execute(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?;
+    let flash = if result.rows_affected() == 0 {
+        flash.info(format!("{entry} is already blocked."))
+    } else {
+        flash.info(format!("{entry} can no longer be used to subscribe."))
+    };
+    Ok((flash, Redirect::to("/admin/blocklist")).into_response())
+}
+
+#[tracing::instrument(name = "Unblock a sender", skip(state, flash, form))]
+pub async fn unblock_sender(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<BlocklistForm>,
+) -> Result<Response, StatusCode> {
+    sqlx::query!(
+        r#"DELETE FROM blocked_senders WHERE entry = $1"#,
+        form.0.entry,
+    )
+    .execute(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?;
+    let flash = flash.info(format!("{} has been unblocked.", form.0.entry));
+    Ok((flash, Redirect::to("/admin/blocklist")).into_response())
+}
@@ -10,12 +10,13 @@ use axum_flash::Flash;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500, AppState};
+use crate::{authentication::UserId, domain::UserRole, utils::e500, AppState};
 
 #[derive(Template)]
 #[template(path = "admin/dashboard.html")]
 struct AdminDashboard {
     username: String,
+    role: UserRole,
 }
 
 pub async fn admin_dashboard(
@@ -23,12 +24,12 @@ pub async fn admin_dashboard(
     flash: Flash,
     user_id: Extension<UserId>,
 ) -> Result<Response, StatusCode> {
-    let user_id = *user_id.0;
-    let username = get_username(&state.pg_connection_pool, user_id)
+    let role = user_id.role();
+    let username = get_username(&state.pg_connection_pool, **user_id)
         .await
         .map_err(e500)?;
 
-    Ok((flash, AdminDashboard { username }).into_response())
+    Ok((flash, AdminDashboard { username, role }).into_response())
 }
 
 #[tracing::instrument(name = "Get username", skip(pg_pool))]
@@ -0,0 +1,132 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{ApplicationBaseUrl, ListSlug},
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Template)]
+#[template(path = "admin/lists.html")]
+struct Lists {
+    msg: String,
+    lists: Vec<ListSummary>,
+    base_url: ApplicationBaseUrl,
+}
+
+impl Lists {
+    fn subscribe_url(&self, list: &ListSummary) -> String {
+        let path = if list.is_default {
+            "subscriptions".to_string()
+        } else {
+            format!("subscriptions?list={}", list.slug)
+        };
+        self.base_url
+            .join(&path)
+            .map(|url| url.to_string())
+            .unwrap_or(path)
+    }
+}
+
+struct ListSummary {
+    slug: String,
+    name: String,
+    is_default: bool,
+    n_confirmed: i64,
+    n_pending: i64,
+}
+
+pub async fn lists(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+) -> Result<Response, StatusCode> {
+    let lists = get_list_summaries(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((
+        flash_messages,
+        Lists {
+            msg,
+            lists,
+            base_url: state.application_base_url.as_ref().clone(),
+        },
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Get mailing lists with member counts", skip(pg_pool))]
+async fn get_list_summaries(pg_pool: &PgPool) -> Result<Vec<ListSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        ListSummary,
+        r#"
+        SELECT
+            l.slug, l.name, l.is_default,
+            COUNT(m.subscriber_id) FILTER (WHERE m.status = 'confirmed') as "n_confirmed!",
+            COUNT(m.subscriber_id) FILTER (WHERE m.status = 'pending_confirmation') as "n_pending!"
+        FROM lists l
+        LEFT JOIN list_memberships m USING (list_id)
+        GROUP BY l.list_id
+        ORDER BY l.is_default DESC, l.name
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct NewListForm {
+    name: String,
+    slug: String,
+}
+
+#[tracing::instrument(name = "Create a mailing list", skip(state, flash, form))]
+pub async fn create_list(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<NewListForm>,
+) -> Result<Response, StatusCode> {
+    let name = form.0.name.trim().to_string();
+    let slug = match ListSlug::parse(form.0.slug.trim().to_string()) {
+        Ok(slug) => slug,
+        Err(e) => return Ok((flash.error(e), Redirect::to("/admin/lists")).into_response()),
+    };
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        let flash = flash.error(format!(
+            "A list name must have between 1 and {MAX_NAME_LENGTH} characters."
+        ));
+        return Ok((flash, Redirect::to("/admin/lists")).into_response());
+    }
+
+    let result = sqlx::query!(
+        r#"INSERT INTO lists (list_id, slug, name) VALUES ($1, $2, $3)"#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name,
+    )
+    .execute(&state.pg_connection_pool)
+    .await;
+    let flash = match result {
+        Ok(_) => flash.info(format!("Created the {name} list.")),
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            flash.error(format!("A list with the slug {slug} already exists."))
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    Ok((flash, Redirect::to("/admin/lists")).into_response())
+}
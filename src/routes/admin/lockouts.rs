@@ -0,0 +1,76 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::clear_login_failures,
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/lockouts.html")]
+struct Lockouts {
+    msg: String,
+    lockouts: Vec<Lockout>,
+}
+
+struct Lockout {
+    key: String,
+    n_failures: i32,
+    last_failure_at: DateTime<Utc>,
+    blocked_until: Option<DateTime<Utc>>,
+}
+
+pub async fn lockouts(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+) -> Result<Response, StatusCode> {
+    let lockouts = get_lockouts(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((flash_messages, Lockouts { msg, lockouts }).into_response())
+}
+
+/// Usernames and addresses whose logins are currently blocked or delayed.
+#[tracing::instrument(name = "Get login lockouts", skip(pg_pool))]
+async fn get_lockouts(pg_pool: &PgPool) -> Result<Vec<Lockout>, sqlx::Error> {
+    sqlx::query_as!(
+        Lockout,
+        r#"
+        SELECT key, n_failures, last_failure_at, blocked_until
+        FROM login_failures
+        WHERE blocked_until > now()
+        ORDER BY last_failure_at DESC
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct UnlockForm {
+    key: String,
+}
+
+#[tracing::instrument(name = "Unlock logins", skip(state, flash, form))]
+pub async fn unlock_login(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<UnlockForm>,
+) -> Result<Response, StatusCode> {
+    clear_login_failures(&state.pg_connection_pool, &form.0.key)
+        .await
+        .map_err(e500)?;
+    let flash = flash.info(format!("{} can log in again.", form.0.key));
+    Ok((flash, Redirect::to("/admin/lockouts")).into_response())
+}
@@ -1,12 +1,28 @@
 use axum::{
+    extract::{Extension, State},
     http::StatusCode,
     response::{IntoResponse, Redirect, Response},
 };
 use axum_flash::Flash;
 
-use crate::session_state::TypedSession;
+use crate::{
+    authentication::{revoke_session, UserId},
+    session_state::TypedSession,
+    utils::e500,
+    AppState,
+};
 
-pub async fn log_out(session: TypedSession, flash: Flash) -> Result<Response, StatusCode> {
+pub async fn log_out(
+    state: State<AppState>,
+    session: TypedSession,
+    flash: Flash,
+    user_id: Extension<UserId>,
+) -> Result<Response, StatusCode> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        revoke_session(&state.pg_connection_pool, **user_id, session_id)
+            .await
+            .map_err(e500)?;
+    }
     session.log_out();
     Ok((
         flash.info("You have successfully logged out."),
@@ -1,11 +1,26 @@
+mod blocklist;
 mod dashboard;
+mod lists;
+mod lockouts;
 mod logout;
 mod newsletter;
+mod pagination;
 mod password;
+mod security;
+mod segments;
+mod sessions;
 mod subscribers;
+mod users;
 
-pub use dashboard::admin_dashboard;
+pub use blocklist::{block_sender, blocklist, unblock_sender};
+pub use dashboard::{admin_dashboard, get_username};
+pub use lists::{create_list, lists};
+pub use lockouts::{lockouts, unlock_login};
 pub use logout::*;
 pub use newsletter::*;
 pub use password::*;
+pub use security::{disable_totp, enable_totp, security, update_account_email};
+pub use segments::{create_segment, segments};
+pub use sessions::{log_out_other_sessions, log_out_session, sessions};
 pub use subscribers::*;
+pub use users::{change_user_role, invite_user, user_action, users};
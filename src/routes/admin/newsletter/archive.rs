@@ -0,0 +1,64 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{utils::e500, AppState};
+
+#[derive(Deserialize)]
+pub struct FormData {
+    hidden: bool,
+}
+
+#[tracing::instrument(name = "Set archive visibility", skip(state, flash, form))]
+pub async fn set_archive_visibility(
+    state: State<AppState>,
+    flash: Flash,
+    path: Path<Uuid>,
+    form: Form<FormData>,
+) -> Result<Response, StatusCode> {
+    let newsletter_issue_id = path.0;
+    let updated =
+        update_archive_visibility(&state.pg_connection_pool, newsletter_issue_id, form.hidden)
+            .await
+            .map_err(e500)?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let flash = if form.hidden {
+        flash.info("The issue is now hidden from the public archive.")
+    } else {
+        flash.info("The issue is now visible in the public archive.")
+    };
+    let location = format!("/admin/issue/{newsletter_issue_id}");
+    Ok((flash, Redirect::to(&location)).into_response())
+}
+
+async fn update_archive_visibility(
+    pg_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    hidden: bool,
+) -> anyhow::Result<bool> {
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET hidden_from_archive = $2
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        hidden
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?
+    .rows_affected();
+
+    Ok(n_updated_rows > 0)
+}
@@ -1,31 +1,52 @@
 use askama_axum::Template;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use axum_flash::IncomingFlashes;
 use uuid::Uuid;
 
-use crate::utils::read_flash_messages;
+use super::recipients::{count_recipients, target_lists};
+use crate::{
+    mailing_lists::{get_all_lists, MailingList},
+    segments::{get_all_segments, Segment},
+    utils::{e500, read_flash_messages},
+    AppState,
+};
 
 #[derive(Template)]
 #[template(path = "admin/newsletter.html")]
 struct NewsletterIssue {
     msg: String,
     idempotency_key: Uuid,
+    lists: Vec<MailingList>,
+    segments: Vec<Segment>,
+    recipients: i64,
 }
 
 pub async fn publish_newsletter_form(
+    state: State<AppState>,
     flash_messages: IncomingFlashes,
 ) -> Result<Response, StatusCode> {
     let msg = read_flash_messages(&flash_messages);
     let idempotency_key = Uuid::new_v4();
+    let pg_pool = &state.pg_connection_pool;
+    let lists = get_all_lists(pg_pool).await.map_err(e500)?;
+    let segments = get_all_segments(pg_pool).await.map_err(e500)?;
+    let default_lists = target_lists(pg_pool, vec![]).await.map_err(e500)?;
+    let recipients = count_recipients(pg_pool, &default_lists, None)
+        .await
+        .map_err(e500)?;
 
     Ok((
         flash_messages,
         NewsletterIssue {
             msg,
             idempotency_key,
+            lists,
+            segments,
+            recipients,
         },
     )
         .into_response())
@@ -1,108 +1,293 @@
-use std::collections::HashMap;
-
 use askama_axum::{IntoResponse, Template};
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Response,
 };
 use axum_flash::IncomingFlashes;
-use sqlx::PgPool;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, QueryBuilder};
 use uuid::Uuid;
 
 use crate::{
-    utils::{e500, read_flash_messages},
+    routes::admin::pagination::{page_size, Cursor, KeyType, Keyset, Page, PageRequest, SortOrder},
+    utils::{e400, e500, read_flash_messages},
     AppState,
 };
 
 const IN_PROGRESS: &str = "IN PROGRESS";
+const PUBLISHED: &str = "PUBLISHED";
 
 #[derive(Template)]
 #[template(path = "admin/newsletter_issues.html")]
 struct NewsletterProgress {
     msg: String,
-    issues: HashMap<Uuid, NewsletterIssue>,
+    query: IssuesQuery,
+    issues: Page<IssueSummary>,
+}
+
+impl NewsletterProgress {
+    fn sort_url(&self, column: IssueSortColumn) -> String {
+        let order = if self.query.sort() == column {
+            self.query.order().toggled()
+        } else {
+            SortOrder::default()
+        };
+        self.query.url(IssuesQuery {
+            sort: Some(column),
+            order: Some(order),
+            ..self.query.filters()
+        })
+    }
+
+    fn next_url(&self) -> Option<String> {
+        self.issues.next_cursor.as_ref().map(|cursor| {
+            self.query.url(IssuesQuery {
+                after: Some(cursor.clone()),
+                ..self.query.filters()
+            })
+        })
+    }
+
+    fn prev_url(&self) -> Option<String> {
+        self.issues.prev_cursor.as_ref().map(|cursor| {
+            self.query.url(IssuesQuery {
+                before: Some(cursor.clone()),
+                ..self.query.filters()
+            })
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum IssueSortColumn {
+    #[default]
+    PublishedAt,
+    Title,
+}
+
+impl IssueSortColumn {
+    fn keyset(self, order: SortOrder) -> Keyset {
+        let (column, key_type) = match self {
+            Self::PublishedAt => ("published_at", KeyType::Timestamp),
+            Self::Title => ("title", KeyType::Text),
+        };
+        Keyset {
+            column,
+            key_type,
+            id_column: "newsletter_issue_id",
+            order,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct IssuesQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort: Option<IssueSortColumn>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<SortOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<String>,
+}
+
+impl IssuesQuery {
+    fn search(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
+
+    fn sort(&self) -> IssueSortColumn {
+        self.sort.unwrap_or_default()
+    }
+
+    fn order(&self) -> SortOrder {
+        self.order.unwrap_or_default()
+    }
+
+    /// The current query without its pagination cursors.
+    fn filters(&self) -> Self {
+        Self {
+            q: self.q.clone(),
+            sort: self.sort,
+            order: self.order,
+            limit: self.limit,
+            after: None,
+            before: None,
+        }
+    }
+
+    fn url(&self, query: Self) -> String {
+        let query = serde_urlencoded::to_string(query).unwrap_or_default();
+        format!("/admin/issues?{query}")
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+    status: String,
 }
 
 struct NewsletterIssue {
     newsletter_issue_id: Uuid,
     title: String,
     content: String,
-    published_at: String,
-    status: String,
+    published_at: DateTime<Utc>,
+    slug: String,
+    hidden_from_archive: bool,
 }
 
 pub async fn issues(
     state: State<AppState>,
     flash_messages: IncomingFlashes,
+    query: Query<IssuesQuery>,
 ) -> Result<Response, StatusCode> {
     let msg = read_flash_messages(&flash_messages);
-    let issues = get_all_newsletter_issues(&state.pg_connection_pool)
+    let query = query.0;
+    let keyset = query.sort().keyset(query.order());
+    let page = PageRequest::parse(query.after.as_deref(), query.before.as_deref(), &keyset)
+        .map_err(e400)?;
+    let issues = get_newsletter_issues(&state.pg_connection_pool, &query, &page)
         .await
         .map_err(e500)?;
 
-    Ok((flash_messages, NewsletterProgress { msg, issues }).into_response())
+    Ok((flash_messages, NewsletterProgress { msg, query, issues }).into_response())
 }
 
-async fn get_all_newsletter_issues(
+#[tracing::instrument(name = "Get newsletter issues", skip_all)]
+async fn get_newsletter_issues(
     pg_pool: &PgPool,
-) -> anyhow::Result<HashMap<Uuid, NewsletterIssue>> {
-    let mut all_issues: HashMap<Uuid, NewsletterIssue> = sqlx::query_as_unchecked!(
-        NewsletterIssue,
-        r#"
-        SELECT newsletter_issue_id, title, content, published_at, 'PUBLISHED' as status
-        FROM newsletter_issues
-        "#,
-    )
-    .fetch_all(pg_pool)
-    .await
-    .map_err(|e| anyhow::anyhow!(e))?
-    .into_iter()
-    .map(|issue| (issue.newsletter_issue_id, issue))
-    .collect();
+    query: &IssuesQuery,
+    page: &PageRequest,
+) -> anyhow::Result<Page<IssueSummary>> {
+    let sort = query.sort();
+    let keyset = sort.keyset(query.order());
+    let page_size = page_size(query.limit);
 
-    let running_issues: Vec<Uuid> = sqlx::query!(
+    let mut builder = QueryBuilder::new(
         r#"
-        SELECT DISTINCT newsletter_issue_id
-        FROM issue_delivery_queue
-        "#,
-    )
-    .fetch_all(pg_pool)
-    .await
-    .map_err(|e| anyhow::anyhow!(e))?
-    .into_iter()
-    .map(|r| r.newsletter_issue_id)
-    .collect();
-
-    running_issues.into_iter().for_each(|issue_id| {
-        if let Some(issue) = all_issues.get_mut(&issue_id) {
-            issue.status = IN_PROGRESS.to_owned();
-        }
-    });
+        SELECT
+            newsletter_issue_id,
+            title,
+            published_at,
+            CASE
+                WHEN EXISTS (
+                    SELECT 1
+                    FROM issue_delivery_queue as q
+                    WHERE q.newsletter_issue_id = i.newsletter_issue_id
+                ) THEN "#,
+    );
+    builder
+        .push_bind(IN_PROGRESS)
+        .push(" ELSE ")
+        .push_bind(PUBLISHED)
+        .push(" END as status FROM newsletter_issues as i WHERE TRUE");
+    if let Some(search) = query.search() {
+        builder
+            .push(" AND title_search @@ websearch_to_tsquery('english', ")
+            .push_bind(search)
+            .push(")");
+    }
+    keyset.push_condition(&mut builder, page);
+    keyset.push_order_and_limit(&mut builder, page, page_size);
+
+    let rows = builder
+        .build_query_as::<IssueSummary>()
+        .fetch_all(pg_pool)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
 
-    Ok(all_issues)
+    Ok(Page::from_rows(rows, page, page_size, |issue| Cursor {
+        key: match sort {
+            IssueSortColumn::PublishedAt => issue
+                .published_at
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            IssueSortColumn::Title => issue.title.clone(),
+        },
+        id: issue.newsletter_issue_id,
+    }))
 }
 
 #[derive(Template)]
 #[template(path = "admin/newsletter_issue.html")]
 struct NewsletterIssueMeta {
+    msg: String,
     issue: NewsletterIssue,
+    stats: DeliveryStats,
+}
+
+struct DeliveryStats {
+    n_pending: i64,
+    n_sent: i64,
+    n_failed: i64,
+    n_retries: i64,
+    started_at: Option<DateTime<Utc>>,
+    last_completed_at: Option<DateTime<Utc>>,
+}
+
+impl DeliveryStats {
+    fn n_recipients(&self) -> i64 {
+        self.n_pending + self.n_sent + self.n_failed
+    }
+
+    fn is_in_progress(&self) -> bool {
+        self.n_pending > 0
+    }
+
+    fn finished_at(&self) -> Option<DateTime<Utc>> {
+        if self.is_in_progress() {
+            None
+        } else {
+            self.last_completed_at
+        }
+    }
+
+    /// Deliveries completed per minute since the first task was picked up.
+    fn send_rate(&self) -> f64 {
+        let Some(started_at) = self.started_at else {
+            return 0.0;
+        };
+        let until = self.finished_at().unwrap_or_else(Utc::now);
+        let elapsed_secs = (until - started_at).num_milliseconds() as f64 / 1000.0;
+        if elapsed_secs <= 0.0 {
+            return 0.0;
+        }
+        (self.n_sent + self.n_failed) as f64 * 60.0 / elapsed_secs
+    }
 }
 
-pub async fn issue(state: State<AppState>, path: Path<Uuid>) -> Result<Response, StatusCode> {
+pub async fn issue(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    path: Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let msg = read_flash_messages(&flash_messages);
     let newsletter_issue_id = path.0;
     let issue = get_issue(&state.pg_connection_pool, newsletter_issue_id)
         .await
         .map_err(e500)?;
+    let stats = get_delivery_stats(&state.pg_connection_pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
 
-    Ok(NewsletterIssueMeta { issue }.into_response())
+    Ok((flash_messages, NewsletterIssueMeta { msg, issue, stats }).into_response())
 }
 
 async fn get_issue(pg_pool: &PgPool, newsletter_issue_id: Uuid) -> anyhow::Result<NewsletterIssue> {
-    let issue = sqlx::query_as_unchecked!(
+    let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT newsletter_issue_id, title, content, published_at, '' as status
+        SELECT newsletter_issue_id, title, content, published_at, slug, hidden_from_archive
         FROM newsletter_issues
         WHERE newsletter_issue_id = $1
         "#,
@@ -114,3 +299,46 @@ async fn get_issue(pg_pool: &PgPool, newsletter_issue_id: Uuid) -> anyhow::Resul
 
     Ok(issue)
 }
+
+#[tracing::instrument(name = "Get delivery statistics", skip(pg_pool))]
+async fn get_delivery_stats(
+    pg_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> anyhow::Result<DeliveryStats> {
+    let stats = sqlx::query_as!(
+        DeliveryStats,
+        r#"
+        SELECT
+            queue.n_pending as "n_pending!",
+            log.n_sent as "n_sent!",
+            log.n_failed as "n_failed!",
+            queue.n_retries + log.n_retries as "n_retries!",
+            issue.delivery_started_at as started_at,
+            log.last_completed_at
+        FROM newsletter_issues as issue,
+        LATERAL (
+            SELECT
+                COUNT(*) as n_pending,
+                COALESCE(SUM(n_retries), 0) as n_retries
+            FROM issue_delivery_queue
+            WHERE newsletter_issue_id = issue.newsletter_issue_id
+        ) as queue,
+        LATERAL (
+            SELECT
+                COUNT(*) FILTER (WHERE outcome = 'sent') as n_sent,
+                COUNT(*) FILTER (WHERE outcome = 'failed') as n_failed,
+                COALESCE(SUM(n_retries), 0) as n_retries,
+                MAX(completed_at) as last_completed_at
+            FROM issue_delivery_log
+            WHERE newsletter_issue_id = issue.newsletter_issue_id
+        ) as log
+        WHERE issue.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(stats)
+}
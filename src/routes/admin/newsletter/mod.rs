@@ -1,7 +1,11 @@
+mod archive;
 mod get;
 mod issues;
 mod post;
+mod recipients;
 
+pub use archive::set_archive_visibility;
 pub use get::publish_newsletter_form;
 pub use issues::{issue, issues};
 pub use post::publish_newsletter;
+pub use recipients::newsletter_recipients;
@@ -3,18 +3,24 @@ use axum::{
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Redirect, Response},
-    Extension, Form,
+    Extension,
 };
+use axum_extra::extract::Form;
 use axum_flash::Flash;
+use chrono::Utc;
 use serde::Deserialize;
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
 
+use super::recipients::{push_recipients, target_lists};
 use crate::{
     authentication::UserId,
-    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    domain::{
+        DigestFrequency, IssueSlug, SegmentDefinition, Subscriber, SubscriberEmail, SubscriberName,
+    },
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
-    utils::{e400, e500},
+    segments::get_segment,
+    utils::{e400, e500, empty_as_none},
     AppState,
 };
 
@@ -23,6 +29,10 @@ pub struct FormData {
     title: String,
     content: String,
     idempotency_key: String,
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    segment_id: Option<Uuid>,
 }
 
 #[tracing::instrument(
@@ -40,6 +50,8 @@ pub async fn publish_newsletter(
         title,
         content,
         idempotency_key,
+        list_ids,
+        segment_id,
     } = form.0;
     let user_id = *user_id.0;
 
@@ -55,11 +67,29 @@ pub async fn publish_newsletter(
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &content)
+    let segment = match segment_id {
+        Some(segment_id) => Some(
+            get_segment(&mut *transaction, segment_id)
+                .await
+                .context("Failed to fetch the segment")
+                .map_err(e500)?
+                .ok_or_else(|| e400("The selected segment does not exist."))?,
+        ),
+        None => None,
+    };
+    let issue_id = insert_newsletter_issue(&mut transaction, &title, &content, segment_id)
         .await
         .context("Failed to store newsletter issue details")
         .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let list_ids = target_lists(&mut *transaction, list_ids)
+        .await
+        .context("Failed to resolve the target lists")
+        .map_err(e500)?;
+    if list_ids.is_empty() {
+        return Err(e400("None of the selected lists exist."));
+    }
+    let segment = segment.as_ref().map(|s| &s.definition.0);
+    enqueue_delivery_tasks(&mut transaction, issue_id, &list_ids, segment)
         .await
         .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
@@ -103,45 +133,71 @@ async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     content: &str,
+    segment_id: Option<Uuid>,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
+    let slug = IssueSlug::generate(title, newsletter_issue_id);
     sqlx::query!(
         r#"
         INSERT INTO newsletter_issues (
             newsletter_issue_id,
             title,
             content,
-            published_at
+            published_at,
+            slug,
+            segment_id
         )
-        VALUES ($1, $2, $3, now())
+        VALUES ($1, $2, $3, now(), $4, $5)
         "#,
         newsletter_issue_id,
         title,
         content,
+        slug.as_ref(),
+        segment_id,
     )
     .execute(&mut **transaction)
     .await?;
     Ok(newsletter_issue_id)
 }
 
+/// Queues one delivery per recipient. Deliveries to subscribers on a digest are held
+/// until their next digest goes out.
 #[tracing::instrument(skip_all)]
 async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+    segment: Option<&SegmentDefinition>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT $1, list_id FROM UNNEST($2::uuid[]) AS list_id
         "#,
         newsletter_issue_id,
+        list_ids,
     )
     .execute(&mut **transaction)
     .await?;
+    let now = Utc::now();
+    let (frequencies, deliver_at): (Vec<_>, Vec<_>) = DigestFrequency::ALL
+        .iter()
+        .map(|frequency| (frequency.as_str().to_string(), frequency.next_delivery(now)))
+        .unzip();
+    let mut query = QueryBuilder::new(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email, execute_after) \
+        SELECT ",
+    );
+    query
+        .push_bind(newsletter_issue_id)
+        .push(", s.email, COALESCE((SELECT d.deliver_at FROM UNNEST(")
+        .push_bind(frequencies)
+        .push("::text[], ")
+        .push_bind(deliver_at)
+        .push("::timestamptz[]) AS d (frequency, deliver_at) WHERE d.frequency = s.digest_frequency), ")
+        .push_bind(now)
+        .push(")");
+    push_recipients(&mut query, list_ids, segment, now);
+    query.build().execute(&mut **transaction).await?;
     Ok(())
 }
@@ -0,0 +1,111 @@
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    domain::SegmentDefinition,
+    mailing_lists::get_default_list,
+    segments::{get_segment, push_segment_conditions},
+    utils::{e400, e500, empty_as_none},
+    AppState,
+};
+
+/// The lists an issue goes to. Clients that do not pick any, such as those written
+/// before there were several lists, keep sending to the default list.
+pub(super) async fn target_lists(
+    executor: impl PgExecutor<'_>,
+    list_ids: Vec<Uuid>,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    if list_ids.is_empty() {
+        let list = get_default_list(executor).await?;
+        return Ok(vec![list.list_id]);
+    }
+    sqlx::query_scalar!(
+        r#"SELECT list_id FROM lists WHERE list_id = ANY($1)"#,
+        &list_ids,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Appends the `FROM` and `WHERE` clauses selecting who an issue sent at `now` reaches:
+/// the confirmed members of `list_ids` that have not paused their emails, narrowed down
+/// to the segment if there is one. Subscribers are aliased as `s`.
+pub(super) fn push_recipients(
+    query: &mut QueryBuilder<'_, Postgres>,
+    list_ids: &[Uuid],
+    segment: Option<&SegmentDefinition>,
+    now: DateTime<Utc>,
+) {
+    query
+        .push(
+            " FROM subscriptions s WHERE EXISTS (\
+            SELECT 1 FROM list_memberships m \
+            WHERE m.subscriber_id = s.id AND m.status = 'confirmed' AND m.list_id = ANY(",
+        )
+        .push_bind(list_ids.to_vec())
+        .push(")) AND (s.paused_until IS NULL OR s.paused_until <= ")
+        .push_bind(now)
+        .push(")");
+    if let Some(segment) = segment {
+        push_segment_conditions(query, segment);
+    }
+}
+
+#[tracing::instrument(name = "Count the recipients of an issue", skip(pg_pool))]
+pub(super) async fn count_recipients(
+    pg_pool: &PgPool,
+    list_ids: &[Uuid],
+    segment: Option<&SegmentDefinition>,
+) -> Result<i64, sqlx::Error> {
+    let mut query = QueryBuilder::new("SELECT COUNT(*)");
+    push_recipients(&mut query, list_ids, segment, Utc::now());
+    query.build_query_scalar().fetch_one(pg_pool).await
+}
+
+#[derive(Deserialize)]
+pub struct RecipientsQuery {
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    segment_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+pub struct RecipientCount {
+    recipients: i64,
+}
+
+/// How many subscribers an issue would reach with the lists and segment picked on the
+/// newsletter form.
+pub async fn newsletter_recipients(
+    state: State<AppState>,
+    query: Query<RecipientsQuery>,
+) -> Result<Json<RecipientCount>, StatusCode> {
+    let RecipientsQuery {
+        list_ids,
+        segment_id,
+    } = query.0;
+    let pg_pool = &state.pg_connection_pool;
+    let list_ids = target_lists(pg_pool, list_ids).await.map_err(e500)?;
+    let segment = match segment_id {
+        Some(segment_id) => Some(
+            get_segment(pg_pool, segment_id)
+                .await
+                .map_err(e500)?
+                .ok_or_else(|| e400("Unknown segment"))?,
+        ),
+        None => None,
+    };
+    let recipients = count_recipients(
+        pg_pool,
+        &list_ids,
+        segment.as_ref().map(|s| &s.definition.0),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(Json(RecipientCount { recipients }))
+}
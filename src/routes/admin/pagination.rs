@@ -0,0 +1,293 @@
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Asc => Self::Desc,
+            Self::Desc => Self::Asc,
+        }
+    }
+
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+
+    fn comparison(self) -> &'static str {
+        match self {
+            Self::Asc => ">",
+            Self::Desc => "<",
+        }
+    }
+}
+
+/// Position of a row in a keyset-paginated listing: the value of the sort column and the row id
+/// used as a tie-breaker.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub key: String,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.id, self.key))
+    }
+
+    pub fn decode(s: &str) -> anyhow::Result<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(s)?)?;
+        let (id, key) = decoded
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Malformed pagination cursor."))?;
+        Ok(Self {
+            key: key.to_owned(),
+            id: Uuid::parse_str(id)?,
+        })
+    }
+}
+
+pub enum PageRequest {
+    First,
+    After(Cursor),
+    Before(Cursor),
+}
+
+impl PageRequest {
+    /// Cursor keys are checked against the keyset's column type here, so that a malformed
+    /// cursor is a bad request rather than a failed query.
+    pub fn parse(
+        after: Option<&str>,
+        before: Option<&str>,
+        keyset: &Keyset,
+    ) -> anyhow::Result<Self> {
+        let page = match (after, before) {
+            (Some(after), _) => Self::After(Cursor::decode(after)?),
+            (None, Some(before)) => Self::Before(Cursor::decode(before)?),
+            (None, None) => Self::First,
+        };
+        if let Self::After(cursor) | Self::Before(cursor) = &page {
+            keyset.key_type.validate(&cursor.key)?;
+        }
+        Ok(page)
+    }
+}
+
+pub fn page_size(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Type of the sort column of a keyset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Timestamp,
+    Text,
+}
+
+impl KeyType {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Timestamp => "timestamptz",
+            Self::Text => "text",
+        }
+    }
+
+    fn validate(self, key: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Timestamp => {
+                DateTime::parse_from_rfc3339(key).context("Malformed pagination cursor.")?;
+            }
+            Self::Text => {
+                anyhow::ensure!(!key.contains('\0'), "Malformed pagination cursor.");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sort column of a keyset-paginated query.
+pub struct Keyset {
+    pub column: &'static str,
+    pub key_type: KeyType,
+    pub id_column: &'static str,
+    pub order: SortOrder,
+}
+
+impl Keyset {
+    /// Walking backwards from a cursor means reading the rows in reverse order.
+    fn effective_order(&self, page: &PageRequest) -> SortOrder {
+        match page {
+            PageRequest::Before(_) => self.order.toggled(),
+            _ => self.order,
+        }
+    }
+
+    /// Appends the keyset condition. The builder must already contain a `WHERE` clause.
+    pub fn push_condition(&self, query: &mut QueryBuilder<'_, Postgres>, page: &PageRequest) {
+        let cursor = match page {
+            PageRequest::First => return,
+            PageRequest::After(cursor) | PageRequest::Before(cursor) => cursor,
+        };
+        query
+            .push(format_args!(
+                " AND ({}, {}) {} (",
+                self.column,
+                self.id_column,
+                self.effective_order(page).comparison()
+            ))
+            .push_bind(cursor.key.clone())
+            .push(format_args!("::{}, ", self.key_type.as_sql()))
+            .push_bind(cursor.id)
+            .push(")");
+    }
+
+    /// Appends `ORDER BY` and `LIMIT`, fetching one extra row to know whether another page exists.
+    pub fn push_order_and_limit(
+        &self,
+        query: &mut QueryBuilder<'_, Postgres>,
+        page: &PageRequest,
+        page_size: i64,
+    ) {
+        let order = self.effective_order(page).as_sql();
+        query
+            .push(format_args!(
+                " ORDER BY {} {order}, {} {order} LIMIT ",
+                self.column, self.id_column
+            ))
+            .push_bind(page_size + 1);
+    }
+}
+
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn from_rows(
+        mut rows: Vec<T>,
+        page: &PageRequest,
+        page_size: i64,
+        cursor: impl Fn(&T) -> Cursor,
+    ) -> Self {
+        let has_more = rows.len() as i64 > page_size;
+        rows.truncate(page_size as usize);
+        if let PageRequest::Before(_) = page {
+            rows.reverse();
+        }
+
+        let (has_next, has_prev) = match page {
+            PageRequest::First => (has_more, false),
+            PageRequest::After(_) => (has_more, true),
+            PageRequest::Before(_) => (true, has_more),
+        };
+        let next_cursor = rows.last().filter(|_| has_next).map(|r| cursor(r).encode());
+        let prev_cursor = rows
+            .first()
+            .filter(|_| has_prev)
+            .map(|r| cursor(r).encode());
+
+        Self {
+            items: rows,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{Cursor, KeyType, Keyset, Page, PageRequest, SortOrder};
+
+    fn cursor(key: &i32) -> Cursor {
+        Cursor {
+            key: key.to_string(),
+            id: Uuid::nil(),
+        }
+    }
+
+    #[test]
+    fn cursors_survive_a_round_trip() {
+        let cursor = Cursor {
+            key: "2024-01-16T14:30:05.123456Z: a title".into(),
+            id: Uuid::new_v4(),
+        };
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded.key, cursor.key);
+        assert_eq!(decoded.id, cursor.id);
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert!(Cursor::decode("not base64!").is_err());
+        assert!(Cursor::decode("bm8tY29sb24").is_err());
+    }
+
+    #[test]
+    fn cursor_keys_must_match_the_column_type() {
+        let keyset = |key_type| Keyset {
+            column: "column",
+            key_type,
+            id_column: "id",
+            order: SortOrder::Desc,
+        };
+        let encode = |key: &str| {
+            Cursor {
+                key: key.into(),
+                id: Uuid::nil(),
+            }
+            .encode()
+        };
+        let timestamps = keyset(KeyType::Timestamp);
+        let timestamp = encode("2024-01-16T14:30:05.123456Z");
+        assert!(PageRequest::parse(Some(&timestamp), None, &timestamps).is_ok());
+        assert!(PageRequest::parse(None, Some(&encode("yesterday")), &timestamps).is_err());
+        let text = keyset(KeyType::Text);
+        assert!(PageRequest::parse(Some(&encode("yesterday")), None, &text).is_ok());
+        assert!(PageRequest::parse(Some(&encode("a\0b")), None, &text).is_err());
+    }
+
+    #[test]
+    fn first_page_only_links_forward() {
+        let page = Page::from_rows(vec![1, 2, 3], &PageRequest::First, 2, cursor);
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.next_cursor.is_some());
+        assert!(page.prev_cursor.is_none());
+    }
+
+    #[test]
+    fn last_page_only_links_backward() {
+        let page = Page::from_rows(vec![3], &PageRequest::After(cursor(&2)), 2, cursor);
+        assert_eq!(page.items, vec![3]);
+        assert!(page.next_cursor.is_none());
+        assert!(page.prev_cursor.is_some());
+    }
+
+    #[test]
+    fn pages_read_backwards_are_put_back_in_order() {
+        let page = Page::from_rows(vec![2, 1], &PageRequest::Before(cursor(&3)), 2, cursor);
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.next_cursor.is_some());
+        assert!(page.prev_cursor.is_none());
+    }
+}
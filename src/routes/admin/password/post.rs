@@ -9,8 +9,9 @@ use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials, UserId},
+    authentication::{revoke_other_sessions, validate_credentials, AuthError, Credentials, UserId},
     routes::admin::dashboard::get_username,
+    session_state::TypedSession,
     utils::e500,
     AppState,
 };
@@ -25,6 +26,7 @@ pub struct FormData {
 pub async fn change_password(
     state: State<AppState>,
     flash: Flash,
+    session: TypedSession,
     user_id: Extension<UserId>,
     form: Form<FormData>,
 ) -> Result<Response, StatusCode> {
@@ -40,10 +42,16 @@ pub async fn change_password(
         .await
         .map_err(e500)?;
     let credentials = Credentials {
-        username,
+        username: username.clone(),
         password: form.0.current_password,
     };
-    if let Err(e) = validate_credentials(&state.pg_connection_pool, credentials).await {
+    if let Err(e) = validate_credentials(
+        &state.pg_connection_pool,
+        credentials,
+        &state.password_hashing,
+    )
+    .await
+    {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 let flash = flash.error("The current password is incorrect.");
@@ -53,9 +61,23 @@ pub async fn change_password(
         };
     }
 
-    crate::authentication::change_password(&state.pg_connection_pool, user_id, form.0.new_password)
+    if let Err(e) = state.password_policy.check(&form.0.new_password, &username) {
+        return Ok((flash.error(e.to_string()), Redirect::to("/admin/password")).into_response());
+    }
+
+    crate::authentication::change_password(
+        &state.pg_connection_pool,
+        user_id,
+        form.0.new_password,
+        &state.password_hashing,
+    )
+    .await
+    .map_err(e500)?;
+    let current_session = session.get_session_id().map_err(e500)?;
+    revoke_other_sessions(&state.pg_connection_pool, user_id, current_session)
         .await
         .map_err(e500)?;
-    let flash = flash.info("Your password has been changed.");
+    let flash =
+        flash.info("Your password has been changed. Your other sessions have been logged out.");
     Ok((flash, Redirect::to("/admin/password")).into_response())
 }
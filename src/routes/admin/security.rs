@@ -0,0 +1,282 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::Utc;
+use qrcode::{render::svg, QrCode};
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        disable_two_factor, enable_two_factor, generate_recovery_codes, two_factor_enabled,
+        unused_recovery_codes, validate_credentials, verify_second_factor, AuthError, Credentials,
+        Totp, UserId,
+    },
+    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    utils::{e500, read_flash_messages, spawn_and_log_error},
+    AppState,
+};
+
+/// The issuer authenticator apps list the codes under.
+const TOTP_ISSUER: &str = "Newsletter";
+
+#[derive(Template)]
+#[template(path = "admin/security.html")]
+struct Security {
+    msg: String,
+    email: Option<String>,
+    enrolment: Option<Enrolment>,
+    unused_recovery_codes: i64,
+}
+
+/// What a user needs to set up their authenticator.
+struct Enrolment {
+    secret: String,
+    otpauth_uri: String,
+    qr_code: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/recovery_codes.html")]
+struct RecoveryCodes {
+    codes: Vec<String>,
+}
+
+pub async fn security(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    session: TypedSession,
+    user_id: Extension<UserId>,
+) -> Result<Response, StatusCode> {
+    let user_id = *user_id.0;
+    let pg_pool = &state.pg_connection_pool;
+    let msg = read_flash_messages(&flash_messages);
+    let email = get_account_email(pg_pool, user_id).await.map_err(e500)?;
+
+    if two_factor_enabled(pg_pool, user_id).await.map_err(e500)? {
+        let unused_recovery_codes = unused_recovery_codes(pg_pool, user_id)
+            .await
+            .map_err(e500)?;
+        let page = Security {
+            msg,
+            email,
+            enrolment: None,
+            unused_recovery_codes,
+        };
+        return Ok((flash_messages, page).into_response());
+    }
+
+    // The same secret is shown until it is confirmed, so reloading the page does not
+    // invalidate an authenticator that was already set up
+    let totp = match session
+        .get_totp_enrolment()
+        .map_err(e500)?
+        .and_then(|secret| Totp::from_base32(&secret))
+    {
+        Some(totp) => totp,
+        None => {
+            let totp = Totp::generate();
+            session
+                .insert_totp_enrolment(&totp.to_base32())
+                .map_err(e500)?;
+            totp
+        }
+    };
+    let username = get_username(pg_pool, user_id).await.map_err(e500)?;
+    let otpauth_uri = totp.otpauth_uri(TOTP_ISSUER, &username);
+    let qr_code = QrCode::new(otpauth_uri.as_bytes())
+        .map_err(e500)?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    let page = Security {
+        msg,
+        email,
+        enrolment: Some(Enrolment {
+            secret: totp.to_base32(),
+            otpauth_uri,
+            qr_code,
+        }),
+        unused_recovery_codes: 0,
+    };
+    Ok((flash_messages, page).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct CodeForm {
+    code: String,
+}
+
+/// Enables two-factor authentication once the user proves their authenticator works,
+/// and shows the recovery codes. They are not shown again.
+#[tracing::instrument(name = "Enable TOTP", skip(state, flash, session, form))]
+pub async fn enable_totp(
+    state: State<AppState>,
+    flash: Flash,
+    session: TypedSession,
+    user_id: Extension<UserId>,
+    form: Form<CodeForm>,
+) -> Result<Response, StatusCode> {
+    let user_id = *user_id.0;
+    let Some(totp) = session
+        .get_totp_enrolment()
+        .map_err(e500)?
+        .and_then(|secret| Totp::from_base32(&secret))
+    else {
+        let flash = flash.error("Please scan the QR code again.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    };
+    let Some(step) = totp.verify(&form.0.code, Utc::now(), None) else {
+        let flash = flash.error("The code is incorrect. Please check your device's clock.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    };
+
+    let codes = generate_recovery_codes();
+    enable_two_factor(&state.pg_connection_pool, user_id, &totp, step, &codes)
+        .await
+        .map_err(e500)?;
+    session.remove_totp_enrolment().map_err(e500)?;
+    Ok(RecoveryCodes { codes }.into_response())
+}
+
+#[tracing::instrument(name = "Disable TOTP", skip(state, flash, form))]
+pub async fn disable_totp(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    form: Form<CodeForm>,
+) -> Result<Response, StatusCode> {
+    let user_id = *user_id.0;
+    let pg_pool = &state.pg_connection_pool;
+    if !verify_second_factor(pg_pool, user_id, &form.0.code)
+        .await
+        .map_err(e500)?
+    {
+        let flash = flash.error("The code is incorrect.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    }
+    disable_two_factor(pg_pool, user_id).await.map_err(e500)?;
+    let flash = flash.info("Two-factor authentication has been disabled.");
+    Ok((flash, Redirect::to("/admin/security")).into_response())
+}
+
+#[tracing::instrument(name = "Get account email", skip(pg_pool))]
+async fn get_account_email(pg_pool: &PgPool, user_id: Uuid) -> anyhow::Result<Option<String>> {
+    sqlx::query_scalar!(r#"SELECT email FROM users WHERE user_id = $1"#, user_id)
+        .fetch_one(pg_pool)
+        .await
+        .context("Failed to look up the account email")
+}
+
+#[derive(Deserialize)]
+pub struct EmailForm {
+    email: String,
+    current_password: Secret<String>,
+    /// Only checked when two-factor authentication is enabled.
+    #[serde(default)]
+    code: String,
+}
+
+/// Sets the address password reset links are sent to. An empty address removes it.
+/// Whoever reads that address can take the account over, so changing it takes the current
+/// password and, with two-factor authentication, a code. The previous address is told.
+#[tracing::instrument(name = "Update account email", skip(state, flash, form))]
+pub async fn update_account_email(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    form: Form<EmailForm>,
+) -> Result<Response, StatusCode> {
+    let user_id = *user_id.0;
+    let pg_pool = &state.pg_connection_pool;
+    let form = form.0;
+    let email = match form.email.trim() {
+        "" => None,
+        email => match SubscriberEmail::parse(email.to_string()) {
+            Ok(email) => Some(email),
+            Err(e) => return Ok((flash.error(e), Redirect::to("/admin/security")).into_response()),
+        },
+    };
+
+    let username = get_username(pg_pool, user_id).await.map_err(e500)?;
+    let credentials = Credentials {
+        username: username.clone(),
+        password: form.current_password,
+    };
+    if let Err(e) = validate_credentials(pg_pool, credentials, &state.password_hashing).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                let flash = flash.error("The current password is incorrect.");
+                Ok((flash, Redirect::to("/admin/security")).into_response())
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+    if two_factor_enabled(pg_pool, user_id).await.map_err(e500)?
+        && !verify_second_factor(pg_pool, user_id, &form.code)
+            .await
+            .map_err(e500)?
+    {
+        let flash = flash.error("The code is incorrect.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    }
+
+    let previous = get_account_email(pg_pool, user_id).await.map_err(e500)?;
+    sqlx::query!(
+        r#"UPDATE users SET email = $2 WHERE user_id = $1"#,
+        user_id,
+        email.as_ref().map(AsRef::<str>::as_ref),
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(e500)?;
+    if let Some(previous) =
+        previous.filter(|previous| Some(previous.as_str()) != email.as_ref().map(AsRef::as_ref))
+    {
+        notify_previous_email(&state, previous, username);
+    }
+
+    let flash = match email {
+        Some(email) => flash.info(format!("Password reset links will be sent to {email}.")),
+        None => flash.info("Your account no longer has an email address."),
+    };
+    Ok((flash, Redirect::to("/admin/security")).into_response())
+}
+
+/// Tells the address an account used to have that it was changed, in the background.
+fn notify_previous_email(state: &AppState, previous: String, username: String) {
+    let recipient = match (
+        SubscriberEmail::parse(previous),
+        SubscriberName::parse(username),
+    ) {
+        (Ok(email), Ok(name)) => Subscriber { email, name },
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::warn!(
+                error.message = %e,
+                "Cannot tell the previous address about the change. It or the username is invalid",
+            );
+            return;
+        }
+    };
+    let email_client = state.email_client.clone();
+    spawn_and_log_error(async move {
+        email_client
+            .send_email(
+                &recipient,
+                "Your account's email address was changed",
+                "Password reset links for your account are no longer sent to this address.<br />\
+                If you did not change it, please contact an owner of the newsletter straight away.",
+            )
+            .await
+    });
+}
@@ -0,0 +1,122 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use sqlx::types::Json;
+use uuid::Uuid;
+
+use crate::{
+    domain::{AttributeKey, SegmentCondition, SegmentDefinition, SubscriberTag},
+    segments::{get_all_segments, Segment},
+    utils::{e500, empty_as_none, read_flash_messages},
+    AppState,
+};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Template)]
+#[template(path = "admin/segments.html")]
+struct Segments {
+    msg: String,
+    segments: Vec<Segment>,
+}
+
+pub async fn segments(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+) -> Result<Response, StatusCode> {
+    let segments = get_all_segments(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((flash_messages, Segments { msg, segments }).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct NewSegmentForm {
+    name: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    subscribed_after: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    subscribed_before: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    tag: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    attribute_key: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    attribute_value: Option<String>,
+}
+
+impl TryFrom<NewSegmentForm> for SegmentDefinition {
+    type Error = String;
+
+    fn try_from(form: NewSegmentForm) -> Result<Self, Self::Error> {
+        let mut conditions = Vec::new();
+        if let Some(date) = form.subscribed_after {
+            conditions.push(SegmentCondition::SubscribedAfter { date });
+        }
+        if let Some(date) = form.subscribed_before {
+            conditions.push(SegmentCondition::SubscribedBefore { date });
+        }
+        if let Some(tag) = form.tag {
+            conditions.push(SegmentCondition::HasTag {
+                tag: SubscriberTag::parse(&tag)?,
+            });
+        }
+        match (form.attribute_key, form.attribute_value) {
+            (Some(key), Some(value)) => conditions.push(SegmentCondition::AttributeEquals {
+                key: AttributeKey::parse(&key)?,
+                value,
+            }),
+            (None, None) => {}
+            _ => return Err("An attribute condition needs both a name and a value.".into()),
+        }
+        SegmentDefinition::new(conditions)
+    }
+}
+
+#[tracing::instrument(name = "Create a segment", skip(state, flash, form))]
+pub async fn create_segment(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<NewSegmentForm>,
+) -> Result<Response, StatusCode> {
+    let mut form = form.0;
+    let name = std::mem::take(&mut form.name).trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        let flash = flash.error(format!(
+            "A segment name must have between 1 and {MAX_NAME_LENGTH} characters."
+        ));
+        return Ok((flash, Redirect::to("/admin/segments")).into_response());
+    }
+    let definition = match SegmentDefinition::try_from(form) {
+        Ok(definition) => definition,
+        Err(e) => return Ok((flash.error(e), Redirect::to("/admin/segments")).into_response()),
+    };
+
+    let result = sqlx::query!(
+        r#"INSERT INTO segments (segment_id, name, definition) VALUES ($1, $2, $3)"#,
+        Uuid::new_v4(),
+        name,
+        Json(definition) as _,
+    )
+    .execute(&state.pg_connection_pool)
+    .await;
+    let flash = match result {
+        Ok(_) => flash.info(format!("Created the {name} segment.")),
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            flash.error(format!("A segment named {name} already exists."))
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    Ok((flash, Redirect::to("/admin/segments")).into_response())
+}
@@ -0,0 +1,93 @@
+use askama_axum::Template;
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{
+        active_sessions, revoke_other_sessions, revoke_session, ActiveSession, UserId,
+    },
+    session_state::TypedSession,
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/sessions.html")]
+struct Sessions {
+    msg: String,
+    current_session: Option<Uuid>,
+    sessions: Vec<ActiveSession>,
+}
+
+impl Sessions {
+    fn is_current(&self, session: &ActiveSession) -> bool {
+        self.current_session == Some(session.session_id)
+    }
+}
+
+pub async fn sessions(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    session: TypedSession,
+    user_id: Extension<UserId>,
+) -> Result<Response, StatusCode> {
+    let sessions = active_sessions(&state.pg_connection_pool, **user_id)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    let page = Sessions {
+        msg,
+        current_session: session.get_session_id().map_err(e500)?,
+        sessions,
+    };
+    Ok((flash_messages, page).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct SessionForm {
+    session_id: Uuid,
+}
+
+/// Logs out one of the user's other sessions, e.g. on a device they lost.
+#[tracing::instrument(name = "Log out a session", skip(state, flash, form))]
+pub async fn log_out_session(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    form: Form<SessionForm>,
+) -> Result<Response, StatusCode> {
+    let flash = if revoke_session(&state.pg_connection_pool, **user_id, form.0.session_id)
+        .await
+        .map_err(e500)?
+    {
+        flash.info("The session has been logged out.")
+    } else {
+        flash.error("This session has already ended.")
+    };
+    Ok((flash, Redirect::to("/admin/security/sessions")).into_response())
+}
+
+#[tracing::instrument(name = "Log out other sessions", skip(state, flash, session))]
+pub async fn log_out_other_sessions(
+    state: State<AppState>,
+    flash: Flash,
+    session: TypedSession,
+    user_id: Extension<UserId>,
+) -> Result<Response, StatusCode> {
+    let current_session = session.get_session_id().map_err(e500)?;
+    let n_sessions = revoke_other_sessions(&state.pg_connection_pool, **user_id, current_session)
+        .await
+        .map_err(e500)?;
+    let flash = flash.info(match n_sessions {
+        1 => "1 other session has been logged out.".to_string(),
+        n => format!("{n} other sessions have been logged out."),
+    });
+    Ok((flash, Redirect::to("/admin/security/sessions")).into_response())
+}
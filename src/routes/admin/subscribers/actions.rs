@@ -0,0 +1,321 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    routes::{generate_subscription_token, send_confirmation_email, store_token},
+    utils::e500,
+    AppState,
+};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberAction {
+    Confirm,
+    Unsubscribe,
+    ResendConfirmation,
+    Delete,
+}
+
+impl SubscriberAction {
+    async fn apply(&self, state: &AppState, ids: &[Uuid]) -> anyhow::Result<String> {
+        let pg_pool = &state.pg_connection_pool;
+        let message = match self {
+            Self::Confirm => {
+                let n = confirm_subscribers(pg_pool, ids).await?;
+                format!("Confirmed {n} subscriber(s).")
+            }
+            Self::Unsubscribe => {
+                let n = unsubscribe_subscribers(pg_pool, ids).await?;
+                format!("Unsubscribed {n} subscriber(s).")
+            }
+            Self::ResendConfirmation => {
+                let n = resend_confirmations(state, ids).await?;
+                format!("Sent {n} confirmation email(s).")
+            }
+            Self::Delete => {
+                let n = delete_subscribers(pg_pool, ids).await?;
+                format!("Deleted {n} subscriber(s).")
+            }
+        };
+        Ok(message)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ActionForm {
+    action: SubscriberAction,
+}
+
+#[tracing::instrument(name = "Apply an action to a subscriber", skip(state, flash, form))]
+pub async fn subscriber_action(
+    state: State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+    form: Form<ActionForm>,
+) -> Result<Response, StatusCode> {
+    let action = form.0.action;
+    let message = action.apply(&state, &[subscriber_id]).await.map_err(e500)?;
+
+    let location = match action {
+        SubscriberAction::Delete => "/admin/subscribers".to_string(),
+        _ => format!("/admin/subscriber/{subscriber_id}"),
+    };
+    Ok((flash.info(message), Redirect::to(&location)).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct BulkActionForm {
+    action: SubscriberAction,
+    #[serde(default)]
+    ids: Vec<Uuid>,
+}
+
+#[tracing::instrument(name = "Apply an action to many subscribers", skip_all)]
+pub async fn subscribers_bulk_action(
+    state: State<AppState>,
+    flash: Flash,
+    form: axum_extra::extract::Form<BulkActionForm>,
+) -> Result<Response, StatusCode> {
+    let BulkActionForm { action, ids } = form.0;
+    let flash = if ids.is_empty() {
+        flash.error("Select at least one subscriber.")
+    } else {
+        flash.info(action.apply(&state, &ids).await.map_err(e500)?)
+    };
+    Ok((flash, Redirect::to("/admin/subscribers")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct EditForm {
+    email: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Edit a subscriber", skip(state, flash, form))]
+pub async fn edit_subscriber(
+    state: State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+    form: Form<EditForm>,
+) -> Result<Response, StatusCode> {
+    let location = format!("/admin/subscriber/{subscriber_id}");
+    let subscriber = match (
+        SubscriberEmail::parse_with(form.0.email, state.email_normalisation),
+        SubscriberName::parse(form.0.name),
+    ) {
+        (Ok(email), Ok(name)) => Subscriber { email, name },
+        (Err(e), _) | (_, Err(e)) => {
+            return Ok((flash.error(e), Redirect::to(&location)).into_response());
+        }
+    };
+
+    let flash = match update_subscriber(&state.pg_connection_pool, subscriber_id, &subscriber).await
+    {
+        Ok(true) => flash.info("The subscriber has been updated."),
+        Ok(false) => return Err(StatusCode::NOT_FOUND),
+        Err(e) if is_unique_violation(&e) => {
+            flash.error("Another subscriber already uses this email address.")
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    Ok((flash, Redirect::to(&location)).into_response())
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+/// Updates a subscriber's details. Deliveries still waiting in the queue follow the
+/// subscriber to their new address, while the delivery log keeps the address each
+/// issue was actually sent to.
+#[tracing::instrument(skip(pg_pool, subscriber))]
+async fn update_subscriber(
+    pg_pool: &PgPool,
+    subscriber_id: Uuid,
+    subscriber: &Subscriber,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pg_pool.begin().await?;
+    let Some(old) = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2, name = $3 WHERE id = $1"#,
+        subscriber_id,
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE issue_delivery_queue SET subscriber_email = $2 WHERE subscriber_email = $1"#,
+        old.email,
+        subscriber.email.as_ref(),
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Confirms subscribers still waiting for confirmation, along with all their pending list
+/// memberships. Subscribers who unsubscribed stay unsubscribed.
+#[tracing::instrument(skip(pg_pool))]
+async fn confirm_subscribers(pg_pool: &PgPool, ids: &[Uuid]) -> Result<u64, sqlx::Error> {
+    let mut transaction = pg_pool.begin().await?;
+    let confirmed = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = ANY($1) AND status = 'pending_confirmation'
+        RETURNING id
+        "#,
+        ids,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'confirmed'
+        WHERE subscriber_id = ANY($1) AND status = 'pending_confirmation'
+        "#,
+        &confirmed,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(confirmed.len() as u64)
+}
+
+/// Marks subscribers as unsubscribed from every list and drops what is still queued
+/// for them, along with any outstanding confirmation tokens.
+#[tracing::instrument(skip(pg_pool))]
+async fn unsubscribe_subscribers(pg_pool: &PgPool, ids: &[Uuid]) -> Result<u64, sqlx::Error> {
+    let mut transaction = pg_pool.begin().await?;
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE id = ANY($1) AND status != 'unsubscribed'
+        "#,
+        ids,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = ANY($1)"#,
+        ids,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    delete_pending_work(&mut transaction, ids).await?;
+    transaction.commit().await?;
+    Ok(result.rows_affected())
+}
+
+#[tracing::instrument(skip(pg_pool))]
+async fn delete_subscribers(pg_pool: &PgPool, ids: &[Uuid]) -> Result<u64, sqlx::Error> {
+    let mut transaction = pg_pool.begin().await?;
+    delete_pending_work(&mut transaction, ids).await?;
+    let result = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = ANY($1)"#, ids,)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(result.rows_affected())
+}
+
+async fn delete_pending_work(
+    transaction: &mut Transaction<'_, Postgres>,
+    ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)"#,
+        ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE subscriber_email IN (SELECT email FROM subscriptions WHERE id = ANY($1))
+        "#,
+        ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Sends a fresh confirmation link to each subscriber still pending confirmation. Returns
+/// how many were sent.
+#[tracing::instrument(skip(state))]
+async fn resend_confirmations(state: &AppState, ids: &[Uuid]) -> anyhow::Result<usize> {
+    let pending = sqlx::query!(
+        r#"
+        SELECT id, email, name FROM subscriptions
+        WHERE id = ANY($1) AND status = 'pending_confirmation'
+        "#,
+        ids,
+    )
+    .fetch_all(&state.pg_connection_pool)
+    .await
+    .context("Failed to fetch pending subscribers")?;
+
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let mut to_confirm = Vec::new();
+    for r in pending {
+        let subscriber = match (
+            SubscriberEmail::parse(r.email),
+            SubscriberName::parse(r.name),
+        ) {
+            (Ok(email), Ok(name)) => Subscriber { email, name },
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::warn!(
+                    error.message = %e,
+                    "Skipping a pending subscriber. Their stored contact details are invalid",
+                );
+                continue;
+            }
+        };
+        let subscription_token = generate_subscription_token();
+        store_token(&mut transaction, r.id, None, &subscription_token).await?;
+        to_confirm.push((subscriber, subscription_token));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store subscription tokens.")?;
+
+    // One failure does not hold back the emails to the others
+    let mut n_sent = 0;
+    for (subscriber, subscription_token) in &to_confirm {
+        match send_confirmation_email(state, subscriber, subscription_token).await {
+            Ok(()) => n_sent += 1,
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                "Failed to resend a confirmation email.",
+            ),
+        }
+    }
+    Ok(n_sent)
+}
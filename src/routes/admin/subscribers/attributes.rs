@@ -0,0 +1,122 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{domain::SubscriberAttributes, utils::e500, AppState};
+
+#[derive(Deserialize)]
+pub struct AttributesForm {
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    attributes: String,
+}
+
+#[tracing::instrument(
+    name = "Edit a subscriber's tags and attributes",
+    skip(state, flash, form)
+)]
+pub async fn edit_subscriber_attributes(
+    state: State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+    form: Form<AttributesForm>,
+) -> Result<Response, StatusCode> {
+    let location = format!("/admin/subscriber/{subscriber_id}");
+    let attributes = match SubscriberAttributes::parse(&form.tags, &form.attributes) {
+        Ok(attributes) => attributes,
+        Err(e) => return Ok((flash.error(e), Redirect::to(&location)).into_response()),
+    };
+
+    if !replace_attributes(&state.pg_connection_pool, subscriber_id, &attributes)
+        .await
+        .map_err(e500)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let flash = flash.info("The tags and attributes have been saved.");
+    Ok((flash, Redirect::to(&location)).into_response())
+}
+
+/// Replaces all of the subscriber's tags and attributes with the given ones.
+#[tracing::instrument(skip(pg_pool, attributes))]
+async fn replace_attributes(
+    pg_pool: &PgPool,
+    subscriber_id: Uuid,
+    attributes: &SubscriberAttributes,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pg_pool.begin().await?;
+    let exists = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .is_some();
+    if !exists {
+        return Ok(false);
+    }
+
+    replace_tags(&mut transaction, subscriber_id, attributes).await?;
+    sqlx::query!(
+        r#"DELETE FROM subscriber_attributes WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    let (keys, values): (Vec<_>, Vec<_>) = attributes
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.as_ref().to_string(), value.clone()))
+        .unzip();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT $1, key, value FROM UNNEST($2::text[], $3::text[]) AS a (key, value)
+        "#,
+        subscriber_id,
+        &keys,
+        &values,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+async fn replace_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    attributes: &SubscriberAttributes,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let tags: Vec<_> = attributes
+        .tags
+        .iter()
+        .map(|tag| tag.as_ref().to_string())
+        .collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $1, tag FROM UNNEST($2::text[]) AS tag
+        "#,
+        subscriber_id,
+        &tags,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use csv_core::{ReadRecordResult, Reader};
+
+/// Splits CSV input into records as it arrives, chunk by chunk, without buffering the
+/// whole document. Records spanning chunk boundaries are completed by the next chunk.
+pub struct CsvRecords {
+    reader: Reader,
+    output: Vec<u8>,
+    ends: Vec<usize>,
+    output_len: usize,
+    ends_len: usize,
+}
+
+impl Default for CsvRecords {
+    fn default() -> Self {
+        Self {
+            reader: Reader::new(),
+            output: vec![0; 1024],
+            ends: vec![0; 16],
+            output_len: 0,
+            ends_len: 0,
+        }
+    }
+}
+
+impl CsvRecords {
+    /// Returns the records completed by `chunk`.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Vec<String>> {
+        if chunk.is_empty() {
+            // An empty input tells the reader the document is over.
+            return Vec::new();
+        }
+        self.read(chunk)
+    }
+
+    /// Returns the last record if the document did not end with a newline.
+    pub fn finish(&mut self) -> Vec<Vec<String>> {
+        self.read(&[])
+    }
+
+    fn read(&mut self, mut input: &[u8]) -> Vec<Vec<String>> {
+        let mut records = Vec::new();
+        loop {
+            let (result, n_in, n_out, n_ends) = self.reader.read_record(
+                input,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            input = &input[n_in..];
+            self.output_len += n_out;
+            self.ends_len += n_ends;
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return records,
+                ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => records.push(self.take_record()),
+            }
+        }
+    }
+
+    fn take_record(&mut self) -> Vec<String> {
+        let mut start = 0;
+        let record = self.ends[..self.ends_len]
+            .iter()
+            .map(|&end| {
+                let field = String::from_utf8_lossy(&self.output[start..end]).into_owned();
+                start = end;
+                field
+            })
+            .collect();
+        self.output_len = 0;
+        self.ends_len = 0;
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvRecords;
+
+    fn read_in_chunks(document: &str, chunk_size: usize) -> Vec<Vec<String>> {
+        let mut csv = CsvRecords::default();
+        let mut records = Vec::new();
+        for chunk in document.as_bytes().chunks(chunk_size) {
+            records.extend(csv.feed(chunk));
+        }
+        records.extend(csv.finish());
+        records
+    }
+
+    #[test]
+    fn records_are_split_on_newlines() {
+        let records = read_in_chunks("email,name\na@example.com,Ann\n", 64);
+        assert_eq!(
+            records,
+            vec![vec!["email", "name"], vec!["a@example.com", "Ann"]]
+        );
+    }
+
+    #[test]
+    fn records_can_span_chunk_boundaries() {
+        let document = "email,name\n\"a@example.com\",\"Smith, Ann\"\nb@example.com,Bob";
+        for chunk_size in 1..document.len() {
+            let records = read_in_chunks(document, chunk_size);
+            assert_eq!(records.len(), 3, "chunk size {chunk_size}");
+            assert_eq!(records[1], vec!["a@example.com", "Smith, Ann"]);
+            assert_eq!(records[2], vec!["b@example.com", "Bob"]);
+        }
+    }
+
+    #[test]
+    fn long_fields_grow_the_buffers() {
+        let name = "x".repeat(5000);
+        let document = format!("{name},{}\n", "y,".repeat(40));
+        let records = read_in_chunks(&document, 100);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][0], name);
+        assert_eq!(records[0].len(), 42);
+    }
+}
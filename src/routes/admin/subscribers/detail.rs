@@ -0,0 +1,109 @@
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_flash::IncomingFlashes;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    mailing_lists::{get_memberships, Membership},
+    segments::{get_attributes, get_tags, Attribute},
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/subscriber.html")]
+struct SubscriberDetail {
+    msg: String,
+    subscriber: SubscriberRecord,
+    memberships: Vec<Membership>,
+    tags: Vec<String>,
+    attributes: Vec<Attribute>,
+}
+
+impl SubscriberDetail {
+    /// The tags as they are typed in the edit form.
+    fn tags_text(&self) -> String {
+        self.tags.join(", ")
+    }
+
+    /// The attributes as they are typed in the edit form, one `key=value` per line.
+    fn attributes_text(&self) -> String {
+        self.attributes
+            .iter()
+            .map(|a| format!("{}={}", a.key, a.value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+struct SubscriberRecord {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    digest_frequency: String,
+    paused_until: Option<DateTime<Utc>>,
+    n_queued: i64,
+    n_sent: i64,
+    n_failed: i64,
+}
+
+#[tracing::instrument(name = "Show a subscriber", skip(state, flash_messages))]
+pub async fn subscriber(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let subscriber = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT
+            s.id, s.email, s.name, s.status, s.subscribed_at, s.digest_frequency, s.paused_until,
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue q
+                WHERE q.subscriber_email = s.email
+            ) as "n_queued!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_log l
+                WHERE l.subscriber_email = s.email AND l.outcome = 'sent'
+            ) as "n_sent!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_log l
+                WHERE l.subscriber_email = s.email AND l.outcome = 'failed'
+            ) as "n_failed!"
+        FROM subscriptions s
+        WHERE s.id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let pg_pool = &state.pg_connection_pool;
+    let memberships = get_memberships(pg_pool, subscriber_id)
+        .await
+        .map_err(e500)?;
+    let tags = get_tags(pg_pool, subscriber_id).await.map_err(e500)?;
+    let attributes = get_attributes(pg_pool, subscriber_id).await.map_err(e500)?;
+
+    let msg = read_flash_messages(&flash_messages);
+    Ok((
+        flash_messages,
+        SubscriberDetail {
+            msg,
+            subscriber,
+            memberships,
+            tags,
+            attributes,
+        },
+    )
+        .into_response())
+}
@@ -0,0 +1,146 @@
+use async_stream::try_stream;
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::Serialize;
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use super::filters::SubscriberFilters;
+use crate::AppState;
+
+#[derive(sqlx::FromRow, Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// Streams the subscribers matching `filters`, newest first, one row at a time.
+fn exported_subscribers(
+    pg_pool: PgPool,
+    filters: SubscriberFilters,
+) -> impl Stream<Item = Result<ExportedSubscriber, sqlx::Error>> {
+    try_stream! {
+        let mut builder = QueryBuilder::new(
+            r#"
+            SELECT id, email, name, status, subscribed_at
+            FROM subscriptions
+            WHERE TRUE"#,
+        );
+        filters.push_conditions(&mut builder);
+        builder.push(" ORDER BY subscribed_at DESC, id DESC");
+
+        let mut rows = builder.build_query_as::<ExportedSubscriber>().fetch(&pg_pool);
+        while let Some(row) = rows.try_next().await? {
+            yield row;
+        }
+    }
+}
+
+#[tracing::instrument(name = "Export subscribers as CSV", skip(state))]
+pub async fn export_subscribers_csv(
+    state: State<AppState>,
+    Query(filters): Query<SubscriberFilters>,
+) -> Response {
+    let rows = exported_subscribers(state.pg_connection_pool.clone(), filters);
+    let body = try_stream! {
+        yield csv_line(["id", "email", "name", "status", "subscribed_at"])?;
+        for await row in rows {
+            let row = row?;
+            yield csv_line([
+                &row.id.to_string(),
+                &row.email,
+                &row.name,
+                &row.status,
+                &row.subscribed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ])?;
+        }
+    };
+    let body = body.inspect_err(|e: &anyhow::Error| {
+        tracing::error!(error.cause_chain = ?e, "Failed to export subscribers.")
+    });
+
+    attachment("text/csv; charset=utf-8", "csv", Body::from_stream(body))
+}
+
+#[tracing::instrument(name = "Export subscribers as JSON", skip(state))]
+pub async fn export_subscribers_json(
+    state: State<AppState>,
+    Query(filters): Query<SubscriberFilters>,
+) -> Response {
+    let rows = exported_subscribers(state.pg_connection_pool.clone(), filters);
+    let body = try_stream! {
+        let mut separator = "[\n";
+        for await row in rows {
+            let mut chunk = separator.as_bytes().to_vec();
+            serde_json::to_writer(&mut chunk, &row?)?;
+            separator = ",\n";
+            yield chunk;
+        }
+        yield if separator == "[\n" { b"[]\n".to_vec() } else { b"\n]\n".to_vec() };
+    };
+    let body = body.inspect_err(|e: &anyhow::Error| {
+        tracing::error!(error.cause_chain = ?e, "Failed to export subscribers.")
+    });
+
+    attachment("application/json", "json", Body::from_stream(body))
+}
+
+/// Spreadsheets read cells starting with one of these as formulas.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Quotes cells a spreadsheet would run as a formula with a leading `'`, so that opening
+/// an export cannot run whatever a subscriber typed in as their name.
+pub(super) fn spreadsheet_safe(cell: &str) -> String {
+    if cell.starts_with(FORMULA_PREFIXES) {
+        format!("'{cell}")
+    } else {
+        cell.to_owned()
+    }
+}
+
+fn csv_line<const N: usize>(record: [&str; N]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record.map(spreadsheet_safe))?;
+    writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("Failed to encode a CSV row. {e}"))
+}
+
+fn attachment(content_type: &'static str, extension: &str, body: Body) -> Response {
+    let disposition = format!(
+        "attachment; filename=\"subscribers-{}.{extension}\"",
+        Utc::now().format("%Y%m%d")
+    );
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::spreadsheet_safe;
+
+    #[test]
+    fn cells_read_as_formulas_are_quoted() {
+        for cell in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(spreadsheet_safe(cell), format!("'{cell}"));
+        }
+        assert_eq!(spreadsheet_safe("Ursula"), "Ursula");
+        assert_eq!(spreadsheet_safe("a=b"), "a=b");
+        assert_eq!(spreadsheet_safe(""), "");
+    }
+}
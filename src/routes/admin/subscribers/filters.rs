@@ -0,0 +1,89 @@
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::utils::empty_as_none;
+
+/// Filters shared by the subscriber listing and its exports.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct SubscriberFilters {
+    #[serde(
+        default,
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub status: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub from: Option<NaiveDate>,
+    #[serde(
+        default,
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub to: Option<NaiveDate>,
+    #[serde(
+        default,
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub q: Option<String>,
+}
+
+impl SubscriberFilters {
+    /// Appends the conditions matching these filters. The builder must already contain a
+    /// `WHERE` clause.
+    pub fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(status) = &self.status {
+            query.push(" AND status = ").push_bind(status.clone());
+        }
+        if let Some(from) = self.from {
+            query
+                .push(" AND subscribed_at >= ")
+                .push_bind(from.and_time(Default::default()).and_utc());
+        }
+        if let Some(to) = self.to.and_then(|to| to.checked_add_days(Days::new(1))) {
+            query
+                .push(" AND subscribed_at < ")
+                .push_bind(to.and_time(Default::default()).and_utc());
+        }
+        if let Some(q) = &self.q {
+            let pattern = format!("%{}%", escape_like_pattern(q));
+            query
+                .push(" AND (email ILIKE ")
+                .push_bind(pattern.clone())
+                .push(" OR name ILIKE ")
+                .push_bind(pattern)
+                .push(")");
+        }
+    }
+}
+
+fn escape_like_pattern(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_like_pattern;
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like_pattern(r"50%_off\"), r"50\%\_off\\");
+    }
+
+    #[test]
+    fn plain_text_is_left_untouched() {
+        assert_eq!(escape_like_pattern("ursula"), "ursula");
+    }
+}
@@ -0,0 +1,530 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{multipart::Field, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{csv_records::CsvRecords, export::spreadsheet_safe};
+use crate::{
+    domain::{EmailNormalisation, Subscriber, SubscriberEmail, SubscriberName},
+    mailing_lists::{find_list, get_all_lists, MailingList},
+    routes::{error_chain_fmt, generate_subscription_token, send_confirmation_email, store_token},
+    signup_policy::SignupPolicyError,
+    utils::{e500, read_flash_messages, spawn_and_log_error},
+    AppState,
+};
+
+/// Upper bound on the size of an uploaded CSV file.
+pub const MAX_IMPORT_SIZE: usize = 50 * 1024 * 1024;
+
+#[derive(Template)]
+#[template(path = "admin/subscribers_import.html")]
+struct ImportForm {
+    msg: String,
+    lists: Vec<MailingList>,
+}
+
+pub async fn import_subscribers_form(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+) -> Result<Response, StatusCode> {
+    let lists = get_all_lists(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((flash_messages, ImportForm { msg, lists }).into_response())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ImportedStatus {
+    Confirmed,
+    PendingConfirmation,
+}
+
+impl ImportedStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::PendingConfirmation => "pending_confirmation",
+        }
+    }
+}
+
+impl TryFrom<String> for ImportedStatus {
+    type Error = ImportError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "confirmed" => Ok(Self::Confirmed),
+            "pending_confirmation" => Ok(Self::PendingConfirmation),
+            other => Err(ImportError::ValidationError(format!(
+                "`{other}` is not a valid subscriber status."
+            ))),
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+enum ImportError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Positions of the columns we care about, read from the header row.
+struct Columns {
+    email: usize,
+    name: usize,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> Result<Self, ImportError> {
+        let position = |column: &str| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(column))
+        };
+        match (position("email"), position("name")) {
+            (Some(email), Some(name)) => Ok(Self { email, name }),
+            _ => Err(ImportError::ValidationError(
+                "The CSV header must contain `email` and `name` columns.".into(),
+            )),
+        }
+    }
+
+    fn extract(&self, record: &[String]) -> (String, String) {
+        let field = |i: usize| record.get(i).cloned().unwrap_or_default();
+        (field(self.email), field(self.name))
+    }
+}
+
+#[derive(Default)]
+struct ImportCounts {
+    n_imported: i32,
+    n_duplicates: i32,
+    n_rejected: i32,
+}
+
+#[tracing::instrument(name = "Import subscribers from CSV", skip_all)]
+pub async fn import_subscribers(
+    state: State<AppState>,
+    flash: Flash,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    match read_import(&state, multipart).await {
+        Ok(import_id) => {
+            let flash = flash.info("The import has completed.");
+            let location = format!("/admin/subscribers/import/{import_id}");
+            Ok((flash, Redirect::to(&location)).into_response())
+        }
+        Err(ImportError::ValidationError(msg)) => {
+            let flash = flash.error(msg);
+            Ok((flash, Redirect::to("/admin/subscribers/import")).into_response())
+        }
+        Err(e) => Err(e500(e)),
+    }
+}
+
+async fn read_import(state: &AppState, mut multipart: Multipart) -> Result<Uuid, ImportError> {
+    let mut status = None;
+    let mut list = None;
+    let mut send_confirmation = false;
+    while let Some(field) = multipart.next_field().await.map_err(bad_upload)? {
+        match field.name() {
+            Some("status") => {
+                status = Some(field.text().await.map_err(bad_upload)?.try_into()?);
+            }
+            Some("list") => list = Some(field.text().await.map_err(bad_upload)?),
+            Some("send_confirmation") => send_confirmation = true,
+            Some("file") => {
+                // Options must precede the file so rows can be processed as they arrive.
+                let status = status.ok_or_else(|| {
+                    ImportError::ValidationError("Choose a status for imported subscribers.".into())
+                })?;
+                let list = find_list(&state.pg_connection_pool, list.as_deref())
+                    .await
+                    .context("Failed to look up the mailing list")?
+                    .ok_or_else(|| ImportError::ValidationError("Unknown mailing list.".into()))?;
+                let send_confirmation =
+                    send_confirmation && status == ImportedStatus::PendingConfirmation;
+                return import_csv(state, field, status, &list, send_confirmation).await;
+            }
+            _ => {}
+        }
+    }
+    Err(ImportError::ValidationError(
+        "Select a CSV file to import.".into(),
+    ))
+}
+
+fn bad_upload(e: axum::extract::multipart::MultipartError) -> ImportError {
+    ImportError::ValidationError(format!("The upload could not be read: {e}"))
+}
+
+async fn import_csv(
+    state: &AppState,
+    mut file: Field<'_>,
+    status: ImportedStatus,
+    list: &MailingList,
+    send_confirmation: bool,
+) -> Result<Uuid, ImportError> {
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let import_id = Uuid::new_v4();
+    insert_import(&mut transaction, import_id, status)
+        .await
+        .context("Failed to store the import")?;
+
+    let mut csv = CsvRecords::default();
+    let mut columns = None;
+    let mut row_number = 0;
+    let mut counts = ImportCounts::default();
+    let mut to_confirm = Vec::new();
+    loop {
+        let chunk = file.chunk().await.map_err(bad_upload)?;
+        let records = match &chunk {
+            Some(chunk) => csv.feed(chunk),
+            None => csv.finish(),
+        };
+        for record in records {
+            row_number += 1;
+            let Some(columns) = &columns else {
+                columns = Some(Columns::from_header(&record)?);
+                continue;
+            };
+            let (email, name) = columns.extract(&record);
+            // Rows the signup policy refuses are rejected, as they would be on signup
+            let subscriber =
+                match parse_subscriber(email.clone(), name.clone(), state.email_normalisation) {
+                    Ok(subscriber) => {
+                        match state
+                            .signup_policy
+                            .check(&mut *transaction, &subscriber.email)
+                            .await
+                        {
+                            Ok(()) => Ok(subscriber),
+                            Err(SignupPolicyError::UnexpectedError(e)) => return Err(e.into()),
+                            Err(e) => Err(e.to_string()),
+                        }
+                    }
+                    Err(reason) => Err(reason),
+                };
+            let subscriber = match subscriber {
+                Ok(subscriber) => subscriber,
+                Err(reason) => {
+                    insert_rejection(
+                        &mut transaction,
+                        import_id,
+                        row_number,
+                        &email,
+                        &name,
+                        &reason,
+                    )
+                    .await
+                    .context("Failed to store a rejected row")?;
+                    counts.n_rejected += 1;
+                    continue;
+                }
+            };
+            match insert_imported_subscriber(&mut transaction, &subscriber, list.list_id, status)
+                .await
+                .context("Failed to store an imported subscriber")?
+            {
+                None => counts.n_duplicates += 1,
+                Some(subscriber_id) => {
+                    counts.n_imported += 1;
+                    if send_confirmation {
+                        let subscription_token = generate_subscription_token();
+                        store_token(
+                            &mut transaction,
+                            subscriber_id,
+                            Some(list.list_id),
+                            &subscription_token,
+                        )
+                        .await?;
+                        to_confirm.push((subscriber, subscription_token));
+                    }
+                }
+            }
+        }
+        if chunk.is_none() {
+            break;
+        }
+    }
+    if columns.is_none() {
+        return Err(ImportError::ValidationError(
+            "The CSV file is empty.".into(),
+        ));
+    }
+
+    update_import_counts(&mut transaction, import_id, &counts)
+        .await
+        .context("Failed to store the import counts")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store imported subscribers.")?;
+
+    // A large import would outlive the request if it waited for every email
+    if !to_confirm.is_empty() {
+        let state = state.clone();
+        spawn_and_log_error(async move {
+            send_confirmation_emails(&state, import_id, to_confirm).await
+        });
+    }
+
+    Ok(import_id)
+}
+
+fn parse_subscriber(
+    email: String,
+    name: String,
+    normalisation: EmailNormalisation,
+) -> Result<Subscriber, String> {
+    let email = SubscriberEmail::parse_with(email, normalisation)?;
+    let name = SubscriberName::parse(name)?;
+    Ok(Subscriber { email, name })
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn insert_import(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    status: ImportedStatus,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscriber_imports (import_id, imported_status) VALUES ($1, $2)"#,
+        import_id,
+        status.as_str(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Adds the subscriber to the list, creating them if their address is new. Returns
+/// `None` if they were already on the list.
+#[tracing::instrument(skip(transaction, subscriber))]
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &Subscriber,
+    list_id: Uuid,
+    status: ImportedStatus,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, now(), $4)
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+        status.as_str(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT $1, id, $3 FROM subscriptions WHERE email = $2
+        ON CONFLICT (list_id, subscriber_id) DO NOTHING
+        RETURNING subscriber_id
+        "#,
+        list_id,
+        subscriber.email.as_ref(),
+        status.as_str(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(result.map(|r| r.subscriber_id))
+}
+
+#[tracing::instrument(skip(transaction, email, name))]
+async fn insert_rejection(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    row_number: i32,
+    email: &str,
+    name: &str,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_import_rejections (import_id, row_number, email, name, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        import_id,
+        row_number,
+        email,
+        name,
+        reason,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction, counts))]
+async fn update_import_counts(
+    transaction: &mut Transaction<'_, Postgres>,
+    import_id: Uuid,
+    counts: &ImportCounts,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_imports
+        SET n_imported = $2, n_duplicates = $3, n_rejected = $4
+        WHERE import_id = $1
+        "#,
+        import_id,
+        counts.n_imported,
+        counts.n_duplicates,
+        counts.n_rejected,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Sends the confirmation emails of an import, then records how many went out.
+#[tracing::instrument(skip(state, to_confirm))]
+async fn send_confirmation_emails(
+    state: &AppState,
+    import_id: Uuid,
+    to_confirm: Vec<(Subscriber, String)>,
+) -> anyhow::Result<()> {
+    let mut n_confirmation_emails = 0;
+    for (subscriber, subscription_token) in &to_confirm {
+        match send_confirmation_email(state, subscriber, subscription_token).await {
+            Ok(()) => n_confirmation_emails += 1,
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                "Failed to send a confirmation email to an imported subscriber.",
+            ),
+        }
+    }
+    if n_confirmation_emails > 0 {
+        record_confirmation_emails(&state.pg_connection_pool, import_id, n_confirmation_emails)
+            .await
+            .context("Failed to store the number of confirmation emails")?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(pg_pool))]
+async fn record_confirmation_emails(
+    pg_pool: &PgPool,
+    import_id: Uuid,
+    n_confirmation_emails: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriber_imports SET n_confirmation_emails = $2 WHERE import_id = $1"#,
+        import_id,
+        n_confirmation_emails,
+    )
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscribers_import_report.html")]
+struct ImportReport {
+    msg: String,
+    import: SubscriberImport,
+}
+
+struct SubscriberImport {
+    import_id: Uuid,
+    imported_status: String,
+    n_imported: i32,
+    n_duplicates: i32,
+    n_rejected: i32,
+    n_confirmation_emails: i32,
+    created_at: DateTime<Utc>,
+}
+
+pub async fn import_report(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    Path(import_id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let import = sqlx::query_as!(
+        SubscriberImport,
+        r#"
+        SELECT import_id, imported_status, n_imported, n_duplicates, n_rejected,
+            n_confirmation_emails, created_at
+        FROM subscriber_imports
+        WHERE import_id = $1
+        "#,
+        import_id,
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let msg = read_flash_messages(&flash_messages);
+    Ok((flash_messages, ImportReport { msg, import }).into_response())
+}
+
+pub async fn import_rejections_csv(
+    state: State<AppState>,
+    Path(import_id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let rejections = sqlx::query!(
+        r#"
+        SELECT row_number, email, name, reason
+        FROM subscriber_import_rejections
+        WHERE import_id = $1
+        ORDER BY row_number
+        "#,
+        import_id,
+    )
+    .fetch_all(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["row", "email", "name", "reason"])
+        .map_err(e500)?;
+    for r in rejections {
+        writer
+            .write_record([
+                r.row_number.to_string(),
+                spreadsheet_safe(&r.email),
+                spreadsheet_safe(&r.name),
+                spreadsheet_safe(&r.reason),
+            ])
+            .map_err(e500)?;
+    }
+    let body = writer.into_inner().map_err(e500)?;
+
+    let disposition = format!("attachment; filename=\"rejected-{import_id}.csv\"");
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
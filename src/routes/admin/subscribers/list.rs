@@ -0,0 +1,193 @@
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_flash::IncomingFlashes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use super::filters::SubscriberFilters;
+use crate::{
+    routes::admin::pagination::{page_size, Cursor, KeyType, Keyset, Page, PageRequest, SortOrder},
+    utils::{e400, e500, empty_as_none, read_flash_messages},
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "admin/subscribers.html")]
+struct Subscribers {
+    msg: String,
+    query: SubscribersQuery,
+    counts: Vec<StatusCount>,
+    subscribers: Page<SubscriberMeta>,
+}
+
+impl Subscribers {
+    fn total(&self) -> i64 {
+        self.counts.iter().map(|c| c.count).sum()
+    }
+
+    /// Exports honour the filters currently applied to the list.
+    fn export_url(&self, format: &str) -> String {
+        let filters = serde_urlencoded::to_string(&self.query.filters).unwrap_or_default();
+        format!("/admin/subscribers/export.{format}?{filters}")
+    }
+
+    fn next_url(&self) -> Option<String> {
+        self.subscribers.next_cursor.as_ref().map(|cursor| {
+            self.query.url(SubscribersQuery {
+                after: Some(cursor.clone()),
+                ..self.query.filters()
+            })
+        })
+    }
+
+    fn prev_url(&self) -> Option<String> {
+        self.subscribers.prev_cursor.as_ref().map(|cursor| {
+            self.query.url(SubscribersQuery {
+                before: Some(cursor.clone()),
+                ..self.query.filters()
+            })
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct SubscribersQuery {
+    #[serde(flatten)]
+    filters: SubscriberFilters,
+    #[serde(
+        default,
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<String>,
+}
+
+impl SubscribersQuery {
+    /// The current query without its pagination cursors.
+    fn filters(&self) -> Self {
+        Self {
+            filters: self.filters.clone(),
+            limit: self.limit,
+            after: None,
+            before: None,
+        }
+    }
+
+    fn url(&self, query: Self) -> String {
+        let query = serde_urlencoded::to_string(query).unwrap_or_default();
+        format!("/admin/subscribers?{query}")
+    }
+
+    fn has_status(&self, status: &str) -> bool {
+        self.filters.status.as_deref() == Some(status)
+    }
+}
+
+struct StatusCount {
+    status: String,
+    count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SubscriberMeta {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+const KEYSET: Keyset = Keyset {
+    column: "subscribed_at",
+    key_type: KeyType::Timestamp,
+    id_column: "id",
+    order: SortOrder::Desc,
+};
+
+pub async fn subscribers_list(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    query: Query<SubscribersQuery>,
+) -> Result<Response, StatusCode> {
+    let query = query.0;
+    let page = PageRequest::parse(query.after.as_deref(), query.before.as_deref(), &KEYSET)
+        .map_err(e400)?;
+    let counts = get_status_counts(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+    let subscribers = get_subscribers(&state.pg_connection_pool, &query, &page)
+        .await
+        .map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    Ok((
+        flash_messages,
+        Subscribers {
+            msg,
+            query,
+            counts,
+            subscribers,
+        },
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Get subscriber counts per status", skip(pg_pool))]
+async fn get_status_counts(pg_pool: &PgPool) -> anyhow::Result<Vec<StatusCount>> {
+    let counts = sqlx::query_as!(
+        StatusCount,
+        r#"
+        SELECT status, COUNT(*) as "count!"
+        FROM subscriptions
+        GROUP BY status
+        ORDER BY status
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(counts)
+}
+
+#[tracing::instrument(name = "Get subscribers", skip_all)]
+async fn get_subscribers(
+    pg_pool: &PgPool,
+    query: &SubscribersQuery,
+    page: &PageRequest,
+) -> anyhow::Result<Page<SubscriberMeta>> {
+    let page_size = page_size(query.limit);
+    let mut builder = QueryBuilder::new(
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE TRUE"#,
+    );
+    query.filters.push_conditions(&mut builder);
+    KEYSET.push_condition(&mut builder, page);
+    KEYSET.push_order_and_limit(&mut builder, page, page_size);
+
+    let rows = builder
+        .build_query_as::<SubscriberMeta>()
+        .fetch_all(pg_pool)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(Page::from_rows(rows, page, page_size, |subscriber| {
+        Cursor {
+            key: subscriber
+                .subscribed_at
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            id: subscriber.id,
+        }
+    }))
+}
@@ -0,0 +1,18 @@
+mod actions;
+mod attributes;
+mod csv_records;
+mod detail;
+mod export;
+mod filters;
+mod import;
+mod list;
+
+pub use actions::{edit_subscriber, subscriber_action, subscribers_bulk_action};
+pub use attributes::edit_subscriber_attributes;
+pub use detail::subscriber;
+pub use export::{export_subscribers_csv, export_subscribers_json};
+pub use import::{
+    import_rejections_csv, import_report, import_subscribers, import_subscribers_form,
+    MAX_IMPORT_SIZE,
+};
+pub use list::subscribers_list;
@@ -0,0 +1,297 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{generate_one_time_token, hash_one_time_token, UserId},
+    domain::{Subscriber, SubscriberEmail, SubscriberName, UserRole},
+    utils::{e500, read_flash_messages},
+    AppState,
+};
+
+/// How long an emailed invitation stays valid.
+const INVITATION_VALIDITY_DAYS: i64 = 7;
+
+#[derive(Template)]
+#[template(path = "admin/users.html")]
+struct Users {
+    msg: String,
+    current_user_id: Uuid,
+    users: Vec<UserSummary>,
+    invitations: Vec<Invitation>,
+    roles: [UserRole; 3],
+}
+
+struct UserSummary {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: String,
+    deactivated_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+struct Invitation {
+    email: String,
+    role: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn users(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    user_id: Extension<UserId>,
+) -> Result<Response, StatusCode> {
+    let pg_pool = &state.pg_connection_pool;
+    let users = get_users(pg_pool).await.map_err(e500)?;
+    let invitations = get_pending_invitations(pg_pool).await.map_err(e500)?;
+    let msg = read_flash_messages(&flash_messages);
+    let page = Users {
+        msg,
+        current_user_id: **user_id,
+        users,
+        invitations,
+        roles: UserRole::ALL,
+    };
+    Ok((flash_messages, page).into_response())
+}
+
+#[tracing::instrument(name = "Get users", skip(pg_pool))]
+async fn get_users(pg_pool: &PgPool) -> Result<Vec<UserSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        UserSummary,
+        r#"
+        SELECT user_id, username, email, role, deactivated_at, created_at
+        FROM users
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get pending invitations", skip(pg_pool))]
+async fn get_pending_invitations(pg_pool: &PgPool) -> Result<Vec<Invitation>, sqlx::Error> {
+    sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT email, role, expires_at
+        FROM user_invitations
+        WHERE accepted_at IS NULL AND expires_at > now()
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct InvitationForm {
+    email: String,
+    role: String,
+}
+
+/// Emails a link to create an admin account with the chosen role.
+#[tracing::instrument(name = "Invite a user", skip(state, flash, form))]
+pub async fn invite_user(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    form: Form<InvitationForm>,
+) -> Result<Response, StatusCode> {
+    let (email, role) = match (
+        SubscriberEmail::parse(form.0.email),
+        UserRole::parse(&form.0.role),
+    ) {
+        (Ok(email), Ok(role)) => (email, role),
+        (Err(e), _) | (_, Err(e)) => {
+            return Ok((flash.error(e), Redirect::to("/admin/users")).into_response())
+        }
+    };
+
+    // The invitation is only stored once its email is sent, so a failed send does not
+    // leave a pending invitation nobody received
+    let token = generate_one_time_token();
+    let now = Utc::now();
+    let mut transaction = state.pg_connection_pool.begin().await.map_err(e500)?;
+    sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (token_hash, email, role, invited_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        hash_one_time_token(&token),
+        email.as_ref(),
+        role.as_str(),
+        **user_id,
+        now,
+        now + Duration::days(INVITATION_VALIDITY_DAYS),
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(e500)?;
+    if let Err(e) = send_invitation_email(&state, email.clone(), role, &token).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send an invitation",
+        );
+        let flash = flash.error(format!(
+            "The invitation could not be sent to {email}. Please try again."
+        ));
+        return Ok((flash, Redirect::to("/admin/users")).into_response());
+    }
+    transaction.commit().await.map_err(e500)?;
+
+    let flash = flash.info(format!("An invitation has been sent to {email}."));
+    Ok((flash, Redirect::to("/admin/users")).into_response())
+}
+
+#[tracing::instrument(name = "Send an invitation", skip(state, token))]
+async fn send_invitation_email(
+    state: &AppState,
+    email: SubscriberEmail,
+    role: UserRole,
+    token: &str,
+) -> anyhow::Result<()> {
+    let name = SubscriberName::parse(email.to_string()).map_err(|e| anyhow::anyhow!(e))?;
+    let link = state
+        .application_base_url
+        .join(&format!("login/invitation?token={token}"))?;
+    let content = format!(
+        "You have been invited to help run the newsletter as {}.<br />\
+        <a href=\"{}\">Create your account</a>. This link expires in {INVITATION_VALIDITY_DAYS} days.",
+        role.as_str(),
+        link.as_str()
+    );
+    state
+        .email_client
+        .send_email(
+            &Subscriber { email, name },
+            "You have been invited to the newsletter admin",
+            &content,
+        )
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct RoleForm {
+    role: String,
+}
+
+#[tracing::instrument(name = "Change a user's role", skip(state, flash, form))]
+pub async fn change_user_role(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    Path(target_id): Path<Uuid>,
+    form: Form<RoleForm>,
+) -> Result<Response, StatusCode> {
+    if **user_id == target_id {
+        return Ok(cannot_change_self(flash));
+    }
+    let role = match UserRole::parse(&form.0.role) {
+        Ok(role) => role,
+        Err(e) => return Ok((flash.error(e), Redirect::to("/admin/users")).into_response()),
+    };
+    let username = sqlx::query_scalar!(
+        r#"UPDATE users SET role = $2 WHERE user_id = $1 RETURNING username"#,
+        target_id,
+        role.as_str(),
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .map_err(e500)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let flash = flash.info(format!("{username} is now {}.", role.as_str()));
+    Ok((flash, Redirect::to("/admin/users")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct UserActionForm {
+    action: String,
+}
+
+/// Deactivates, reactivates or deletes a user. Deactivated users are logged out and
+/// cannot log in, but keep their account.
+#[tracing::instrument(name = "Apply an action to a user", skip(state, flash, form))]
+pub async fn user_action(
+    state: State<AppState>,
+    flash: Flash,
+    user_id: Extension<UserId>,
+    Path(target_id): Path<Uuid>,
+    form: Form<UserActionForm>,
+) -> Result<Response, StatusCode> {
+    if **user_id == target_id {
+        return Ok(cannot_change_self(flash));
+    }
+    let pg_pool = &state.pg_connection_pool;
+    let message = match form.0.action.as_str() {
+        "deactivate" => sqlx::query_scalar!(
+            r#"
+            UPDATE users SET deactivated_at = now()
+            WHERE user_id = $1 AND deactivated_at IS NULL
+            RETURNING username
+            "#,
+            target_id,
+        )
+        .fetch_optional(pg_pool)
+        .await
+        .map_err(e500)?
+        .map(|username| format!("{username} has been deactivated.")),
+        "reactivate" => sqlx::query_scalar!(
+            r#"
+            UPDATE users SET deactivated_at = NULL
+            WHERE user_id = $1
+            RETURNING username
+            "#,
+            target_id,
+        )
+        .fetch_optional(pg_pool)
+        .await
+        .map_err(e500)?
+        .map(|username| format!("{username} has been reactivated.")),
+        "delete" => delete_user(pg_pool, target_id)
+            .await
+            .map_err(e500)?
+            .map(|username| format!("{username} has been deleted.")),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let message = message.ok_or(StatusCode::NOT_FOUND)?;
+    Ok((flash.info(message), Redirect::to("/admin/users")).into_response())
+}
+
+/// Deletes the user along with the responses stored for their idempotent requests.
+#[tracing::instrument(name = "Delete a user", skip(pg_pool))]
+async fn delete_user(pg_pool: &PgPool, user_id: Uuid) -> anyhow::Result<Option<String>> {
+    let mut transaction = pg_pool.begin().await?;
+    sqlx::query!(r#"DELETE FROM idempotency WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the user's idempotency keys")?;
+    let username = sqlx::query_scalar!(
+        r#"DELETE FROM users WHERE user_id = $1 RETURNING username"#,
+        user_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to delete the user")?;
+    transaction.commit().await?;
+    Ok(username)
+}
+
+/// Owners cannot demote, deactivate or delete themselves, so there is always an owner.
+fn cannot_change_self(flash: Flash) -> Response {
+    let flash = flash.error("You cannot change your own account here.");
+    (flash, Redirect::to("/admin/users")).into_response()
+}
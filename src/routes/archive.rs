@@ -0,0 +1,98 @@
+use askama_axum::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{utils::e500, AppState};
+
+#[derive(Template)]
+#[template(path = "archive.html")]
+struct Archive {
+    issues: Vec<ArchivedIssue>,
+}
+
+pub struct ArchivedIssue {
+    pub title: String,
+    pub slug: String,
+    pub published_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "archive_issue.html")]
+struct ArchiveIssue {
+    issue: ArchivedIssueContent,
+}
+
+struct ArchivedIssueContent {
+    title: String,
+    content: String,
+    published_at: DateTime<Utc>,
+}
+
+pub async fn archive(state: State<AppState>) -> Result<Response, StatusCode> {
+    let issues = get_archived_issues(&state.pg_connection_pool)
+        .await
+        .map_err(e500)?;
+
+    Ok(Archive { issues }.into_response())
+}
+
+pub async fn archive_issue(
+    state: State<AppState>,
+    path: Path<String>,
+) -> Result<Response, StatusCode> {
+    let mut issue = get_archived_issue(&state.pg_connection_pool, &path.0)
+        .await
+        .map_err(e500)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    // The archive is public, so editors' HTML is cut down to harmless markup before
+    // it is served unescaped
+    issue.content = ammonia::clean(&issue.content);
+
+    Ok(ArchiveIssue { issue }.into_response())
+}
+
+#[tracing::instrument(name = "Get archived issues", skip(pg_pool))]
+pub async fn get_archived_issues(pg_pool: &PgPool) -> anyhow::Result<Vec<ArchivedIssue>> {
+    let issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT title, slug, published_at
+        FROM newsletter_issues
+        WHERE NOT hidden_from_archive
+        ORDER BY published_at DESC
+        "#,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(issues)
+}
+
+#[tracing::instrument(name = "Get archived issue", skip(pg_pool))]
+async fn get_archived_issue(
+    pg_pool: &PgPool,
+    slug: &str,
+) -> anyhow::Result<Option<ArchivedIssueContent>> {
+    let issue = sqlx::query_as!(
+        ArchivedIssueContent,
+        r#"
+        SELECT title, content, published_at
+        FROM newsletter_issues
+        WHERE
+            slug = $1 AND
+            NOT hidden_from_archive
+        "#,
+        slug
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    Ok(issue)
+}
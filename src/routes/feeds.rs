@@ -0,0 +1,221 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{domain::ApplicationBaseUrl, utils::e500, AppState};
+
+const FEED_TITLE: &str = "Newsletter Archive";
+const FEED_DESCRIPTION: &str = "Issues published by our newsletter.";
+const FEED_SIZE: i64 = 20;
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Template)]
+#[template(path = "feeds/rss.xml")]
+struct RssFeed {
+    title: &'static str,
+    description: &'static str,
+    archive_url: String,
+    self_url: String,
+    updated: Option<DateTime<Utc>>,
+    entries: Vec<FeedEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "feeds/atom.xml")]
+struct AtomFeed {
+    title: &'static str,
+    description: &'static str,
+    archive_url: String,
+    self_url: String,
+    updated: Option<DateTime<Utc>>,
+    entries: Vec<FeedEntry>,
+}
+
+impl AtomFeed {
+    /// An empty feed was last updated at the Unix epoch, so that it keeps the same ETag.
+    fn updated_rfc3339(&self) -> String {
+        self.updated
+            .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+struct FeedEntry {
+    newsletter_issue_id: Uuid,
+    title: String,
+    content: String,
+    link: String,
+    published_at: DateTime<Utc>,
+}
+
+impl FeedEntry {
+    fn guid(&self) -> String {
+        format!("urn:uuid:{}", self.newsletter_issue_id)
+    }
+
+    fn published_rfc3339(&self) -> String {
+        self.published_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+pub async fn rss_feed(state: State<AppState>, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let entries = get_feed_entries(&state.pg_connection_pool, &state.application_base_url)
+        .await
+        .map_err(e500)?;
+    let updated = entries.first().map(|e| e.published_at);
+    let feed = RssFeed {
+        title: FEED_TITLE,
+        description: FEED_DESCRIPTION,
+        archive_url: state
+            .application_base_url
+            .join("archive")
+            .map_err(e500)?
+            .into(),
+        self_url: state
+            .application_base_url
+            .join("feed.rss")
+            .map_err(e500)?
+            .into(),
+        updated,
+        entries,
+    };
+    let body = feed.render().map_err(e500)?;
+
+    Ok(conditional_response(
+        &headers,
+        body,
+        "application/rss+xml; charset=utf-8",
+        updated,
+    ))
+}
+
+pub async fn atom_feed(state: State<AppState>, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let entries = get_feed_entries(&state.pg_connection_pool, &state.application_base_url)
+        .await
+        .map_err(e500)?;
+    let updated = entries.first().map(|e| e.published_at);
+    let feed = AtomFeed {
+        title: FEED_TITLE,
+        description: FEED_DESCRIPTION,
+        archive_url: state
+            .application_base_url
+            .join("archive")
+            .map_err(e500)?
+            .into(),
+        self_url: state
+            .application_base_url
+            .join("feed.atom")
+            .map_err(e500)?
+            .into(),
+        updated,
+        entries,
+    };
+    let body = feed.render().map_err(e500)?;
+
+    Ok(conditional_response(
+        &headers,
+        body,
+        "application/atom+xml; charset=utf-8",
+        updated,
+    ))
+}
+
+/// Answers with `304 Not Modified` when the client already holds the current version of the feed.
+/// `If-None-Match` takes precedence over `If-Modified-Since`, as required by RFC 9110.
+fn conditional_response(
+    headers: &HeaderMap,
+    body: String,
+    content_type: &'static str,
+    last_modified: Option<DateTime<Utc>>,
+) -> Response {
+    let etag = format!("\"{:x}\"", Sha256::digest(body.as_bytes()));
+    let last_modified_header = last_modified.map(|t| t.format(HTTP_DATE_FORMAT).to_string());
+
+    let not_modified = match headers.get(header::IF_NONE_MATCH) {
+        Some(if_none_match) => etag_matches(if_none_match, &etag),
+        None => match (headers.get(header::IF_MODIFIED_SINCE), last_modified) {
+            (Some(if_modified_since), Some(last_modified)) => {
+                not_modified_since(if_modified_since, last_modified)
+            }
+            _ => false,
+        },
+    };
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, content_type)], body).into_response()
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, etag);
+    }
+    if let Some(Ok(last_modified)) = last_modified_header.map(|h| HeaderValue::from_str(&h)) {
+        response_headers.insert(header::LAST_MODIFIED, last_modified);
+    }
+    response
+}
+
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    if_none_match
+        .split(',')
+        .map(|candidate| candidate.trim().trim_start_matches("W/"))
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+fn not_modified_since(if_modified_since: &HeaderValue, last_modified: DateTime<Utc>) -> bool {
+    if_modified_since
+        .to_str()
+        .ok()
+        .and_then(|s| DateTime::parse_from_rfc2822(s).ok())
+        .map(|since| last_modified.timestamp() <= since.timestamp())
+        .unwrap_or(false)
+}
+
+#[tracing::instrument(name = "Get feed entries", skip(pg_pool, base_url))]
+async fn get_feed_entries(
+    pg_pool: &PgPool,
+    base_url: &ApplicationBaseUrl,
+) -> anyhow::Result<Vec<FeedEntry>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            newsletter_issue_id,
+            title,
+            content,
+            slug,
+            published_at
+        FROM newsletter_issues
+        WHERE NOT hidden_from_archive
+        ORDER BY published_at DESC
+        LIMIT $1
+        "#,
+        FEED_SIZE
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+
+    rows.into_iter()
+        .map(|r| {
+            let link = base_url.join(&format!("archive/{}", r.slug))?;
+            Ok(FeedEntry {
+                newsletter_issue_id: r.newsletter_issue_id,
+                title: r.title,
+                content: r.content,
+                link: link.into(),
+                published_at: r.published_at,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    authentication::{create_user, hash_one_time_token, NewUser},
+    domain::UserRole,
+    routes::error_chain_fmt,
+    utils::read_flash_messages,
+    AppState,
+};
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("This invitation is invalid, has expired or has already been accepted.")]
+    InvalidInvitation,
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for InvitationError {
+    fn into_response(self) -> Response {
+        match &self {
+            Self::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+            Self::InvalidInvitation => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenParameter {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "login_invitation.html")]
+struct AcceptInvitation {
+    msg: String,
+    token: String,
+    email: String,
+    role: String,
+}
+
+pub async fn invitation_form(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    parameter: Query<TokenParameter>,
+) -> Result<Response, InvitationError> {
+    let invitation = sqlx::query!(
+        r#"
+        SELECT email, role FROM user_invitations
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > now()
+        "#,
+        hash_one_time_token(&parameter.token),
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the invitation")?
+    .ok_or(InvitationError::InvalidInvitation)?;
+
+    let msg = read_flash_messages(&flash_messages);
+    let page = AcceptInvitation {
+        msg,
+        token: parameter.0.token,
+        email: invitation.email,
+        role: invitation.role,
+    };
+    Ok((flash_messages, page).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationFormData {
+    token: String,
+    username: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+/// Creates the invited user's account. The invitation cannot be used again.
+#[tracing::instrument(name = "Accept an invitation", skip_all, fields(username = %form.username))]
+pub async fn accept_invitation(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<AcceptInvitationFormData>,
+) -> Result<Response, InvitationError> {
+    let form = form.0;
+    let form_path = invitation_form_path(&form.token);
+    let username = form.username.trim().to_string();
+    if username.is_empty() {
+        let flash = flash.error("Please choose a username.");
+        return Ok((flash, Redirect::to(&form_path)).into_response());
+    }
+    if form.password.expose_secret() != form.password_check.expose_secret() {
+        let flash =
+            flash.error("You entered two different passwords - the field values must match.");
+        return Ok((flash, Redirect::to(&form_path)).into_response());
+    }
+    if let Err(e) = state.password_policy.check(&form.password, &username) {
+        return Ok((flash.error(e.to_string()), Redirect::to(&form_path)).into_response());
+    }
+
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to begin a transaction")?;
+    let invitation = sqlx::query!(
+        r#"
+        UPDATE user_invitations SET accepted_at = now()
+        WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > now()
+        RETURNING email, role
+        "#,
+        hash_one_time_token(&form.token),
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to use the invitation")?
+    .ok_or(InvitationError::InvalidInvitation)?;
+    let role = UserRole::parse(&invitation.role).map_err(|e| anyhow::anyhow!(e))?;
+
+    let user = NewUser {
+        username: username.clone(),
+        password: form.password,
+        email: Some(invitation.email),
+        role,
+    };
+    if create_user(&mut *transaction, user, &state.password_hashing)
+        .await?
+        .is_none()
+    {
+        // Dropping the transaction leaves the invitation unused
+        let flash = flash.error(format!("The username {username} is already taken."));
+        return Ok((flash, Redirect::to(&form_path)).into_response());
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new user")?;
+
+    let flash = flash.info("Your account has been created. Please log in.");
+    Ok((flash, Redirect::to("/login")).into_response())
+}
+
+/// The invitation form for `token`, with the token encoded so that it is always a valid URL.
+fn invitation_form_path(token: &str) -> String {
+    let query = serde_urlencoded::to_string([("token", token)]).unwrap_or_default();
+    format!("/login/invitation?{query}")
+}
@@ -1,5 +1,13 @@
 mod get;
+mod invitation;
+mod password_reset;
 mod post;
+mod two_factor;
 
 pub use get::login_form;
-pub use post::login;
+pub use invitation::{accept_invitation, invitation_form};
+pub use password_reset::{
+    forgot_password_form, request_password_reset, reset_password_form, reset_password_with_token,
+};
+pub use post::{login, LoginError};
+pub use two_factor::{two_factor_form, verify_two_factor};
@@ -0,0 +1,282 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{generate_one_time_token, hash_one_time_token, reset_password},
+    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    routes::error_chain_fmt,
+    utils::{read_flash_messages, spawn_and_log_error},
+    AppState,
+};
+
+/// How long the emailed reset link stays valid.
+const LINK_VALIDITY_MINUTES: i64 = 60;
+
+#[derive(thiserror::Error)]
+pub enum PasswordResetError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("This link is invalid or has expired.")]
+    InvalidLink,
+}
+
+impl std::fmt::Debug for PasswordResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PasswordResetError {
+    fn into_response(self) -> Response {
+        match &self {
+            Self::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+            Self::InvalidLink => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "login_forgot.html")]
+struct ForgotPassword {
+    msg: String,
+}
+
+pub async fn forgot_password_form(flash_messages: IncomingFlashes) -> Response {
+    let msg = read_flash_messages(&flash_messages);
+    (flash_messages, ForgotPassword { msg }).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordFormData {
+    username: String,
+}
+
+/// Emails a reset link to the user's address, if they have one. The response is the same
+/// whether or not the username exists, so the form cannot be used to probe for accounts:
+/// the email is sent in the background, so neither its latency nor its failure shows.
+#[tracing::instrument(name = "Request a password reset", skip_all)]
+pub async fn request_password_reset(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<ForgotPasswordFormData>,
+) -> Result<Response, PasswordResetError> {
+    let user = sqlx::query!(
+        r#"SELECT user_id, username, email FROM users WHERE username = $1"#,
+        form.0.username,
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the user")?;
+
+    if let Some(r) = user {
+        match (
+            r.email.map(SubscriberEmail::parse).transpose(),
+            SubscriberName::parse(r.username),
+        ) {
+            (Ok(Some(email)), Ok(name)) => {
+                let user = Subscriber { email, name };
+                let state = state.0.clone();
+                spawn_and_log_error(
+                    async move { send_reset_email(&state, r.user_id, &user).await },
+                );
+            }
+            (Ok(None), _) => {
+                tracing::info!("Cannot send a password reset link. The user has no email address")
+            }
+            (Err(e), _) | (_, Err(e)) => tracing::warn!(
+                error.message = %e,
+                "Cannot send a password reset link. The stored email or username is invalid",
+            ),
+        }
+    }
+
+    let flash = flash.info(
+        "If this account has an email address, we have sent it a link to reset your password.",
+    );
+    Ok((flash, Redirect::to("/login")).into_response())
+}
+
+#[tracing::instrument(name = "Send a password reset link", skip(state, user))]
+async fn send_reset_email(
+    state: &AppState,
+    user_id: Uuid,
+    user: &Subscriber,
+) -> anyhow::Result<()> {
+    let token = generate_one_time_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        hash_one_time_token(&token),
+        user_id,
+        Utc::now() + Duration::minutes(LINK_VALIDITY_MINUTES),
+    )
+    .execute(&state.pg_connection_pool)
+    .await
+    .context("Failed to store the password reset token")?;
+
+    let link = state
+        .application_base_url
+        .join(&format!("login/reset?token={token}"))?;
+    let content = format!(
+        "<a href=\"{}\">Reset your password</a>. This link expires in {LINK_VALIDITY_MINUTES} minutes and can only be used once.<br />\
+        If you did not ask for it you can ignore this email.",
+        link.as_str()
+    );
+    state
+        .email_client
+        .send_email(user, "Reset your password", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct TokenParameter {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "login_reset.html")]
+struct ResetPassword {
+    msg: String,
+    token: String,
+}
+
+pub async fn reset_password_form(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    parameter: Query<TokenParameter>,
+) -> Result<Response, PasswordResetError> {
+    let token_hash = hash_one_time_token(&parameter.token);
+    let valid = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        ) as "valid!"
+        "#,
+        token_hash,
+    )
+    .fetch_one(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the password reset token")?;
+    if !valid {
+        return Err(PasswordResetError::InvalidLink);
+    }
+
+    let msg = read_flash_messages(&flash_messages);
+    let page = ResetPassword {
+        msg,
+        token: parameter.0.token,
+    };
+    Ok((flash_messages, page).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+/// Sets the new password and logs the user out everywhere. The link cannot be used again.
+#[tracing::instrument(name = "Reset a password", skip_all, fields(user_id=tracing::field::Empty))]
+pub async fn reset_password_with_token(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<ResetPasswordFormData>,
+) -> Result<Response, PasswordResetError> {
+    let form = form.0;
+    let username = reset_token_username(&state.pg_connection_pool, &form.token)
+        .await?
+        .ok_or(PasswordResetError::InvalidLink)?;
+    let form_path = reset_form_path(&form.token);
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        let flash =
+            flash.error("You entered two different new passwords - the field values must match.");
+        return Ok((flash, Redirect::to(&form_path)).into_response());
+    }
+    if let Err(e) = state.password_policy.check(&form.new_password, &username) {
+        return Ok((flash.error(e.to_string()), Redirect::to(&form_path)).into_response());
+    }
+
+    // The token is only used up if the new password is stored
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to begin a transaction")?;
+    let user_id = use_reset_token(&mut transaction, &form.token)
+        .await?
+        .ok_or(PasswordResetError::InvalidLink)?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    reset_password(
+        &mut transaction,
+        user_id,
+        form.new_password,
+        &state.password_hashing,
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new password")?;
+
+    let flash = flash.info("Your password has been reset. Please log in.");
+    Ok((flash, Redirect::to("/login")).into_response())
+}
+
+/// The reset form for `token`, with the token encoded so that it is always a valid URL.
+fn reset_form_path(token: &str) -> String {
+    let query = serde_urlencoded::to_string([("token", token)]).unwrap_or_default();
+    format!("/login/reset?{query}")
+}
+
+/// The username of the token's user, if the token is valid.
+#[tracing::instrument(name = "Look up a password reset token", skip_all)]
+async fn reset_token_username(pg_pool: &PgPool, token: &str) -> anyhow::Result<Option<String>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT u.username FROM password_reset_tokens t
+        JOIN users u ON u.user_id = t.user_id
+        WHERE t.token_hash = $1 AND t.used_at IS NULL AND t.expires_at > now()
+        "#,
+        hash_one_time_token(token),
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to look up the password reset token")
+}
+
+/// Marks the token as used. Returns its user if it was valid.
+#[tracing::instrument(name = "Use a password reset token", skip_all)]
+async fn use_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> anyhow::Result<Option<Uuid>> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = now()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_one_time_token(token),
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to use the password reset token")
+}
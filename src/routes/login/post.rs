@@ -1,6 +1,6 @@
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Redirect, Response},
     Form,
 };
@@ -9,7 +9,12 @@ use secrecy::Secret;
 use serde::Deserialize;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        clear_login_failures, login_failure_keys, login_is_blocked, record_login_failure,
+        start_session, two_factor_enabled, user_agent, validate_credentials, AuthError,
+        Credentials,
+    },
+    rate_limit::ClientIp,
     routes::error_chain_fmt,
     session_state::TypedSession,
     AppState,
@@ -22,11 +27,13 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(state, flash, session, form),
+    skip(state, headers, flash, session, form),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     state: State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
     flash: Flash,
     session: TypedSession,
     form: Form<FormData>,
@@ -35,13 +42,46 @@ pub async fn login(
         username: form.0.username,
         password: form.0.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
-    match validate_credentials(&state.pg_connection_pool, credentials).await {
+    // Blocked attempts get the same answer as a wrong password, without checking it
+    let keys = login_failure_keys(&credentials.username, ip);
+    match login_is_blocked(&state.pg_connection_pool, &keys).await {
+        Ok(false) => {}
+        Ok(true) => {
+            let e = LoginError::AuthError(anyhow::anyhow!("Too many failed logins."));
+            return Ok(login_redirect(e, flash));
+        }
+        Err(e) => return Ok(login_redirect(LoginError::UnexpectedError(e), flash)),
+    }
+
+    match validate_credentials(
+        &state.pg_connection_pool,
+        credentials,
+        &state.password_hashing,
+    )
+    .await
+    {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
-            session.renew();
-            if let Err(e) = session.insert_user_id(user_id) {
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            match two_factor_enabled(&state.pg_connection_pool, user_id).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    session.renew();
+                    if let Err(e) = session.insert_pending_user_id(user_id) {
+                        return Ok(login_redirect(LoginError::UnexpectedError(e), flash));
+                    }
+                    return Ok((flash, Redirect::to("/login/two-factor")).into_response());
+                }
+                Err(e) => return Ok(login_redirect(LoginError::UnexpectedError(e), flash)),
+            }
+            if let Err(e) = clear_login_failures(&state.pg_connection_pool, &keys[0]).await {
+                return Ok(login_redirect(LoginError::UnexpectedError(e), flash));
+            }
+            let user_agent = user_agent(&headers);
+            if let Err(e) =
+                start_session(&state.pg_connection_pool, &session, user_id, ip, user_agent).await
+            {
                 let e = LoginError::UnexpectedError(e);
                 return Ok(login_redirect(e, flash));
             }
@@ -50,7 +90,15 @@ pub async fn login(
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    let pg_pool = &state.pg_connection_pool;
+                    if let Err(e) =
+                        record_login_failure(pg_pool, &keys, &state.login_throttling).await
+                    {
+                        return Ok(login_redirect(LoginError::UnexpectedError(e), flash));
+                    }
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
 
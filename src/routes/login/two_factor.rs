@@ -0,0 +1,88 @@
+use askama_axum::Template;
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
+
+use super::LoginError;
+use crate::{
+    authentication::{
+        clear_login_failures, login_failure_keys, login_is_blocked, record_login_failure,
+        start_session, user_agent, verify_second_factor,
+    },
+    rate_limit::ClientIp,
+    routes::admin::get_username,
+    session_state::TypedSession,
+    utils::read_flash_messages,
+    AppState,
+};
+
+#[derive(Template)]
+#[template(path = "login_two_factor.html")]
+struct TwoFactor {
+    msg: String,
+}
+
+pub async fn two_factor_form(flash_messages: IncomingFlashes, session: TypedSession) -> Response {
+    if !matches!(session.get_pending_user_id(), Ok(Some(_))) {
+        return Redirect::to("/login").into_response();
+    }
+    let msg = read_flash_messages(&flash_messages);
+    (flash_messages, TwoFactor { msg }).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+/// The second login step of users with two-factor authentication: a code from their
+/// authenticator or one of their recovery codes. Failures are throttled like passwords.
+#[tracing::instrument(
+    skip(state, headers, flash, session, form),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    state: State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    flash: Flash,
+    session: TypedSession,
+    form: Form<FormData>,
+) -> Result<Response, LoginError> {
+    let Some(user_id) = session.get_pending_user_id()? else {
+        return Ok((flash.error("Session expired!"), Redirect::to("/login")).into_response());
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    let pg_pool = &state.pg_connection_pool;
+
+    let username = get_username(pg_pool, user_id).await?;
+    let keys = login_failure_keys(&username, ip);
+    if login_is_blocked(pg_pool, &keys).await? {
+        let e = LoginError::AuthError(anyhow::anyhow!("Too many failed logins."));
+        return Ok(two_factor_redirect(e, flash));
+    }
+
+    if !verify_second_factor(pg_pool, user_id, &form.0.code).await? {
+        record_login_failure(pg_pool, &keys, &state.login_throttling).await?;
+        let e = LoginError::AuthError(anyhow::anyhow!("Invalid second factor."));
+        return Ok(two_factor_redirect(e, flash));
+    }
+
+    clear_login_failures(pg_pool, &keys[0]).await?;
+    session.remove_pending_user_id()?;
+    start_session(pg_pool, &session, user_id, ip, user_agent(&headers)).await?;
+    Ok((flash, Redirect::to("/admin/dashboard")).into_response())
+}
+
+fn two_factor_redirect(e: LoginError, flash: Flash) -> Response {
+    (
+        flash.error(e.to_string()),
+        Redirect::to("/login/two-factor"),
+    )
+        .into_response()
+}
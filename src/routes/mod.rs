@@ -1,10 +1,14 @@
 mod admin;
+mod archive;
+mod feeds;
 mod health_check;
 mod home;
 mod login;
 mod subscriptions;
 
 pub use admin::*;
+pub use archive::*;
+pub use feeds::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
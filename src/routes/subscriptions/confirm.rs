@@ -45,34 +45,73 @@ pub async fn confirm(
     state: State<AppState>,
     parameters: Query<Parameters>,
 ) -> Result<Response, ConfirmationError> {
-    let id =
+    let token =
         get_subscriber_id_from_token(&state.pg_connection_pool, &parameters.subscription_token)
             .await?;
 
-    match id {
+    match token {
         None => Err(ConfirmationError::UnknownToken),
-        Some(subscriber_id) => {
-            confirm_subscriber(&state.pg_connection_pool, subscriber_id).await?;
+        Some(token) => {
+            confirm_subscriber(
+                &state.pg_connection_pool,
+                token.subscriber_id,
+                token.list_id,
+            )
+            .await?;
             Ok((StatusCode::OK, "Subscribed Successfully!!").into_response())
         }
     }
 }
 
+/// Confirms the subscriber's address and their pending membership of `list_id`, or of
+/// every list they are pending on if the token was not issued for a particular list.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(pg_pool, subscriber_id))]
-pub async fn confirm_subscriber(pg_pool: &PgPool, subscriber_id: Uuid) -> anyhow::Result<()> {
+pub async fn confirm_subscriber(
+    pg_pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+) -> anyhow::Result<()> {
+    let mut transaction = pg_pool.begin().await.map_err(|e| {
+        anyhow::anyhow!("Failed to acquire a Postgres connection from the pool. {e}")
+    })?;
     sqlx::query!(
         r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
         subscriber_id,
     )
-    .execute(pg_pool)
+    .execute(&mut *transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         anyhow::anyhow!("Failed to update confirmation status. {e}")
     })?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'confirmed'
+        WHERE subscriber_id = $1
+            AND ($2::uuid IS NULL OR list_id = $2)
+            AND status = 'pending_confirmation'
+        "#,
+        subscriber_id,
+        list_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        anyhow::anyhow!("Failed to update list membership status. {e}")
+    })?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to commit the confirmation. {e}"))?;
     Ok(())
 }
 
+pub struct TokenOwner {
+    pub subscriber_id: Uuid,
+    pub list_id: Option<Uuid>,
+}
+
 #[tracing::instrument(
     name = "Get subscriber_id from token",
     skip(pg_pool, subscription_token)
@@ -80,9 +119,10 @@ pub async fn confirm_subscriber(pg_pool: &PgPool, subscriber_id: Uuid) -> anyhow
 pub async fn get_subscriber_id_from_token(
     pg_pool: &PgPool,
     subscription_token: &str,
-) -> anyhow::Result<Option<Uuid>> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+) -> anyhow::Result<Option<TokenOwner>> {
+    let result = sqlx::query_as!(
+        TokenOwner,
+        r#"SELECT subscriber_id, list_id FROM subscription_tokens WHERE subscription_token = $1"#,
         subscription_token
     )
     .fetch_optional(pg_pool)
@@ -91,5 +131,5 @@ pub async fn get_subscriber_id_from_token(
         tracing::error!("Failed to execute query: {:?}", e);
         anyhow::anyhow!("Failed to get data from postgres. {e}")
     })?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(result)
 }
@@ -0,0 +1,425 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::error_chain_fmt;
+use crate::{
+    domain::{Subscriber, SubscriberEmail, SubscriberName, SubscriberToken, TokenPurpose},
+    mailing_lists::{get_memberships, Membership},
+    segments::{get_attributes, get_tags, Attribute},
+    utils::{read_flash_messages, spawn_and_log_error},
+    AppState,
+};
+
+/// How long the emailed data access link stays valid.
+const LINK_VALIDITY_HOURS: i64 = 24;
+
+#[derive(thiserror::Error)]
+pub enum DataRequestError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("This link is invalid or has expired.")]
+    InvalidLink,
+}
+
+impl std::fmt::Debug for DataRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for DataRequestError {
+    fn into_response(self) -> Response {
+        match &self {
+            Self::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+            Self::InvalidLink => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "data_request.html")]
+struct DataRequestForm {
+    msg: String,
+}
+
+pub async fn data_request_form(flash_messages: IncomingFlashes) -> Response {
+    let msg = read_flash_messages(&flash_messages);
+    (flash_messages, DataRequestForm { msg }).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct DataRequestFormData {
+    email: String,
+}
+
+/// Emails a data access link to the address if it belongs to a subscriber. The response
+/// is the same either way, so the form cannot be used to probe who is subscribed: the
+/// email is sent in the background, so neither its latency nor its failure shows.
+#[tracing::instrument(name = "Request a subscriber data access link", skip_all)]
+pub async fn request_data_access(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<DataRequestFormData>,
+) -> Result<Response, DataRequestError> {
+    let email = match SubscriberEmail::parse_with(form.0.email, state.email_normalisation) {
+        Ok(email) => email,
+        Err(e) => return Ok((flash.error(e), Redirect::to("/subscriptions/data")).into_response()),
+    };
+
+    let subscriber = sqlx::query!(
+        r#"SELECT id, email, name FROM subscriptions WHERE email = $1"#,
+        email.as_ref(),
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the subscriber")?;
+
+    if let Some(r) = subscriber {
+        // The link goes to the address we hold, not to the one typed into the form
+        match (
+            SubscriberEmail::parse(r.email),
+            SubscriberName::parse(r.name),
+        ) {
+            (Ok(email), Ok(name)) => {
+                let subscriber = Subscriber { email, name };
+                let state = state.0.clone();
+                spawn_and_log_error(async move {
+                    send_data_access_email(&state, r.id, &subscriber).await
+                });
+            }
+            (Err(e), _) | (_, Err(e)) => tracing::warn!(
+                error.message = %e,
+                "Cannot send a data access link. The stored email or name is invalid",
+            ),
+        }
+    }
+
+    let flash = flash
+        .info("If we hold data for this address, we have sent it a link to download or erase it.");
+    Ok((flash, Redirect::to("/subscriptions/data")).into_response())
+}
+
+#[tracing::instrument(name = "Send a data access link", skip(state, subscriber))]
+async fn send_data_access_email(
+    state: &AppState,
+    subscriber_id: Uuid,
+    subscriber: &Subscriber,
+) -> anyhow::Result<()> {
+    let token = SubscriberToken::issue(
+        subscriber_id,
+        TokenPurpose::DataAccess,
+        Duration::hours(LINK_VALIDITY_HOURS),
+        &state.hmac_secret,
+    );
+    let link = state
+        .application_base_url
+        .join(&format!("subscriptions/data/manage?token={token}"))?;
+    let content = format!(
+        "We received a request for the data we hold about you.<br />\
+        <a href=\"{}\">Download or erase your data</a>. This link expires in {LINK_VALIDITY_HOURS} hours.<br />\
+        If you did not make this request you can ignore this email.",
+        link.as_str()
+    );
+    state
+        .email_client
+        .send_email(subscriber, "Your data", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct TokenParameters {
+    token: String,
+}
+
+impl TokenParameters {
+    fn subscriber_id(&self, state: &AppState) -> Result<Uuid, DataRequestError> {
+        SubscriberToken::verify(&self.token, TokenPurpose::DataAccess, &state.hmac_secret)
+            .map(|token| token.subscriber_id)
+            .map_err(|e| {
+                tracing::warn!(error.message = %e, "Rejected a data access link");
+                DataRequestError::InvalidLink
+            })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "data_manage.html")]
+struct DataManage {
+    token: String,
+    email: String,
+}
+
+pub async fn manage_data(
+    state: State<AppState>,
+    parameters: Query<TokenParameters>,
+) -> Result<Response, DataRequestError> {
+    let subscriber_id = parameters.subscriber_id(&state)?;
+    let subscription = get_subscription(&state.pg_connection_pool, subscriber_id)
+        .await?
+        .ok_or(DataRequestError::InvalidLink)?;
+
+    Ok(DataManage {
+        token: parameters.0.token,
+        email: subscription.email,
+    }
+    .into_response())
+}
+
+#[derive(Serialize)]
+struct SubscriberData {
+    exported_at: DateTime<Utc>,
+    subscription: Subscription,
+    lists: Vec<Membership>,
+    tags: Vec<String>,
+    attributes: Vec<Attribute>,
+    subscription_tokens: Vec<String>,
+    queued_deliveries: Vec<QueuedDelivery>,
+    delivery_history: Vec<DeliveryRecord>,
+}
+
+#[derive(Serialize)]
+struct Subscription {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    digest_frequency: String,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct QueuedDelivery {
+    newsletter_issue_id: Uuid,
+    title: String,
+    n_retries: i16,
+    execute_after: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct DeliveryRecord {
+    newsletter_issue_id: Uuid,
+    title: String,
+    outcome: String,
+    completed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Export a subscriber's data", skip_all)]
+pub async fn export_data(
+    state: State<AppState>,
+    parameters: Query<TokenParameters>,
+) -> Result<Response, DataRequestError> {
+    let subscriber_id = parameters.subscriber_id(&state)?;
+    let pg_pool = &state.pg_connection_pool;
+    let subscription = get_subscription(pg_pool, subscriber_id)
+        .await?
+        .ok_or(DataRequestError::InvalidLink)?;
+
+    let lists = get_memberships(pg_pool, subscriber_id)
+        .await
+        .context("Failed to fetch list memberships")?;
+    let tags = get_tags(pg_pool, subscriber_id)
+        .await
+        .context("Failed to fetch tags")?;
+    let attributes = get_attributes(pg_pool, subscriber_id)
+        .await
+        .context("Failed to fetch attributes")?;
+    let subscription_tokens = sqlx::query_scalar!(
+        r#"SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .context("Failed to fetch subscription tokens")?;
+    let queued_deliveries = sqlx::query_as!(
+        QueuedDelivery,
+        r#"
+        SELECT q.newsletter_issue_id, i.title, q.n_retries, q.execute_after
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE q.subscriber_email = $1
+        ORDER BY q.execute_after
+        "#,
+        subscription.email,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .context("Failed to fetch queued deliveries")?;
+    let delivery_history = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT l.newsletter_issue_id, i.title, l.outcome, l.completed_at
+        FROM issue_delivery_log l
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        WHERE l.subscriber_email = $1
+        ORDER BY l.completed_at
+        "#,
+        subscription.email,
+    )
+    .fetch_all(pg_pool)
+    .await
+    .context("Failed to fetch the delivery history")?;
+
+    record_request(pg_pool, subscriber_id, "export").await?;
+
+    let data = SubscriberData {
+        exported_at: Utc::now(),
+        subscription,
+        lists,
+        tags,
+        attributes,
+        subscription_tokens,
+        queued_deliveries,
+        delivery_history,
+    };
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"my-data.json\"",
+        )],
+        Json(data),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct EraseFormData {
+    token: String,
+}
+
+/// Deletes everything held about the subscriber. Their delivery history is kept for the
+/// per-issue statistics, and rows of theirs rejected by CSV imports for the import
+/// reports, with the address replaced by an anonymous placeholder.
+#[tracing::instrument(name = "Erase a subscriber's data", skip_all)]
+pub async fn erase_data(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<EraseFormData>,
+) -> Result<Response, DataRequestError> {
+    let subscriber_id = TokenParameters {
+        token: form.0.token,
+    }
+    .subscriber_id(&state)?;
+
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let erased = erase_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to erase the subscriber")?;
+    if !erased {
+        return Err(DataRequestError::InvalidLink);
+    }
+    record_request(&mut *transaction, subscriber_id, "erasure").await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber.")?;
+
+    let flash = flash.info("Your data has been erased.");
+    Ok((flash, Redirect::to("/subscriptions/data")).into_response())
+}
+
+async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let Some(r) = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        r.email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE issue_delivery_log SET subscriber_email = $2 WHERE subscriber_email = $1"#,
+        r.email,
+        format!("erased-{}", Uuid::new_v4()),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_import_rejections SET email = $2, name = ''
+        WHERE lower(btrim(email)) = lower($1)
+        "#,
+        r.email,
+        format!("erased-{}", Uuid::new_v4()),
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(true)
+}
+
+async fn get_subscription(
+    pg_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> anyhow::Result<Option<Subscription>> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT id, email, name, status, subscribed_at, digest_frequency, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to fetch the subscription")
+}
+
+/// Keeps an audit trail of data requests. Only the subscriber id is recorded, so the
+/// trail itself holds no personal data once the subscriber is erased.
+async fn record_request(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    kind: &str,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_data_requests (request_id, subscriber_id, kind)
+        VALUES ($1, $2, $3)
+        "#,
+        Uuid::new_v4(),
+        subscriber_id,
+        kind,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to record the data request")?;
+    Ok(())
+}
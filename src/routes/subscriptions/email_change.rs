@@ -0,0 +1,237 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Form;
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{
+    error_chain_fmt, generate_subscription_token, preferences_path, verify_token, PreferencesError,
+};
+use crate::{
+    domain::{Subscriber, SubscriberEmail, SubscriberName},
+    signup_policy::SignupPolicyError,
+    AppState,
+};
+
+/// How long the link sent to the new address stays valid.
+const LINK_VALIDITY_HOURS: i32 = 24;
+
+#[derive(Deserialize)]
+pub struct EmailChangeFormData {
+    token: String,
+    email: String,
+}
+
+/// Sends a confirmation link to the new address, if the signup policy allows it. The
+/// subscriber keeps their current address until it is followed. Addresses already used by another subscriber get the
+/// same response but no email, so the form cannot be used to probe who is subscribed.
+#[tracing::instrument(name = "Request an email address change", skip_all)]
+pub async fn request_email_change(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<EmailChangeFormData>,
+) -> Result<Response, PreferencesError> {
+    let subscriber_id = verify_token(&state, &form.0.token)?;
+    let redirect = Redirect::to(&preferences_path(&form.0.token));
+    let new_email = match SubscriberEmail::parse_with(form.0.email, state.email_normalisation) {
+        Ok(email) => email,
+        Err(e) => return Ok((flash.error(e), redirect).into_response()),
+    };
+    match state
+        .signup_policy
+        .check(&state.pg_connection_pool, &new_email)
+        .await
+    {
+        Ok(()) => {}
+        Err(SignupPolicyError::UnexpectedError(e)) => return Err(e.into()),
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    }
+
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            name,
+            EXISTS (
+                SELECT 1 FROM subscriptions WHERE email = $2 AND id <> $1
+            ) as "taken!"
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        new_email.as_ref(),
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the subscriber")?
+    .ok_or(PreferencesError::InvalidLink)?;
+
+    if !r.taken {
+        let name = SubscriberName::parse(r.name)
+            .map_err(anyhow::Error::msg)
+            .context("The stored subscriber name is invalid")?;
+        let change_token = generate_subscription_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO email_change_requests (change_token, subscriber_id, new_email)
+            VALUES ($1, $2, $3)
+            "#,
+            change_token,
+            subscriber_id,
+            new_email.as_ref(),
+        )
+        .execute(&state.pg_connection_pool)
+        .await
+        .context("Failed to store the email change request")?;
+        let subscriber = Subscriber {
+            email: new_email,
+            name,
+        };
+        send_email_change_confirmation(&state, &subscriber, &change_token).await?;
+    }
+
+    let flash = flash.info("We have sent a confirmation link to your new address.");
+    Ok((flash, redirect).into_response())
+}
+
+#[tracing::instrument(name = "Send an email change confirmation", skip_all)]
+async fn send_email_change_confirmation(
+    state: &AppState,
+    subscriber: &Subscriber,
+    change_token: &str,
+) -> anyhow::Result<()> {
+    let link = state
+        .application_base_url
+        .join(&format!("subscriptions/email/confirm?token={change_token}"))?;
+    let content = format!(
+        "Click <a href=\"{}\">here</a> to start receiving our newsletter at this address. \
+        This link expires in {LINK_VALIDITY_HOURS} hours.<br />\
+        If you did not ask for this change you can ignore this email.",
+        link.as_str()
+    );
+    state
+        .email_client
+        .send_email(subscriber, "Confirm your new address", &content)
+        .await
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailChangeError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("This link is invalid or has expired.")]
+    UnknownToken,
+    #[error("This address is already subscribed.")]
+    AddressTaken,
+}
+
+impl std::fmt::Debug for EmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for EmailChangeError {
+    fn into_response(self) -> Response {
+        match &self {
+            Self::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+            Self::UnknownToken => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+            Self::AddressTaken => (StatusCode::CONFLICT, self.to_string()).into_response(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EmailChangeParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Confirm an email address change", skip_all)]
+pub async fn confirm_email_change(
+    state: State<AppState>,
+    parameters: Query<EmailChangeParameters>,
+) -> Result<Response, EmailChangeError> {
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let request = sqlx::query!(
+        r#"
+        SELECT subscriber_id, new_email
+        FROM email_change_requests
+        WHERE change_token = $1
+            AND requested_at > now() - make_interval(hours => $2)
+        "#,
+        parameters.token,
+        LINK_VALIDITY_HOURS,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the email change request")?
+    .ok_or(EmailChangeError::UnknownToken)?;
+
+    match change_email(&mut transaction, request.subscriber_id, &request.new_email).await {
+        Ok(()) => {}
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            return Err(EmailChangeError::AddressTaken)
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to change the email")
+                .into())
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change an email address.")?;
+
+    Ok((StatusCode::OK, "Your email address has been changed.").into_response())
+}
+
+/// Moves the subscriber to their new address. As when an admin edits a subscriber, queued
+/// deliveries follow them while the delivery log keeps the address each issue went to.
+async fn change_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_email: &str,
+) -> Result<(), sqlx::Error> {
+    let old = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2 WHERE id = $1"#,
+        subscriber_id,
+        new_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE issue_delivery_queue SET subscriber_email = $2 WHERE subscriber_email = $1"#,
+        old.email,
+        new_email,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM email_change_requests WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
@@ -1,16 +1,47 @@
 use askama_axum::Template;
-use axum::response::{IntoResponse, Response};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use axum_flash::IncomingFlashes;
+use serde::Deserialize;
 
-use crate::utils::read_flash_messages;
+use crate::{
+    mailing_lists::{find_list, MailingList},
+    utils::{e500, read_flash_messages},
+    AppState,
+};
 
 #[derive(Template)]
 #[template(path = "subscribe.html")]
 struct Subscribe {
     msg: String,
+    list: MailingList,
+    form_token: String,
+    captcha: bool,
 }
 
-pub async fn subscribe_form(flash_messages: IncomingFlashes) -> Response {
+#[derive(Deserialize)]
+pub struct ListParameter {
+    list: Option<String>,
+}
+
+pub async fn subscribe_form(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    parameter: Query<ListParameter>,
+) -> Result<Response, StatusCode> {
+    let list = find_list(&state.pg_connection_pool, parameter.list.as_deref())
+        .await
+        .map_err(e500)?
+        .ok_or(StatusCode::NOT_FOUND)?;
     let msg = read_flash_messages(&flash_messages);
-    (flash_messages, Subscribe { msg }).into_response()
+    let subscribe = Subscribe {
+        msg,
+        list,
+        form_token: state.bot_protection.form_token(&state.hmac_secret),
+        captcha: state.bot_protection.has_captcha(),
+    };
+    Ok((flash_messages, subscribe).into_response())
 }
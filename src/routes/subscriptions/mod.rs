@@ -1,7 +1,13 @@
 mod confirm;
+mod data;
+mod email_change;
 mod get;
 mod post;
+mod preferences;
 
 pub use confirm::*;
+pub use data::*;
+pub use email_change::*;
 pub use get::*;
 pub use post::*;
+pub use preferences::*;
@@ -1,4 +1,7 @@
-use crate::domain::{Subscriber, SubscriberEmail, SubscriberName};
+use crate::bot_protection::BotProtectionError;
+use crate::domain::{EmailNormalisation, Subscriber, SubscriberEmail, SubscriberName};
+use crate::mailing_lists::{find_list, MailingList};
+use crate::signup_policy::SignupPolicyError;
 use crate::AppState;
 use anyhow::Context;
 use axum::extract::State;
@@ -17,15 +20,22 @@ use uuid::Uuid;
 pub struct FormData {
     email: String,
     name: String,
+    #[serde(default)]
+    list: Option<String>,
+    #[serde(default)]
+    form_token: String,
+    #[serde(default)]
+    captcha_response: String,
+    /// Hidden from people, so only bots fill it in.
+    #[serde(default)]
+    website: String,
 }
 
-impl TryFrom<FormData> for Subscriber {
-    type Error = String;
-
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { email, name })
+impl FormData {
+    fn parse(self, normalisation: EmailNormalisation) -> Result<Subscriber, String> {
+        let name = SubscriberName::parse(self.name)?;
+        let email = SubscriberEmail::parse_with(self.email, normalisation)?;
+        Ok(Subscriber { email, name })
     }
 }
 
@@ -44,6 +54,24 @@ impl std::fmt::Debug for SubscribeError {
     }
 }
 
+impl From<BotProtectionError> for SubscribeError {
+    fn from(e: BotProtectionError) -> Self {
+        match e {
+            BotProtectionError::UnexpectedError(e) => Self::UnexpectedError(e),
+            e => Self::ValidationError(e.to_string()),
+        }
+    }
+}
+
+impl From<SignupPolicyError> for SubscribeError {
+    fn from(e: SignupPolicyError) -> Self {
+        match e {
+            SignupPolicyError::UnexpectedError(e) => Self::UnexpectedError(e),
+            e => Self::ValidationError(e.to_string()),
+        }
+    }
+}
+
 impl IntoResponse for SubscribeError {
     fn into_response(self) -> Response {
         match &self {
@@ -68,22 +96,50 @@ pub async fn subscribe(
     flash: Flash,
     form: Form<FormData>,
 ) -> Result<Response, SubscribeError> {
-    let subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let mut form = form.0;
+    let list = find_list(&state.pg_connection_pool, form.list.take().as_deref())
+        .await
+        .context("Failed to look up the mailing list")?
+        .ok_or_else(|| SubscribeError::ValidationError("Unknown mailing list.".into()))?;
+    let form_path = subscribe_form_path(&list);
+    let sent = "Sent verification mail. Please confirm to complete subscription.";
+    if !form.website.is_empty() {
+        // Look the same as a successful subscription so bots learn nothing.
+        tracing::warn!("Ignored a subscription that filled in the honeypot field");
+        return Ok((flash.info(sent), Redirect::to(&form_path)).into_response());
+    }
+    state
+        .bot_protection
+        .check(&form.form_token, &form.captcha_response, &state.hmac_secret)
+        .await?;
+    let subscriber = form
+        .parse(state.email_normalisation)
+        .map_err(SubscribeError::ValidationError)?;
+    state
+        .signup_policy
+        .check(&state.pg_connection_pool, &subscriber.email)
+        .await?;
     let mut transaction = state
         .pg_connection_pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &subscriber).await?;
+    let subscriber_id = insert_subscriber(&mut transaction, &subscriber, list.list_id).await?;
     if subscriber_id.is_none() {
         let flash = flash.info("Already subscribed!");
-        return Ok((flash, Redirect::to("/subscriptions")).into_response());
+        return Ok((flash, Redirect::to(&form_path)).into_response());
     }
     let subscriber_id = subscriber_id.unwrap();
 
     let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token).await?;
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        Some(list.list_id),
+        &subscription_token,
+    )
+    .await?;
 
     transaction
         .commit()
@@ -92,10 +148,19 @@ pub async fn subscribe(
 
     send_confirmation_email(&state, &subscriber, &subscription_token).await?;
 
-    let flash = flash.info("Sent verification mail. Please confirm to complete subscription.");
-    Ok((flash, Redirect::to("/subscriptions")).into_response())
+    Ok((flash.info(sent), Redirect::to(&form_path)).into_response())
+}
+
+fn subscribe_form_path(list: &MailingList) -> String {
+    if list.is_default {
+        "/subscriptions".into()
+    } else {
+        format!("/subscriptions?list={}", list.slug)
+    }
 }
 
+/// Adds the subscriber to `list_id`, creating them if they are new. Returns `None` if
+/// they are already a confirmed member of the list.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
@@ -103,6 +168,7 @@ pub async fn subscribe(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &Subscriber,
+    list_id: Uuid,
 ) -> anyhow::Result<Option<Uuid>> {
     sqlx::query!(
         r#"
@@ -122,18 +188,52 @@ pub async fn insert_subscriber(
         anyhow::anyhow!("Failed to insert new subscriber in the database. {e}")
     })?;
 
-    let result = sqlx::query!(
-        r#"SELECT id from subscriptions where email=$1 and status != 'confirmed'"#,
+    let subscriber_id = sqlx::query!(
+        r#"SELECT id from subscriptions where email=$1"#,
         new_subscriber.email.as_ref(),
     )
-    .fetch_optional(&mut **transaction)
+    .fetch_one(&mut **transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute get id query: {e:?}");
         anyhow::anyhow!("Failed to get new subscriber data from the database. {e}")
+    })?
+    .id;
+
+    // People who left the list earlier start over as pending members.
+    let membership = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        VALUES ($1, $2, 'pending_confirmation')
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = 'pending_confirmation', subscribed_at = now()
+        WHERE list_memberships.status = 'unsubscribed'
+        RETURNING status
+        "#,
+        list_id,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {e:?}");
+        anyhow::anyhow!("Failed to add the subscriber to the list. {e}")
     })?;
+    let status =
+        match membership {
+            Some(r) => r.status,
+            None => sqlx::query!(
+                r#"SELECT status FROM list_memberships WHERE list_id = $1 AND subscriber_id = $2"#,
+                list_id,
+                subscriber_id,
+            )
+            .fetch_one(&mut **transaction)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to get the list membership. {e}"))?
+            .status,
+        };
 
-    Ok(result.map(|r| r.id))
+    Ok((status != "confirmed").then_some(subscriber_id))
 }
 
 #[tracing::instrument(
@@ -158,6 +258,8 @@ pub async fn send_confirmation_email(
         .await
 }
 
+/// Stores a confirmation token. A token bound to a list confirms that membership only,
+/// otherwise it confirms every membership still pending.
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(subscription_token, transaction)
@@ -165,13 +267,15 @@ pub async fn send_confirmation_email(
 pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Option<Uuid>,
     subscription_token: &str,
 ) -> anyhow::Result<()> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        VALUES ($1, $2, $3)"#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        list_id,
     )
     .execute(&mut **transaction)
     .await
@@ -181,7 +285,7 @@ pub async fn store_token(
     Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -0,0 +1,397 @@
+use anyhow::Context;
+use askama_axum::Template;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Form;
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::error_chain_fmt;
+use crate::{
+    domain::{
+        DigestFrequency, PausePeriod, Subscriber, SubscriberEmail, SubscriberName, SubscriberToken,
+        TokenPurpose,
+    },
+    utils::{read_flash_messages, spawn_and_log_error},
+    AppState,
+};
+
+/// How long the emailed preferences link stays valid.
+const LINK_VALIDITY_DAYS: i64 = 7;
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("This link is invalid or has expired.")]
+    InvalidLink,
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PreferencesError {
+    fn into_response(self) -> Response {
+        match &self {
+            Self::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+            }
+            Self::InvalidLink => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "preferences_request.html")]
+struct PreferencesRequestForm {
+    msg: String,
+}
+
+pub async fn preferences_request_form(flash_messages: IncomingFlashes) -> Response {
+    let msg = read_flash_messages(&flash_messages);
+    (flash_messages, PreferencesRequestForm { msg }).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct PreferencesRequestFormData {
+    email: String,
+}
+
+/// Emails a preferences link to the address if it belongs to a confirmed subscriber. The
+/// response is the same either way, so the form cannot be used to probe who is subscribed:
+/// the email is sent in the background, so neither its latency nor its failure shows.
+#[tracing::instrument(name = "Request a subscriber preferences link", skip_all)]
+pub async fn request_preferences_link(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<PreferencesRequestFormData>,
+) -> Result<Response, PreferencesError> {
+    let email = match SubscriberEmail::parse_with(form.0.email, state.email_normalisation) {
+        Ok(email) => email,
+        Err(e) => {
+            return Ok((
+                flash.error(e),
+                Redirect::to("/subscriptions/preferences/link"),
+            )
+                .into_response())
+        }
+    };
+
+    let subscriber = sqlx::query!(
+        r#"SELECT id, name FROM subscriptions WHERE email = $1 AND status = 'confirmed'"#,
+        email.as_ref(),
+    )
+    .fetch_optional(&state.pg_connection_pool)
+    .await
+    .context("Failed to look up the subscriber")?;
+
+    if let Some(r) = subscriber {
+        match SubscriberName::parse(r.name) {
+            Ok(name) => {
+                let subscriber = Subscriber { email, name };
+                let state = state.0.clone();
+                spawn_and_log_error(async move {
+                    send_preferences_email(&state, r.id, &subscriber).await
+                });
+            }
+            Err(e) => tracing::warn!(
+                error.message = %e,
+                "Cannot send a preferences link. The stored subscriber name is invalid",
+            ),
+        }
+    }
+
+    let flash =
+        flash.info("If this address is subscribed, we have sent it a link to your preferences.");
+    Ok((flash, Redirect::to("/subscriptions/preferences/link")).into_response())
+}
+
+#[tracing::instrument(name = "Send a preferences link", skip(state, subscriber))]
+async fn send_preferences_email(
+    state: &AppState,
+    subscriber_id: Uuid,
+    subscriber: &Subscriber,
+) -> anyhow::Result<()> {
+    let token = SubscriberToken::issue(
+        subscriber_id,
+        TokenPurpose::Preferences,
+        Duration::days(LINK_VALIDITY_DAYS),
+        &state.hmac_secret,
+    );
+    let link = state
+        .application_base_url
+        .join(&format!("subscriptions/preferences?token={token}"))?;
+    let content = format!(
+        "<a href=\"{}\">Manage your subscription preferences</a>. This link expires in {LINK_VALIDITY_DAYS} days.<br />\
+        If you did not ask for it you can ignore this email.",
+        link.as_str()
+    );
+    state
+        .email_client
+        .send_email(subscriber, "Your subscription preferences", &content)
+        .await
+}
+
+pub(super) fn verify_token(state: &AppState, token: &str) -> Result<Uuid, PreferencesError> {
+    SubscriberToken::verify(token, TokenPurpose::Preferences, &state.hmac_secret)
+        .map(|token| token.subscriber_id)
+        .map_err(|e| {
+            tracing::warn!(error.message = %e, "Rejected a preferences link");
+            PreferencesError::InvalidLink
+        })
+}
+
+pub(super) fn preferences_path(token: &str) -> String {
+    format!("/subscriptions/preferences?token={token}")
+}
+
+#[derive(Deserialize)]
+pub struct TokenParameter {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "preferences.html")]
+struct Preferences {
+    msg: String,
+    token: String,
+    email: String,
+    name: String,
+    digest_frequency: DigestFrequency,
+    paused_until: Option<DateTime<Utc>>,
+    lists: Vec<ListChoice>,
+}
+
+impl Preferences {
+    fn frequencies(&self) -> [DigestFrequency; 3] {
+        DigestFrequency::ALL
+    }
+
+    fn pause_periods(&self) -> [PausePeriod; 3] {
+        PausePeriod::ALL
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused_until.is_some_and(|until| until > Utc::now())
+    }
+}
+
+struct ListChoice {
+    list_id: Uuid,
+    name: String,
+    selected: bool,
+}
+
+pub async fn preferences(
+    state: State<AppState>,
+    flash_messages: IncomingFlashes,
+    parameter: Query<TokenParameter>,
+) -> Result<Response, PreferencesError> {
+    let subscriber_id = verify_token(&state, &parameter.token)?;
+    let pg_pool = &state.pg_connection_pool;
+    let r = sqlx::query!(
+        r#"
+        SELECT email, name, digest_frequency, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .context("Failed to fetch the subscriber")?
+    .ok_or(PreferencesError::InvalidLink)?;
+    let digest_frequency = DigestFrequency::parse(&r.digest_frequency)
+        .map_err(anyhow::Error::msg)
+        .context("The stored digest frequency is invalid")?;
+    let lists = get_list_choices(pg_pool, subscriber_id)
+        .await
+        .context("Failed to fetch the mailing lists")?;
+
+    let msg = read_flash_messages(&flash_messages);
+    Ok((
+        flash_messages,
+        Preferences {
+            msg,
+            token: parameter.0.token,
+            email: r.email,
+            name: r.name,
+            digest_frequency,
+            paused_until: r.paused_until,
+            lists,
+        },
+    )
+        .into_response())
+}
+
+async fn get_list_choices(
+    pg_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListChoice>, sqlx::Error> {
+    sqlx::query_as!(
+        ListChoice,
+        r#"
+        SELECT
+            l.list_id, l.name,
+            COALESCE(m.status = 'confirmed', false) as "selected!"
+        FROM lists l
+        LEFT JOIN list_memberships m
+            ON m.list_id = l.list_id AND m.subscriber_id = $1
+        ORDER BY l.is_default DESC, l.name
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pg_pool)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct PreferencesFormData {
+    token: String,
+    name: String,
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
+    digest_frequency: String,
+    #[serde(default)]
+    pause: String,
+}
+
+/// What to do with the subscriber's pause, as picked on the form.
+enum PauseChange {
+    Keep,
+    Resume,
+    Pause(PausePeriod),
+}
+
+impl PauseChange {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "" => Ok(Self::Resume),
+            s => PausePeriod::parse(s).map(Self::Pause),
+        }
+    }
+}
+
+struct NewPreferences {
+    name: SubscriberName,
+    list_ids: Vec<Uuid>,
+    digest_frequency: DigestFrequency,
+    pause: PauseChange,
+}
+
+impl TryFrom<PreferencesFormData> for NewPreferences {
+    type Error = String;
+
+    fn try_from(form: PreferencesFormData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: SubscriberName::parse(form.name)?,
+            list_ids: form.list_ids,
+            digest_frequency: DigestFrequency::parse(&form.digest_frequency)?,
+            pause: PauseChange::parse(&form.pause)?,
+        })
+    }
+}
+
+#[tracing::instrument(name = "Update subscriber preferences", skip_all)]
+pub async fn update_preferences(
+    state: State<AppState>,
+    flash: Flash,
+    form: Form<PreferencesFormData>,
+) -> Result<Response, PreferencesError> {
+    let token = form.0.token.clone();
+    let subscriber_id = verify_token(&state, &token)?;
+    let redirect = Redirect::to(&preferences_path(&token));
+    let preferences: NewPreferences = match form.0.try_into() {
+        Ok(preferences) => preferences,
+        Err(e) => return Ok((flash.error(e), redirect).into_response()),
+    };
+
+    let mut transaction = state
+        .pg_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let updated = apply_preferences(&mut transaction, subscriber_id, &preferences)
+        .await
+        .context("Failed to update the subscriber preferences")?;
+    if !updated {
+        return Err(PreferencesError::InvalidLink);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update subscriber preferences.")?;
+
+    Ok((flash.info("Your preferences have been saved."), redirect).into_response())
+}
+
+/// Joins the subscriber to the selected lists and leaves the others. The link they
+/// followed was emailed to them, so new memberships need no further confirmation.
+async fn apply_preferences(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    preferences: &NewPreferences,
+) -> Result<bool, sqlx::Error> {
+    let (keep_pause, paused_until) = match preferences.pause {
+        PauseChange::Keep => (true, None),
+        PauseChange::Resume => (false, None),
+        PauseChange::Pause(period) => (false, Some(period.until(Utc::now()))),
+    };
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            name = $2,
+            digest_frequency = $3,
+            paused_until = CASE WHEN $4 THEN paused_until ELSE $5 END
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        preferences.name.as_ref(),
+        preferences.digest_frequency.as_str(),
+        keep_pause,
+        paused_until,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT list_id, $1, 'confirmed' FROM lists WHERE list_id = ANY($2)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = 'confirmed', subscribed_at = now()
+        WHERE list_memberships.status <> 'confirmed'
+        "#,
+        subscriber_id,
+        &preferences.list_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = $1
+            AND NOT (list_id = ANY($2))
+            AND status <> 'unsubscribed'
+        "#,
+        subscriber_id,
+        &preferences.list_ids,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(true)
+}
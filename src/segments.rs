@@ -0,0 +1,121 @@
+use serde::Serialize;
+use sqlx::{types::Json, PgExecutor, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::domain::{SegmentCondition, SegmentDefinition};
+
+/// A named subset of subscribers that issues can be sent to.
+#[derive(Debug)]
+pub struct Segment {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub definition: Json<SegmentDefinition>,
+}
+
+#[tracing::instrument(name = "Get a segment", skip(executor))]
+pub async fn get_segment(
+    executor: impl PgExecutor<'_>,
+    segment_id: Uuid,
+) -> Result<Option<Segment>, sqlx::Error> {
+    sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, definition as "definition: Json<SegmentDefinition>"
+        FROM segments
+        WHERE segment_id = $1
+        "#,
+        segment_id,
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+#[tracing::instrument(name = "Get all segments", skip(executor))]
+pub async fn get_all_segments(executor: impl PgExecutor<'_>) -> Result<Vec<Segment>, sqlx::Error> {
+    sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, definition as "definition: Json<SegmentDefinition>"
+        FROM segments
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// A custom attribute of a subscriber, as stored.
+#[derive(Debug, Serialize)]
+pub struct Attribute {
+    pub key: String,
+    pub value: String,
+}
+
+#[tracing::instrument(name = "Get a subscriber's tags", skip(executor))]
+pub async fn get_tags(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscriber_id,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+#[tracing::instrument(name = "Get a subscriber's attributes", skip(executor))]
+pub async fn get_attributes(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Vec<Attribute>, sqlx::Error> {
+    sqlx::query_as!(
+        Attribute,
+        r#"SELECT key, value FROM subscriber_attributes WHERE subscriber_id = $1 ORDER BY key"#,
+        subscriber_id,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Appends the segment's conditions. The builder must already contain a `WHERE` clause
+/// over `subscriptions` aliased as `s`.
+pub fn push_segment_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    segment: &SegmentDefinition,
+) {
+    for condition in segment.conditions() {
+        match condition {
+            SegmentCondition::SubscribedAfter { date } => {
+                query
+                    .push(" AND s.subscribed_at >= ")
+                    .push_bind(date.and_time(Default::default()).and_utc());
+            }
+            SegmentCondition::SubscribedBefore { date } => {
+                query
+                    .push(" AND s.subscribed_at < ")
+                    .push_bind(date.and_time(Default::default()).and_utc());
+            }
+            SegmentCondition::HasTag { tag } => {
+                query
+                    .push(
+                        " AND EXISTS (SELECT 1 FROM subscriber_tags t \
+                        WHERE t.subscriber_id = s.id AND t.tag = ",
+                    )
+                    .push_bind(tag.as_ref().to_string())
+                    .push(")");
+            }
+            SegmentCondition::AttributeEquals { key, value } => {
+                query
+                    .push(
+                        " AND EXISTS (SELECT 1 FROM subscriber_attributes a \
+                        WHERE a.subscriber_id = s.id AND a.key = ",
+                    )
+                    .push_bind(key.as_ref().to_string())
+                    .push(" AND a.value = ")
+                    .push_bind(value.clone())
+                    .push(")");
+            }
+        }
+    }
+}
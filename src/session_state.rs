@@ -1,18 +1,33 @@
 use axum::extract::FromRequestParts;
+use chrono::{DateTime, Utc};
 use tower_sessions::Session;
 use uuid::Uuid;
 
+/// Sessions are dropped after this long without a request.
+pub const SESSION_INACTIVITY_TIMEOUT_SECONDS: i64 = 900;
+
 #[derive(FromRequestParts)]
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const AUTHENTICATED_AT_KEY: &'static str = "authenticated_at";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const TOTP_ENROLMENT_KEY: &'static str = "totp_enrolment";
 
     pub fn renew(&self) {
         self.0.cycle_id();
     }
 
-    pub fn insert_user_id(&self, user_id: Uuid) -> anyhow::Result<()> {
+    /// Logs the user in. `session_id` identifies the session in `user_sessions`.
+    pub fn insert_user_id(&self, user_id: Uuid, session_id: Uuid) -> anyhow::Result<()> {
+        self.0
+            .insert(Self::AUTHENTICATED_AT_KEY, Utc::now())
+            .map_err(|e| anyhow::anyhow!(e))?;
+        self.0
+            .insert(Self::SESSION_ID_KEY, session_id)
+            .map_err(|e| anyhow::anyhow!(e))?;
         self.0
             .insert(Self::USER_ID_KEY, user_id)
             .map_err(|e| anyhow::anyhow!(e))
@@ -24,6 +39,60 @@ impl TypedSession {
             .map_err(|e| anyhow::anyhow!(e))
     }
 
+    pub fn get_session_id(&self) -> anyhow::Result<Option<Uuid>> {
+        self.0
+            .get(Self::SESSION_ID_KEY)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// When the user logged in. Unknown for sessions from before this was recorded.
+    pub fn get_authenticated_at(&self) -> anyhow::Result<Option<DateTime<Utc>>> {
+        self.0
+            .get(Self::AUTHENTICATED_AT_KEY)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Remembers a user who gave the right password but still has to give a second factor.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> anyhow::Result<()> {
+        self.0
+            .insert(Self::PENDING_USER_ID_KEY, user_id)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    pub fn get_pending_user_id(&self) -> anyhow::Result<Option<Uuid>> {
+        self.0
+            .get(Self::PENDING_USER_ID_KEY)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Forgets the pending user, once they are fully authenticated.
+    pub fn remove_pending_user_id(&self) -> anyhow::Result<()> {
+        self.0
+            .remove::<Uuid>(Self::PENDING_USER_ID_KEY)
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// The base32 TOTP secret being set up, until the user proves their authenticator has it.
+    pub fn insert_totp_enrolment(&self, secret: &str) -> anyhow::Result<()> {
+        self.0
+            .insert(Self::TOTP_ENROLMENT_KEY, secret)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    pub fn get_totp_enrolment(&self) -> anyhow::Result<Option<String>> {
+        self.0
+            .get(Self::TOTP_ENROLMENT_KEY)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    pub fn remove_totp_enrolment(&self) -> anyhow::Result<()> {
+        self.0
+            .remove::<String>(Self::TOTP_ENROLMENT_KEY)
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
     pub fn log_out(self) {
         self.0.flush()
     }
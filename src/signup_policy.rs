@@ -0,0 +1,84 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::Context;
+use sqlx::PgExecutor;
+
+use crate::{
+    domain::{domain_suffixes, SubscriberEmail},
+    routes::error_chain_fmt,
+};
+
+/// Decides which addresses may sign up: those from disposable email providers listed in
+/// a file are refused, as are the domains and addresses admins have blocked.
+#[derive(Debug, Default)]
+pub struct SignupPolicy {
+    disposable_domains: HashSet<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum SignupPolicyError {
+    #[error("Addresses from disposable email providers cannot subscribe.")]
+    DisposableDomain,
+    #[error("This email address cannot subscribe.")]
+    Blocked,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SignupPolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl SignupPolicy {
+    /// Reads one domain per line. Blank lines and lines starting with `#` are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the disposable domains in {path:?}"))?;
+        Ok(Self::new(contents.lines()))
+    }
+
+    pub fn new<'a>(disposable_domains: impl IntoIterator<Item = &'a str>) -> Self {
+        let disposable_domains = disposable_domains
+            .into_iter()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Self { disposable_domains }
+    }
+
+    #[tracing::instrument(name = "Check the signup policy", skip(self, executor))]
+    pub async fn check(
+        &self,
+        executor: impl PgExecutor<'_>,
+        email: &SubscriberEmail,
+    ) -> Result<(), SignupPolicyError> {
+        let suffixes = domain_suffixes(email);
+        if suffixes
+            .iter()
+            .any(|domain| self.disposable_domains.contains(domain))
+        {
+            return Err(SignupPolicyError::DisposableDomain);
+        }
+
+        let blocked = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM blocked_senders WHERE entry = lower($1) OR entry = ANY($2)
+            ) as "blocked!"
+            "#,
+            email.as_ref(),
+            &suffixes,
+        )
+        .fetch_one(executor)
+        .await
+        .context("Failed to look up blocked senders")?;
+        if blocked {
+            return Err(SignupPolicyError::Blocked);
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,38 @@
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, RedisSettings, Settings};
+use crate::authentication::{
+    reject_anonymous_users, reject_viewer_changes, require_owner, PasswordPolicy,
+};
+use crate::bot_protection::BotProtection;
+use crate::configuration::{DatabaseSettings, RateLimitSettings, RedisSettings, Settings};
+use crate::rate_limit::{rate_limit, RateLimit, RateLimiter};
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, confirm, health_check, home, issue,
-    issues, log_out, login, login_form, publish_newsletter, publish_newsletter_form, subscribe,
-    subscribe_form, subscribers_list,
+    accept_invitation, admin_dashboard, archive, archive_issue, atom_feed, block_sender, blocklist,
+    change_password, change_password_form, change_user_role, confirm, confirm_email_change,
+    create_list, create_segment, data_request_form, disable_totp, edit_subscriber,
+    edit_subscriber_attributes, enable_totp, erase_data, export_data, export_subscribers_csv,
+    export_subscribers_json, forgot_password_form, health_check, home, import_rejections_csv,
+    import_report, import_subscribers, import_subscribers_form, invitation_form, invite_user,
+    issue, issues, lists, lockouts, log_out, log_out_other_sessions, log_out_session, login,
+    login_form, manage_data, newsletter_recipients, preferences, preferences_request_form,
+    publish_newsletter, publish_newsletter_form, request_data_access, request_email_change,
+    request_password_reset, request_preferences_link, reset_password_form,
+    reset_password_with_token, rss_feed, security, segments, sessions, set_archive_visibility,
+    subscribe, subscribe_form, subscriber, subscriber_action, subscribers_bulk_action,
+    subscribers_list, two_factor_form, unblock_sender, unlock_login, update_account_email,
+    update_preferences, user_action, users, verify_two_factor, MAX_IMPORT_SIZE,
 };
+use crate::session_state::SESSION_INACTIVITY_TIMEOUT_SECONDS;
+use crate::signup_policy::SignupPolicy;
 use crate::{AppState, HmacSecret};
 
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::extract::{ConnectInfo, DefaultBodyLimit};
 use axum::middleware;
+use axum::middleware::AddExtension;
 use axum::routing::post;
 use secrecy::ExposeSecret;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::request_id::MakeRequestUuid;
 use tower_http::ServiceBuilderExt;
@@ -37,7 +58,10 @@ use tracing::{field::Empty, info, Span};
 use axum::error_handling::HandleErrorLayer;
 use tower_sessions::{Expiry, SessionManagerLayer};
 
-type Server = Serve<Router, Router>;
+type Server = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
 
 pub struct Application {
     port: u16,
@@ -55,13 +79,26 @@ impl Application {
                 .expose_secret()
                 .as_bytes(),
         );
+        // Fail now rather than at the first login
+        configuration.password_hashing.params()?;
         let redis_client = redis_client(configuration.redis).await?;
+        let rate_limiter = RateLimiter::new(Some(redis_client.clone()), &configuration.rate_limits);
+        let signup_policy = SignupPolicy::from_file(&configuration.signup.disposable_domains_file)?;
         let app_state = AppState {
             pg_connection_pool,
             email_client,
             application_base_url: Arc::new(configuration.application.base_url),
             hmac_secret: Arc::new(HmacSecret(configuration.application.hmac_secret)),
             flash_config: axum_flash::Config::new(flash_key),
+            email_normalisation: configuration.application.email_normalisation,
+            signup_policy: Arc::new(signup_policy),
+            bot_protection: Arc::new(BotProtection::new(&configuration.signup)),
+            rate_limiter: Arc::new(rate_limiter),
+            login_throttling: configuration.login_throttling,
+            password_policy: Arc::new(PasswordPolicy::from_settings(
+                &configuration.password_policy,
+            )?),
+            password_hashing: configuration.password_hashing,
         };
 
         let address = format!(
@@ -72,7 +109,7 @@ impl Application {
             .await
             .expect("Failed to bind port");
         let port = listener.local_addr()?.port();
-        let server = run(listener, app_state, redis_client)?;
+        let server = run(listener, app_state, redis_client, configuration.rate_limits)?;
 
         Ok(Self { port, server })
     }
@@ -90,7 +127,9 @@ pub fn run(
     listener: TcpListener,
     app_state: crate::AppState,
     redis_client: RedisClient,
+    rate_limits: RateLimitSettings,
 ) -> anyhow::Result<Server> {
+    let rate_limiter = app_state.rate_limiter.clone();
     let session_store = RedisStore::new(redis_client);
     let service = ServiceBuilder::new()
         .set_x_request_id(MakeRequestUuid)
@@ -128,21 +167,79 @@ pub fn run(
         .layer(
             SessionManagerLayer::new(session_store)
                 .with_secure(true)
-                .with_expiry(Expiry::OnInactivity(time::Duration::seconds(900))),
+                .with_expiry(Expiry::OnInactivity(time::Duration::seconds(
+                    SESSION_INACTIVITY_TIMEOUT_SECONDS,
+                ))),
         )
         .propagate_x_request_id();
 
-    let admin_routes = Router::new()
+    // Any user can manage their own account
+    let account_routes = Router::new()
         .route("/dashboard", get(admin_dashboard))
         .route("/password", get(change_password_form).post(change_password))
         .route("/logout", post(log_out))
+        .route("/security", get(security))
+        .route("/security/enable", post(enable_totp))
+        .route("/security/disable", post(disable_totp))
+        .route("/security/email", post(update_account_email))
+        .route("/security/sessions", get(sessions))
+        .route("/security/sessions/revoke", post(log_out_session))
+        .route(
+            "/security/sessions/revoke-others",
+            post(log_out_other_sessions),
+        );
+
+    let owner_routes = Router::new()
+        .route("/users", get(users))
+        .route("/users/invite", post(invite_user))
+        .route("/users/:id/role", post(change_user_role))
+        .route("/users/:id/action", post(user_action))
+        .route("/lockouts", get(lockouts))
+        .route("/lockouts/unlock", post(unlock_login))
+        .layer(middleware::from_fn(require_owner));
+
+    // Editors can change content, viewers can only look at it
+    let content_routes = Router::new()
         .route(
             "/newsletters",
             get(publish_newsletter_form).post(publish_newsletter),
         )
+        .route("/newsletters/recipients", get(newsletter_recipients))
+        .route("/lists", get(lists).post(create_list))
+        .route("/segments", get(segments).post(create_segment))
+        .route("/blocklist", get(blocklist).post(block_sender))
+        .route("/blocklist/remove", post(unblock_sender))
         .route("/issues", get(issues))
         .route("/issue/:id", get(issue))
+        .route("/issue/:id/archive", post(set_archive_visibility))
         .route("/subscribers", get(subscribers_list))
+        .route("/subscribers/bulk", post(subscribers_bulk_action))
+        .route("/subscriber/:id", get(subscriber))
+        .route("/subscriber/:id/edit", post(edit_subscriber))
+        .route(
+            "/subscriber/:id/attributes",
+            post(edit_subscriber_attributes),
+        )
+        .route("/subscriber/:id/action", post(subscriber_action))
+        .route("/subscribers/export.csv", get(export_subscribers_csv))
+        .route("/subscribers/export.json", get(export_subscribers_json))
+        .route(
+            "/subscribers/import",
+            get(import_subscribers_form)
+                .post(import_subscribers)
+                .layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+        )
+        .route("/subscribers/import/:id", get(import_report))
+        .route(
+            "/subscribers/import/:id/rejected.csv",
+            get(import_rejections_csv),
+        )
+        .layer(middleware::from_fn(reject_viewer_changes));
+
+    let admin_routes = Router::new()
+        .merge(account_routes)
+        .merge(owner_routes)
+        .merge(content_routes)
         .layer(middleware::from_fn_with_state(
             app_state.clone(),
             reject_anonymous_users,
@@ -150,18 +247,75 @@ pub fn run(
 
     let subscription_routes = Router::new()
         .route("/", get(subscribe_form).post(subscribe))
-        .route("/confirm", get(confirm));
+        .route("/confirm", get(confirm))
+        .route("/data", get(data_request_form).post(request_data_access))
+        .route("/data/manage", get(manage_data))
+        .route("/data/export", get(export_data))
+        .route("/data/erase", post(erase_data))
+        .route("/preferences", get(preferences).post(update_preferences))
+        .route("/preferences/email", post(request_email_change))
+        .route("/email/confirm", get(confirm_email_change))
+        .route(
+            "/preferences/link",
+            get(preferences_request_form).post(request_preferences_link),
+        )
+        .layer(middleware::from_fn_with_state(
+            RateLimit::new(
+                rate_limiter.clone(),
+                "subscriptions",
+                rate_limits.subscriptions,
+            ),
+            rate_limit,
+        ));
 
+    let login_rate_limit = middleware::from_fn_with_state(
+        RateLimit::new(rate_limiter, "login", rate_limits.login),
+        rate_limit,
+    );
     let app = Router::new()
         .route("/health_check", get(health_check))
         .route("/", get(home))
-        .route("/login", get(login_form).post(login))
+        .route(
+            "/login",
+            get(login_form).post(login).layer(login_rate_limit.clone()),
+        )
+        .route(
+            "/login/two-factor",
+            get(two_factor_form)
+                .post(verify_two_factor)
+                .layer(login_rate_limit.clone()),
+        )
+        .route(
+            "/login/forgot",
+            get(forgot_password_form)
+                .post(request_password_reset)
+                .layer(login_rate_limit.clone()),
+        )
+        .route(
+            "/login/reset",
+            get(reset_password_form)
+                .post(reset_password_with_token)
+                .layer(login_rate_limit.clone()),
+        )
+        .route(
+            "/login/invitation",
+            get(invitation_form)
+                .post(accept_invitation)
+                .layer(login_rate_limit),
+        )
+        .route("/archive", get(archive))
+        .route("/archive/:slug", get(archive_issue))
+        .route("/feed.rss", get(rss_feed))
+        .route("/feed.atom", get(atom_feed))
         .nest("/subscriptions", subscription_routes)
         .nest("/admin", admin_routes)
         .with_state(app_state)
         .layer(service);
 
-    Ok(axum::serve(listener, app))
+    Ok(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    ))
 }
 
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
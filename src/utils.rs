@@ -1,7 +1,11 @@
 use anyhow::Context;
 use axum::http::StatusCode;
 use axum_flash::IncomingFlashes;
-use std::fmt::Write;
+use serde::{de, Deserialize, Deserializer};
+use std::fmt::{Display, Write};
+use std::future::Future;
+use std::str::FromStr;
+use tracing::Instrument;
 
 pub fn e500<T>(e: T) -> StatusCode
 where
@@ -26,3 +30,36 @@ pub fn read_flash_messages(flash_messages: &IncomingFlashes) -> String {
     }
     msg_html
 }
+
+/// Runs `task` without waiting for it, logging its error if it fails. For work that must
+/// not change the response, not even its timing, such as emails whose only recipients
+/// are existing accounts.
+pub fn spawn_and_log_error<F>(task: F)
+where
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let task = async move {
+        if let Err(e) = task.await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "A background task failed"
+            );
+        }
+    };
+    tokio::spawn(task.instrument(tracing::Span::current()));
+}
+
+/// HTML forms submit empty strings for blank fields: treat them as missing.
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(de::Error::custom),
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn publish_issue(app: &TestApp, title: &str, content: &str) -> (Uuid, String) {
+    let newsletter_request_body = serde_json::json!({
+        "title": title,
+        "content": content,
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    let row = sqlx::query!(
+        "SELECT newsletter_issue_id, slug FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the published issue.");
+    (row.newsletter_issue_id, row.slug)
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_in_the_public_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let (_, slug) = publish_issue(&app, "Archived issue", "<p>Archived body</p>").await;
+    assert!(slug.starts_with("archived-issue-"));
+
+    let html_page = app.get_archive_html().await;
+    assert!(html_page.contains("Archived issue"));
+    assert!(html_page.contains(&format!("/archive/{slug}")));
+
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("<p>Archived body</p>"));
+}
+
+#[tokio::test]
+async fn unknown_archive_slugs_return_a_404() {
+    let app = spawn_app().await;
+
+    let response = app.get_archive_issue("not-an-issue").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn scripts_in_issues_are_removed_from_the_public_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let (_, slug) = publish_issue(
+        &app,
+        "Scripted issue",
+        r#"<p onclick="steal()">Body</p><script>steal()</script>"#,
+    )
+    .await;
+
+    let html_page = app.get_archive_issue(&slug).await.text().await.unwrap();
+    assert!(html_page.contains("<p>Body</p>"));
+    assert!(!html_page.contains("steal()"));
+}
+
+#[tokio::test]
+async fn hidden_issues_are_not_shown_in_the_public_archive() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (issue_id, slug) = publish_issue(&app, "Secret issue", "Secret body").await;
+
+    let response = app.post_archive_visibility(issue_id, true).await;
+    assert_is_redirect_to(&response, &format!("/admin/issue/{issue_id}"));
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("The issue is now hidden from the public archive."));
+
+    let html_page = app.get_archive_html().await;
+    assert!(!html_page.contains("Secret issue"));
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.post_archive_visibility(issue_id, false).await;
+    let response = app.get_archive_issue(&slug).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_archive_visibility() {
+    let app = spawn_app().await;
+
+    let response = app.post_archive_visibility(Uuid::new_v4(), true).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sent_issues_contain_a_view_in_browser_link() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, 'reader@example.com', 'Reader', now(), 'confirmed')
+        "#,
+        Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list("reader@example.com").await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let (_, slug) = publish_issue(&app, "Linked issue", "<p>Linked body</p>").await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_content = body["htmlContent"].as_str().unwrap();
+    assert!(html_content.starts_with("<p>Linked body</p>"));
+    assert!(html_content.contains(&format!("/archive/{slug}\">View in browser</a>")));
+}
@@ -0,0 +1,144 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn block(app: &TestApp, entry: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/blocklist", &app.address))
+        .form(&[("entry", entry)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn n_subscribers(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_block_senders() {
+    let app = spawn_app().await;
+
+    let response = block(&app, "example.com").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn disposable_domains_cannot_subscribe() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["ursula%40mailinator.com", "ursula%40inbox.Yopmail.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={email}"))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "Addresses from disposable email providers cannot subscribe."
+        );
+    }
+    assert_eq!(n_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn blocked_domains_and_addresses_cannot_subscribe() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = block(&app, "Spam.example").await;
+    assert_is_redirect_to(&response, "/admin/blocklist");
+    block(&app, "Bot@Example.com").await;
+
+    for email in [
+        "ursula%40spam.example",
+        "ursula%40mail.spam.example",
+        "bot%40example.com",
+    ] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={email}"))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "{email}");
+    }
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(n_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn blocked_and_disposable_addresses_are_rejected_on_import() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    block(&app, "spam.example").await;
+    let csv = "Name,Email\n\
+        Ursula,ursula@example.com\n\
+        Spammer,ursula@spam.example\n\
+        Throwaway,ursula@mailinator.com\n";
+
+    let response = app.post_subscribers_import("confirmed", false, csv).await;
+
+    let location = response.headers()["Location"].to_str().unwrap().to_owned();
+    let report = app
+        .api_client
+        .get(format!("{}{location}/rejected.csv", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let rows: Vec<&str> = report.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("3,ursula@spam.example,Spammer,"));
+    assert!(rows[1].contains("This email address cannot subscribe."));
+    assert!(rows[2].starts_with("4,ursula@mailinator.com,Throwaway,"));
+    assert_eq!(n_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn an_admin_can_manage_the_blocklist() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    block(&app, "Spam.example").await;
+    let html_page = app.get_admin_page_html("/admin/blocklist").await;
+    assert!(html_page.contains("spam.example can no longer be used to subscribe."));
+    assert!(html_page.contains(r#"<td>spam.example</td>"#));
+
+    block(&app, "not a domain").await;
+    let html_page = app.get_admin_page_html("/admin/blocklist").await;
+    assert!(html_page.contains("not a domain is not a valid domain or email address."));
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/blocklist/remove", &app.address))
+        .form(&[("entry", "spam.example")])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/blocklist");
+    let html_page = app.get_admin_page_html("/admin/blocklist").await;
+    assert!(html_page.contains("spam.example has been unblocked."));
+    assert!(!html_page.contains(r#"<td>spam.example</td>"#));
+}
@@ -0,0 +1,141 @@
+use chrono::{Duration, Utc};
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zerotoprod::{captcha::CaptchaSettings, domain::FormToken};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+const BODY: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+async fn n_subscribers(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+fn form_token_in(html_page: &str) -> String {
+    let start = html_page.find(r#"name="form_token" value=""#).unwrap() + 25;
+    let end = start + html_page[start..].find('"').unwrap();
+    html_page[start..end].to_string()
+}
+
+#[tokio::test]
+async fn the_subscribe_form_carries_a_token_and_a_honeypot() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_admin_page_html("/subscriptions").await;
+
+    assert!(!form_token_in(&html_page).is_empty());
+    assert!(html_page.contains(r#"name="website""#));
+    assert!(!html_page.contains(r#"name="captcha_response""#));
+}
+
+#[tokio::test]
+async fn a_filled_in_honeypot_looks_successful_but_subscribes_nobody() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(format!("{BODY}&website=http%3A%2F%2Fspam.example"))
+        .await;
+
+    assert_is_redirect_to(&response, "/subscriptions");
+    let html_page = app.get_admin_page_html("/subscriptions").await;
+    assert!(html_page.contains("Sent verification mail."));
+    assert_eq!(n_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn submissions_without_a_valid_form_token_are_rejected() {
+    let app = spawn_app().await;
+    let too_fast = FormToken::issue(Utc::now(), &app.hmac_secret);
+    let stale = FormToken::issue(Utc::now() - Duration::days(2), &app.hmac_secret);
+
+    for (body, message) in [
+        (
+            BODY.to_string(),
+            "Please reload the page and submit the form again.",
+        ),
+        (
+            format!("{BODY}&form_token=forged"),
+            "Please reload the page and submit the form again.",
+        ),
+        (
+            format!("{BODY}&form_token={too_fast}"),
+            "The form was submitted too quickly. Please try again.",
+        ),
+        (
+            format!("{BODY}&form_token={stale}"),
+            "The form has expired. Please reload the page and try again.",
+        ),
+    ] {
+        let response = app.post_subscriptions_raw(body).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(response.text().await.unwrap(), message);
+    }
+    assert_eq!(n_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn the_token_from_the_form_is_accepted() {
+    let app = spawn_app_with(|c| c.signup.min_form_seconds = 0).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let html_page = app.get_admin_page_html("/subscriptions").await;
+    let form_token = form_token_in(&html_page);
+    let response = app
+        .post_subscriptions_raw(format!("{BODY}&form_token={form_token}"))
+        .await;
+
+    assert_is_redirect_to(&response, "/subscriptions");
+    assert_eq!(n_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn a_configured_captcha_must_be_solved() {
+    let app = spawn_app_with(|c| {
+        c.signup.captcha = Some(CaptchaSettings::Local {
+            answer: Secret::new("let me in".into()),
+        })
+    })
+    .await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let html_page = app.get_admin_page_html("/subscriptions").await;
+    assert!(html_page.contains(r#"name="captcha_response""#));
+
+    for body in [BODY.to_string(), format!("{BODY}&captcha_response=guess")] {
+        let response = app.post_subscriptions(body).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.text().await.unwrap(),
+            "Please complete the CAPTCHA."
+        );
+    }
+
+    let response = app
+        .post_subscriptions(format!("{BODY}&captcha_response=let+me+in"))
+        .await;
+    assert_is_redirect_to(&response, "/subscriptions");
+    assert_eq!(n_subscribers(&app).await, 1);
+}
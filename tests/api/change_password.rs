@@ -117,3 +117,41 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_passwords_must_satisfy_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let with_username = format!("{}-extra", app.test_user.username);
+    for (new_password, error_message) in [
+        ("short", "Passwords must be at least 12 characters long."),
+        (
+            "PASSWORD1234",
+            "This password is one of the most common passwords.",
+        ),
+        ("Password123456789", "This password is too easy to guess."),
+        (&with_username, "Passwords must not contain your username."),
+    ] {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": new_password,
+                "new_password_check": new_password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = app.get_change_password_html().await;
+        assert!(html_page.contains(error_message), "{new_password}");
+    }
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
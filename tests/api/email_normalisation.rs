@@ -0,0 +1,142 @@
+use uuid::Uuid;
+use zerotoprod::domain::EmailNormalisation;
+use zerotoprod::email_normalisation::{normalise_stored_emails, NormalisedEmails};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: &str, days_ago: i32) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', now() - make_interval(days => $4), $3)",
+        id,
+        email,
+        status,
+        days_ago,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list(email).await;
+    id
+}
+
+async fn emails(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn internationalised_domains_are_rewritten_as_punycode() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@bücher.example", "confirmed", 1).await;
+    insert_subscriber(&app, "frank@example.com", "confirmed", 1).await;
+
+    let report = normalise_stored_emails(&app.db_pool, EmailNormalisation::default())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        NormalisedEmails {
+            rewritten: 1,
+            merged: 0
+        }
+    );
+    assert_eq!(
+        emails(&app).await,
+        ["frank@example.com", "ursula@xn--bcher-kva.example"]
+    );
+    let report = normalise_stored_emails(&app.db_pool, EmailNormalisation::default())
+        .await
+        .unwrap();
+    assert_eq!(report, NormalisedEmails::default());
+}
+
+#[tokio::test]
+async fn a_subscriber_whose_address_is_taken_once_rewritten_is_merged() {
+    let app = spawn_app().await;
+    let original = insert_subscriber(&app, "ursula@bücher.example", "confirmed", 30).await;
+    let duplicate = insert_subscriber(
+        &app,
+        "ursula@xn--bcher-kva.example",
+        "pending_confirmation",
+        1,
+    )
+    .await;
+    sqlx::query!(
+        "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, 'books')",
+        duplicate,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let report = normalise_stored_emails(&app.db_pool, EmailNormalisation::default())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        NormalisedEmails {
+            rewritten: 1,
+            merged: 1
+        }
+    );
+    let kept = sqlx::query!("SELECT id, email, status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].id, original);
+    assert_eq!(kept[0].email, "ursula@xn--bcher-kva.example");
+    assert_eq!(kept[0].status, "confirmed");
+    let tags = sqlx::query_scalar!(
+        "SELECT tag FROM subscriber_tags WHERE subscriber_id = $1",
+        original,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tags, ["books"]);
+}
+
+#[tokio::test]
+async fn local_parts_are_lowercased_once_asked_to() {
+    let app = spawn_app().await;
+    let original = insert_subscriber(&app, "Ursula@example.com", "confirmed", 30).await;
+    insert_subscriber(&app, "ursula@example.com", "pending_confirmation", 1).await;
+    insert_subscriber(&app, "Frank@example.com", "confirmed", 1).await;
+
+    let report = normalise_stored_emails(&app.db_pool, EmailNormalisation::default())
+        .await
+        .unwrap();
+    assert_eq!(report, NormalisedEmails::default());
+
+    let lowercase = EmailNormalisation {
+        lowercase_local_part: true,
+    };
+    let report = normalise_stored_emails(&app.db_pool, lowercase)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        NormalisedEmails {
+            rewritten: 2,
+            merged: 1
+        }
+    );
+    assert_eq!(
+        emails(&app).await,
+        ["frank@example.com", "ursula@example.com"]
+    );
+    let kept =
+        sqlx::query_scalar!("SELECT id FROM subscriptions WHERE email = 'ursula@example.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(kept, original);
+}
@@ -0,0 +1,144 @@
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn publish_issue(app: &TestApp, title: &str) -> (Uuid, String) {
+    let newsletter_request_body = serde_json::json!({
+        "title": title,
+        "content": "<p>Feed body</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+
+    let row = sqlx::query!(
+        "SELECT newsletter_issue_id, slug FROM newsletter_issues WHERE title = $1",
+        title
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the published issue.");
+    (row.newsletter_issue_id, row.slug)
+}
+
+#[tokio::test]
+async fn rss_feed_lists_published_issues() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (issue_id, slug) = publish_issue(&app, "First & foremost").await;
+
+    let response = app.get_feed("feed.rss", &[]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/rss+xml; charset=utf-8"
+    );
+    assert!(response.headers().get("ETag").is_some());
+    assert!(response.headers().get("Last-Modified").is_some());
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<title>First &amp; foremost</title>"));
+    assert!(body.contains(&format!(
+        "<guid isPermaLink=\"false\">urn:uuid:{issue_id}</guid>"
+    )));
+    assert!(body.contains(&format!(
+        "<link>http://127.0.0.1:8000/archive/{slug}</link>"
+    )));
+    assert!(body.contains("&lt;p&gt;Feed body&lt;/p&gt;"));
+}
+
+#[tokio::test]
+async fn atom_feed_lists_published_issues() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (issue_id, slug) = publish_issue(&app, "Atom issue").await;
+
+    let response = app.get_feed("feed.atom", &[]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "application/atom+xml; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<title>Atom issue</title>"));
+    assert!(body.contains(&format!("<id>urn:uuid:{issue_id}</id>")));
+    assert!(body.contains(&format!(
+        "<link href=\"http://127.0.0.1:8000/archive/{slug}\" rel=\"alternate\""
+    )));
+}
+
+#[tokio::test]
+async fn hidden_issues_are_not_in_the_feeds() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (issue_id, _) = publish_issue(&app, "Hidden issue").await;
+    app.post_archive_visibility(issue_id, true).await;
+
+    for feed in ["feed.rss", "feed.atom"] {
+        let body = app.get_feed(feed, &[]).await.text().await.unwrap();
+        assert!(!body.contains("Hidden issue"));
+    }
+}
+
+#[tokio::test]
+async fn feeds_honour_if_none_match() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "Cached issue").await;
+
+    for feed in ["feed.rss", "feed.atom"] {
+        let response = app.get_feed(feed, &[]).await;
+        let etag = response.headers().get("ETag").unwrap().to_str().unwrap();
+
+        let response = app.get_feed(feed, &[("If-None-Match", etag)]).await;
+        assert_eq!(response.status().as_u16(), 304);
+        assert!(response.text().await.unwrap().is_empty());
+
+        let response = app
+            .get_feed(feed, &[("If-None-Match", "\"stale-etag\"")])
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn empty_feeds_can_be_cached() {
+    let app = spawn_app().await;
+
+    for feed in ["feed.rss", "feed.atom"] {
+        let response = app.get_feed(feed, &[]).await;
+        let etag = response.headers().get("ETag").unwrap().to_str().unwrap();
+
+        let response = app.get_feed(feed, &[("If-None-Match", etag)]).await;
+        assert_eq!(response.status().as_u16(), 304, "{feed}");
+    }
+}
+
+#[tokio::test]
+async fn feeds_honour_if_modified_since() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issue(&app, "Dated issue").await;
+
+    let response = app.get_feed("feed.rss", &[]).await;
+    let last_modified = response
+        .headers()
+        .get("Last-Modified")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .get_feed("feed.rss", &[("If-Modified-Since", &last_modified)])
+        .await;
+    assert_eq!(response.status().as_u16(), 304);
+
+    let response = app
+        .get_feed(
+            "feed.rss",
+            &[("If-Modified-Since", "Mon, 01 Jan 2001 00:00:00 GMT")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
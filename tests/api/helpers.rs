@@ -1,15 +1,18 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use chrono::{Duration, Utc};
 use once_cell::sync::Lazy;
 use serde_json::Value;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
-use zerotoprod::configuration::{get_configuration, DatabaseSettings};
+use zerotoprod::configuration::{get_configuration, DatabaseSettings, Settings};
+use zerotoprod::domain::{ApplicationBaseUrl, FormToken};
 use zerotoprod::email_client::EmailClient;
 use zerotoprod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
 use zerotoprod::startup::{get_connection_pool, Application};
 use zerotoprod::telemetry::init_subscriber;
+use zerotoprod::HmacSecret;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
@@ -41,12 +44,21 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub application_base_url: ApplicationBaseUrl,
+    pub hmac_secret: HmacSecret,
 }
 
 impl TestApp {
+    /// Posts the subscribe form as a person would, with a form token issued a minute ago.
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        let form_token = FormToken::issue(Utc::now() - Duration::minutes(1), &self.hmac_secret);
+        self.post_subscriptions_raw(format!("{body}&form_token={form_token}"))
+            .await
+    }
+
+    pub async fn post_subscriptions_raw(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -74,7 +86,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -89,13 +101,134 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_issues_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_issues_html_with(&self, query: &str) -> String {
+        self.get_admin_page_html(&format!("/admin/issues?{query}"))
+            .await
+    }
+
+    pub async fn get_admin_page_html(&self, path: &str) -> String {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_issue_html(&self, newsletter_issue_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/issue/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_archive_visibility(
+        &self,
+        newsletter_issue_id: Uuid,
+        hidden: bool,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issue/{}/archive",
+                &self.address, newsletter_issue_id
+            ))
+            .form(&serde_json::json!({ "hidden": hidden }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_archive_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/archive", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_archive_issue(&self, slug: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/archive/{}", &self.address, slug))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_feed(&self, feed: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}/{}", &self.address, feed));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_subscribers_import(
+        &self,
+        status: &str,
+        send_confirmation: bool,
+        csv: &str,
+    ) -> reqwest::Response {
+        let mut form = reqwest::multipart::Form::new().text("status", status.to_string());
+        if send_confirmation {
+            form = form.text("send_confirmation", "on");
+        }
+        let file = reqwest::multipart::Part::text(csv.to_string())
+            .file_name("subscribers.csv")
+            .mime_str("text/csv")
+            .unwrap();
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form.part("file", file))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Puts a subscriber inserted straight into `subscriptions` on the default list,
+    /// with the same status.
+    pub async fn add_to_default_list(&self, email: &str) {
+        sqlx::query!(
+            "INSERT INTO list_memberships (list_id, subscriber_id, status)
+            SELECT l.list_id, s.id, s.status
+            FROM lists l, subscriptions s
+            WHERE l.is_default AND s.email = $1",
+            email,
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to add the subscriber to the default list.");
+    }
+
     #[allow(dead_code)]
     pub async fn test_user(&self) -> (String, String) {
         let row = sqlx::query!("SELECT username, password_hash FROM users LIMIT 1",)
@@ -110,7 +243,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
@@ -119,7 +252,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -130,7 +263,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -142,7 +275,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -153,7 +286,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -166,18 +299,34 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    /// Waits until the email server has received `count` requests, as some emails are sent
+    /// in the background after the response.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("The email server did not receive {count} requests.");
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.application_base_url,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -186,6 +335,11 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the application with settings adjusted by `customise`.
+pub async fn spawn_app_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -195,6 +349,8 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email.endpoint = email_server.uri();
+        c.rate_limits.key_prefix = format!("test:{}", c.database.database_name);
+        customise(&mut c);
         c
     };
     configure_database(&configuration.database).await;
@@ -220,6 +376,8 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client,
         email_client: configuration.email.client().unwrap(),
+        application_base_url: configuration.application.base_url,
+        hmac_secret: HmacSecret(configuration.application.hmac_secret),
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
@@ -262,7 +420,11 @@ impl TestUser {
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
+        self.store_with_role(pool, "owner").await
+    }
+
+    pub async fn store_with_role(&self, pool: &PgPool, role: &str) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
@@ -274,10 +436,11 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role) VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            role,
         )
         .execute(pool)
         .await
@@ -0,0 +1,115 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn publish_issues(app: &TestApp, titles: &[&str]) {
+    for title in titles {
+        let newsletter_request_body = serde_json::json!({
+            "title": title,
+            "content": "Newsletter body as plain text",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        });
+        app.post_publish_newsletter(&newsletter_request_body).await;
+    }
+}
+
+fn link_labelled(html_page: &str, label: &str) -> Option<String> {
+    let end = html_page.find(&format!("\">{label}"))?;
+    let start = html_page[..end].rfind("href=\"")? + "href=\"".len();
+    Some(html_page[start..end].replace("&amp;", "&"))
+}
+
+#[tokio::test]
+async fn issues_are_paginated() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issues(
+        &app,
+        &["Issue 1", "Issue 2", "Issue 3", "Issue 4", "Issue 5"],
+    )
+    .await;
+
+    let first_page = app.get_issues_html_with("limit=2").await;
+    assert!(first_page.contains("Issue 5") && first_page.contains("Issue 4"));
+    assert!(!first_page.contains("Issue 3"));
+    assert!(link_labelled(&first_page, "&larr; Previous").is_none());
+
+    let next = link_labelled(&first_page, "Next &rarr;").unwrap();
+    let second_page = app.get_admin_page_html(&next).await;
+    assert!(second_page.contains("Issue 3") && second_page.contains("Issue 2"));
+    assert!(!second_page.contains("Issue 4") && !second_page.contains("Issue 1"));
+
+    let next = link_labelled(&second_page, "Next &rarr;").unwrap();
+    let last_page = app.get_admin_page_html(&next).await;
+    assert!(last_page.contains("Issue 1"));
+    assert!(link_labelled(&last_page, "Next &rarr;").is_none());
+
+    let prev = link_labelled(&last_page, "&larr; Previous").unwrap();
+    let second_page_again = app.get_admin_page_html(&prev).await;
+    assert!(second_page_again.contains("Issue 3") && second_page_again.contains("Issue 2"));
+    assert!(!second_page_again.contains("Issue 1"));
+}
+
+#[tokio::test]
+async fn issues_can_be_searched_by_title() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issues(
+        &app,
+        &["Rust weekly digest", "Gardening tips", "Rusty tools"],
+    )
+    .await;
+
+    let html_page = app.get_issues_html_with("q=rust").await;
+
+    assert!(html_page.contains("Rust weekly digest"));
+    assert!(!html_page.contains("Gardening tips"));
+}
+
+#[tokio::test]
+async fn issues_can_be_sorted_by_title() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    publish_issues(&app, &["Bravo", "Charlie", "Alpha"]).await;
+
+    let html_page = app.get_issues_html_with("sort=title&order=asc").await;
+
+    let alpha = html_page.find("Alpha").unwrap();
+    let bravo = html_page.find("Bravo").unwrap();
+    let charlie = html_page.find("Charlie").unwrap();
+    assert!(alpha < bravo && bravo < charlie);
+}
+
+#[tokio::test]
+async fn invalid_cursors_are_rejected_with_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/issues?after=garbage", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn cursors_with_a_malformed_key_are_rejected_with_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let cursor = URL_SAFE_NO_PAD.encode(format!("{}:not a timestamp", Uuid::new_v4()));
+
+    for path in ["/admin/issues", "/admin/subscribers"] {
+        let response = app
+            .api_client
+            .get(format!("{}{path}?before={cursor}", &app.address))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 400, "{path}");
+    }
+}
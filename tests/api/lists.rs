@@ -0,0 +1,234 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn create_list(app: &TestApp, name: &str, slug: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/lists", &app.address))
+        .form(&serde_json::json!({ "name": name, "slug": slug }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn list_id(app: &TestApp, slug: &str) -> Uuid {
+    sqlx::query_scalar!("SELECT list_id FROM lists WHERE slug = $1", slug)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn membership_status(app: &TestApp, email: &str, slug: &str) -> Option<String> {
+    sqlx::query_scalar!(
+        "SELECT m.status FROM list_memberships m
+        JOIN lists l USING (list_id)
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE s.email = $1 AND l.slug = $2",
+        email,
+        slug,
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+/// Subscribes through the public form and returns the emailed confirmation link.
+async fn subscribe(app: &TestApp, email: &str, list: &str) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!(
+        "name=Ursula&email={}&list={list}",
+        email.replace('@', "%40")
+    );
+    let response = app.post_subscriptions(body).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request)
+}
+
+async fn subscribe_and_confirm(app: &TestApp, email: &str, list: &str) {
+    let confirmation_link = subscribe(app, email, list).await;
+    reqwest::get(confirmation_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn publish_to(app: &TestApp, list_ids: &[Uuid]) -> reqwest::Response {
+    let mut body = format!(
+        "title=Newsletter+title&content=Body&idempotency_key={}",
+        Uuid::new_v4()
+    );
+    for list_id in list_ids {
+        body.push_str(&format!("&list_ids={list_id}"));
+    }
+    app.api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_lists() {
+    let app = spawn_app().await;
+
+    let response = create_list(&app, "Releases", "releases").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_admin_can_create_a_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = create_list(&app, "Release notes", "releases").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_admin_page_html("/admin/lists").await;
+    assert!(html_page.contains("Created the Release notes list."));
+    assert!(html_page.contains("subscriptions?list=releases"));
+}
+
+#[tokio::test]
+async fn list_slugs_must_be_valid_and_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    create_list(&app, "Release notes", "releases").await;
+    let response = create_list(&app, "More releases", "releases").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_admin_page_html("/admin/lists").await;
+    assert!(html_page.contains("A list with the slug releases already exists."));
+
+    create_list(&app, "Bad", "Not A Slug").await;
+    let n_lists = sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM lists"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_lists, 2);
+}
+
+#[tokio::test]
+async fn each_list_has_its_own_subscribe_form() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Release notes", "releases").await;
+
+    let html_page = app
+        .get_admin_page_html("/subscriptions?list=releases")
+        .await;
+    assert!(html_page.contains("Subscribe to Release notes"));
+
+    let response = app
+        .api_client
+        .get(format!("{}/subscriptions?list=missing", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=ursula%40example.com&list=missing".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn confirming_a_second_list_leaves_the_first_membership_alone() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Release notes", "releases").await;
+
+    subscribe(&app, "ursula@example.com", "newsletter").await;
+    subscribe_and_confirm(&app, "ursula@example.com", "releases").await;
+
+    assert_eq!(
+        membership_status(&app, "ursula@example.com", "releases")
+            .await
+            .as_deref(),
+        Some("confirmed")
+    );
+    assert_eq!(
+        membership_status(&app, "ursula@example.com", "newsletter")
+            .await
+            .as_deref(),
+        Some("pending_confirmation")
+    );
+}
+
+#[tokio::test]
+async fn issues_only_reach_confirmed_members_of_the_chosen_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Release notes", "releases").await;
+    subscribe_and_confirm(&app, "newsletter-only@example.com", "newsletter").await;
+    subscribe_and_confirm(&app, "releases-only@example.com", "releases").await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let releases = list_id(&app, "releases").await;
+    let response = publish_to(&app, &[releases]).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    app.dispatch_all_pending_emails().await;
+
+    let recipients = sqlx::query_scalar!("SELECT subscriber_email FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(recipients, vec!["releases-only@example.com".to_string()]);
+}
+
+#[tokio::test]
+async fn members_of_several_target_lists_get_one_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Release notes", "releases").await;
+    subscribe_and_confirm(&app, "ursula@example.com", "newsletter").await;
+    subscribe_and_confirm(&app, "ursula@example.com", "releases").await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let lists = [
+        list_id(&app, "newsletter").await,
+        list_id(&app, "releases").await,
+    ];
+    let response = publish_to(&app, &lists).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    app.dispatch_all_pending_emails().await;
+}
@@ -1,4 +1,6 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use zerotoprod::configuration::PasswordHashingSettings;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -28,3 +30,55 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+async fn stored_password_hash(app: &TestApp) -> String {
+    sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn outdated_password_hashes_are_replaced_on_login() {
+    let app = spawn_app_with(|c| {
+        c.password_hashing = PasswordHashingSettings {
+            memory_kib: 8192,
+            iterations: 3,
+            parallelism: 1,
+        }
+    })
+    .await;
+    assert!(stored_password_hash(&app)
+        .await
+        .starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+
+    app.test_user.login(&app).await;
+    let rehashed = stored_password_hash(&app).await;
+    assert!(rehashed.starts_with("$argon2id$v=19$m=8192,t=3,p=1$"));
+
+    // Once up to date the hash is left alone, and still matches the password
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    assert_eq!(stored_password_hash(&app).await, rehashed);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn failed_logins_do_not_rehash_the_password() {
+    let app = spawn_app().await;
+    let before = stored_password_hash(&app).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    assert_eq!(stored_password_hash(&app).await, before);
+}
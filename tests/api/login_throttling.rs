@@ -0,0 +1,151 @@
+use zerotoprod::configuration::Settings;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app_with, TestApp, TestUser};
+
+/// Every failure after the first two blocks logins for an hour.
+fn strict(c: &mut Settings) {
+    c.login_throttling.free_attempts = 2;
+    c.login_throttling.base_delay_seconds = 3600;
+    c.login_throttling.max_failures = 5;
+    c.login_throttling.lockout_minutes = 60;
+    c.rate_limits.trusted_proxies = vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+}
+
+async fn post_login_from(
+    app: &TestApp,
+    username: &str,
+    password: &str,
+    forwarded_for: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .form(&[("username", username), ("password", password)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn assert_login_fails(app: &TestApp, response: reqwest::Response) {
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Authentication failed"));
+}
+
+async fn is_blocked(app: &TestApp, key: &str) -> bool {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM login_failures WHERE key = $1 AND blocked_until > now()
+        ) as "blocked!""#,
+        key,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn after_repeated_failures_even_the_right_password_is_refused() {
+    let app = spawn_app_with(strict).await;
+    let username = app.test_user.username.clone();
+
+    for ip in ["203.0.113.1", "203.0.113.2", "203.0.113.3"] {
+        let response = post_login_from(&app, &username, "wrong-password", ip).await;
+        assert_login_fails(&app, response).await;
+    }
+
+    let response = post_login_from(&app, &username, &app.test_user.password, "203.0.113.4").await;
+    assert_login_fails(&app, response).await;
+    assert!(is_blocked(&app, &format!("username:{username}")).await);
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_throttled_like_existing_ones() {
+    let app = spawn_app_with(strict).await;
+
+    for ip in ["203.0.113.1", "203.0.113.2", "203.0.113.3", "203.0.113.4"] {
+        let response = post_login_from(&app, "nobody", "wrong-password", ip).await;
+        assert_login_fails(&app, response).await;
+    }
+
+    assert!(is_blocked(&app, "username:nobody").await);
+}
+
+#[tokio::test]
+async fn failures_from_one_address_are_counted_across_usernames() {
+    let app = spawn_app_with(strict).await;
+
+    for username in ["alice", "bob", "carol"] {
+        post_login_from(&app, username, "wrong-password", "203.0.113.9").await;
+    }
+
+    let response = post_login_from(
+        &app,
+        &app.test_user.username,
+        &app.test_user.password,
+        "203.0.113.9",
+    )
+    .await;
+    assert_login_fails(&app, response).await;
+    assert!(is_blocked(&app, "ip:203.0.113.9").await);
+
+    let response = post_login_from(
+        &app,
+        &app.test_user.username,
+        &app.test_user.password,
+        "198.51.100.1",
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn logging_in_forgets_earlier_failures() {
+    let app = spawn_app_with(strict).await;
+    let username = app.test_user.username.clone();
+
+    post_login_from(&app, &username, "wrong-password", "203.0.113.1").await;
+    post_login_from(&app, &username, "wrong-password", "203.0.113.2").await;
+    let response = post_login_from(&app, &username, &app.test_user.password, "203.0.113.3").await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let n_rows = sqlx::query_scalar!(
+        r#"SELECT count(*) as "count!" FROM login_failures WHERE key = $1"#,
+        format!("username:{username}"),
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_rows, 0);
+}
+
+#[tokio::test]
+async fn an_admin_can_unlock_a_locked_out_username() {
+    let app = spawn_app_with(strict).await;
+    let username = app.test_user.username.clone();
+    for ip in ["203.0.113.1", "203.0.113.2", "203.0.113.3"] {
+        post_login_from(&app, &username, "wrong-password", ip).await;
+    }
+
+    let admin = TestUser::generate();
+    admin.store(&app.db_pool).await;
+    admin.login(&app).await;
+    let html_page = app.get_admin_page_html("/admin/lockouts").await;
+    let key = format!("username:{username}");
+    assert!(html_page.contains(&key));
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/lockouts/unlock", &app.address))
+        .form(&[("key", &key)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/lockouts");
+    let html_page = app.get_admin_page_html("/admin/lockouts").await;
+    assert!(html_page.contains(&format!("{key} can log in again.")));
+    assert!(!is_blocked(&app, &key).await);
+
+    let response = post_login_from(&app, &username, &app.test_user.password, "203.0.113.4").await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,28 @@
 mod admin_dashboard;
+mod archive;
+mod blocklist;
+mod bot_protection;
 mod change_password;
+mod email_normalisation;
+mod feeds;
 mod health_check;
 mod helpers;
+mod issues;
+mod lists;
 mod login;
+mod login_throttling;
 mod newsletter;
+mod password_reset;
+mod rate_limit;
+mod segments;
+mod sessions;
+mod subscriber_actions;
+mod subscriber_data;
+mod subscriber_preferences;
+mod subscribers;
+mod subscribers_export;
+mod subscribers_import;
 mod subscriptions;
 mod subscriptions_confirm;
+mod two_factor;
+mod users;
@@ -37,7 +37,7 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
 async fn create_unconfirmed_subscriber(app: &TestApp) -> reqwest::Url {
     let name: String = Name().fake();
     let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(&serde_json::json!({
+    let body = serde_urlencoded::to_string(serde_json::json!({
         "name": name,
         "email": email
     }))
@@ -51,7 +51,7 @@ async fn create_unconfirmed_subscriber(app: &TestApp) -> reqwest::Url {
         .mount_as_scoped(&app.email_server)
         .await;
 
-    app.post_subscriptions(body.into())
+    app.post_subscriptions(body)
         .await
         .error_for_status()
         .unwrap();
@@ -63,7 +63,7 @@ async fn create_unconfirmed_subscriber(app: &TestApp) -> reqwest::Url {
         .unwrap()
         .pop()
         .unwrap();
-    app.get_confirmation_links(&email_request)
+    app.get_confirmation_links(email_request)
 }
 
 async fn create_confirmed_subscriber(app: &TestApp) {
@@ -192,6 +192,124 @@ async fn concurrent_form_submission_is_handled_gracefully() {
     app.dispatch_all_pending_emails().await;
 }
 
+async fn latest_issue_id(app: &TestApp) -> uuid::Uuid {
+    sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues ORDER BY published_at DESC LIMIT 1"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch newsletter issue.")
+    .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn issue_page_shows_delivery_statistics() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    let issue_id = latest_issue_id(&app).await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(html_page.contains("Sending is in progress"));
+
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issue_html(issue_id).await;
+    assert!(!html_page.contains("Sending is in progress"));
+    let stats = sqlx::query!(
+        "SELECT outcome FROM issue_delivery_log WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(stats.len(), 2);
+    assert!(stats.iter().all(|r| r.outcome == "sent"));
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_before_being_marked_as_failed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(4)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": "Newsletter body as plain text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    let issue_id = latest_issue_id(&app).await;
+
+    app.dispatch_all_pending_emails().await;
+    let task = sqlx::query!("SELECT n_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The failed delivery should still be queued.");
+    assert_eq!(task.n_retries, 1);
+
+    for _ in 0..3 {
+        sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let log = sqlx::query!(
+        "SELECT outcome, n_retries FROM issue_delivery_log WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(log.outcome, "failed");
+    assert_eq!(log.n_retries, 3);
+}
+
+#[tokio::test]
+async fn issues_are_listed_newest_first() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for title in ["First issue", "Second issue", "Third issue"] {
+        let newsletter_request_body = serde_json::json!({
+            "title": title,
+            "content": "Newsletter body as plain text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        });
+        app.post_publish_newsletter(&newsletter_request_body).await;
+    }
+
+    let html_page = app.get_issues_html().await;
+    let first = html_page.find("First issue").unwrap();
+    let second = html_page.find("Second issue").unwrap();
+    let third = html_page.find("Third issue").unwrap();
+    assert!(third < second && second < first);
+}
+
 // fn when_sending_an_email() -> MockBuilder {
 //     Mock::given(path("/v3/smtp/email")).and(method("POST"))
 // }
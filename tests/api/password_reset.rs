@@ -0,0 +1,304 @@
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn set_email(app: &TestApp, email: &str) {
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE user_id = $2",
+        email,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn post_forgot(app: &TestApp, username: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login/forgot", &app.address))
+        .form(&[("username", username)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_reset(app: &TestApp, token: &str, password: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login/reset", &app.address))
+        .form(&[
+            ("token", token),
+            ("new_password", password),
+            ("new_password_check", password),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Requests a reset link for the test user and returns its token.
+async fn reset_token(app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let sent = app.email_server.received_requests().await.unwrap().len();
+    post_forgot(app, &app.test_user.username).await;
+    let requests = app.wait_for_emails(sent + 1).await;
+    let email_request = requests.last().unwrap();
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["subject"], "Reset your password");
+    assert_eq!(body["to"][0]["email"], "admin@example.com");
+    let link = app.get_confirmation_links(email_request);
+    assert_eq!(link.path(), "/login/reset");
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn login_with(app: &TestApp, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn the_forgot_password_form_does_not_reveal_which_accounts_exist() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let mut pages = Vec::new();
+    for username in [app.test_user.username.clone(), Uuid::new_v4().to_string()] {
+        let response = post_forgot(&app, &username).await;
+        assert_is_redirect_to(&response, "/login");
+        pages.push(app.get_login_html().await);
+    }
+
+    assert_eq!(pages[0], pages[1]);
+    assert!(pages[0].contains("If this account has an email address, we have sent it a link"));
+}
+
+#[tokio::test]
+async fn email_failures_do_not_reveal_which_accounts_exist() {
+    let app = spawn_app().await;
+    set_email(&app, "admin@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let mut pages = Vec::new();
+    for username in [app.test_user.username.clone(), Uuid::new_v4().to_string()] {
+        let response = post_forgot(&app, &username).await;
+        assert_is_redirect_to(&response, "/login");
+        pages.push(app.get_login_html().await);
+    }
+
+    assert_eq!(pages[0], pages[1]);
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn a_reset_link_sets_a_new_password() {
+    let app = spawn_app().await;
+    set_email(&app, "admin@example.com").await;
+    let token = reset_token(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login/reset?token={token}", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_password = Uuid::new_v4().to_string();
+    let response = post_reset(&app, &token, &new_password).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your password has been reset. Please log in."));
+
+    let response = login_with(&app, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = login_with(&app, &new_password).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    set_email(&app, "admin@example.com").await;
+    let token = reset_token(&app).await;
+
+    post_reset(&app, &token, &Uuid::new_v4().to_string()).await;
+    let response = post_reset(&app, &token, &Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_weak_new_password_leaves_the_reset_link_usable() {
+    let app = spawn_app().await;
+    set_email(&app, "admin@example.com").await;
+    let token = reset_token(&app).await;
+
+    let response = post_reset(&app, &token, "letmein").await;
+    assert_is_redirect_to(&response, &format!("/login/reset?token={token}"));
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/reset?token={token}", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Passwords must be at least 12 characters long."));
+
+    let response = post_reset(&app, &token, &Uuid::new_v4().to_string()).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn expired_and_unknown_reset_links_are_rejected() {
+    let app = spawn_app().await;
+    set_email(&app, "admin@example.com").await;
+    let token = reset_token(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    for token in [token.as_str(), "not-a-token"] {
+        let response = app
+            .api_client
+            .get(format!("{}/login/reset?token={token}", &app.address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+        let response = post_reset(&app, token, &Uuid::new_v4().to_string()).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = login_with(&app, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn tokens_are_checked_before_the_new_passwords_are_compared() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/login/reset", &app.address))
+        .form(&[
+            ("token", "not-a-token\r\nSet-Cookie: x=y"),
+            ("new_password", "one-new-password"),
+            ("new_password_check", "another-new-password"),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn resetting_the_password_ends_existing_sessions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    set_email(&app, "admin@example.com").await;
+    let token = reset_token(&app).await;
+
+    post_reset(&app, &token, &Uuid::new_v4().to_string()).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_admin_can_set_the_address_reset_links_are_sent_to() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/security/email", &app.address))
+        .form(&[
+            ("email", "admin@example.com"),
+            ("current_password", &app.test_user.password),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/security");
+
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("Password reset links will be sent to admin@example.com."));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+    reset_token(&app).await;
+}
+
+async fn post_account_email(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/security/email", &app.address))
+        .form(&[("email", email), ("current_password", password)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn changing_the_account_email_needs_the_current_password() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    set_email(&app, "admin@example.com").await;
+
+    let response = post_account_email(&app, "attacker@example.com", "wrong-password").await;
+    assert_is_redirect_to(&response, "/admin/security");
+
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("The current password is incorrect."));
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+}
+
+#[tokio::test]
+async fn the_previous_account_email_is_told_about_a_change() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    set_email(&app, "admin@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_account_email(&app, "new-admin@example.com", &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/admin/security");
+
+    let requests = app.wait_for_emails(1).await;
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["to"][0]["email"], "admin@example.com");
+    assert_eq!(body["subject"], "Your account's email address was changed");
+    assert!(!body["htmlContent"]
+        .as_str()
+        .unwrap()
+        .contains("new-admin@example.com"));
+}
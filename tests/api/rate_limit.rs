@@ -0,0 +1,134 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zerotoprod::configuration::Quota;
+
+use crate::helpers::{spawn_app_with, TestApp};
+
+fn quota(requests: u32) -> Option<Quota> {
+    Some(Quota {
+        requests,
+        window_seconds: 600,
+    })
+}
+
+async fn post_wrong_login(app: &TestApp, forwarded_for: Option<&str>) -> reqwest::Response {
+    let mut request = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&[
+            ("username", "random-username"),
+            ("password", "random-password"),
+        ]);
+    if let Some(ip) = forwarded_for {
+        request = request.header("X-Forwarded-For", ip);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+fn assert_is_rate_limited(response: &reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=600).contains(&retry_after));
+}
+
+#[tokio::test]
+async fn login_attempts_beyond_the_limit_get_a_429() {
+    let app = spawn_app_with(|c| c.rate_limits.login.per_ip = quota(2)).await;
+
+    for _ in 0..2 {
+        let response = post_wrong_login(&app, None).await;
+        assert_eq!(response.status().as_u16(), 303);
+    }
+    let response = post_wrong_login(&app, None).await;
+    assert_is_rate_limited(&response);
+
+    // Only submissions count, the login page stays reachable
+    let response = app
+        .api_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn clients_behind_a_trusted_proxy_are_limited_separately() {
+    let app = spawn_app_with(|c| {
+        c.rate_limits.trusted_proxies = vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        c.rate_limits.login.per_ip = quota(1);
+    })
+    .await;
+
+    let response = post_wrong_login(&app, Some("198.51.100.1")).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let response = post_wrong_login(&app, Some("198.51.100.1")).await;
+    assert_is_rate_limited(&response);
+
+    let response = post_wrong_login(&app, Some("198.51.100.2")).await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn forwarded_for_is_ignored_without_a_trusted_proxy() {
+    let app = spawn_app_with(|c| c.rate_limits.login.per_ip = quota(1)).await;
+
+    post_wrong_login(&app, Some("198.51.100.1")).await;
+    let response = post_wrong_login(&app, Some("198.51.100.2")).await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn subscriptions_are_limited_per_email() {
+    let app = spawn_app_with(|c| {
+        c.rate_limits.subscriptions.per_ip = quota(100);
+        c.rate_limits.subscriptions.per_email = quota(2);
+    })
+    .await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["ursula%40example.com", "Ursula%40Example.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={email}"))
+            .await;
+        assert_eq!(response.status().as_u16(), 303);
+    }
+    let response = app
+        .post_subscriptions("name=le%20guin&email=URSULA%40example.com".into())
+        .await;
+    assert_is_rate_limited(&response);
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=someone.else%40example.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn subscriptions_are_limited_per_ip() {
+    let app = spawn_app_with(|c| c.rate_limits.subscriptions.per_ip = quota(1)).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+    let response = app
+        .post_subscriptions("name=le%20guin&email=someone.else%40example.com".into())
+        .await;
+
+    assert_is_rate_limited(&response);
+}
@@ -0,0 +1,271 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str, at: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    let subscribed_at: DateTime<Utc> = at.parse().unwrap();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', $3, 'confirmed')",
+        id,
+        email,
+        subscribed_at,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list(email).await;
+    id
+}
+
+async fn post_attributes(
+    app: &TestApp,
+    subscriber_id: Uuid,
+    tags: &str,
+    attributes: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscriber/{subscriber_id}/attributes",
+            &app.address
+        ))
+        .form(&[("tags", tags), ("attributes", attributes)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn create_segment(app: &TestApp, form: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/segments", &app.address))
+        .form(form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn segment_id(app: &TestApp, name: &str) -> Uuid {
+    sqlx::query_scalar!("SELECT segment_id FROM segments WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn recipients(app: &TestApp, query: &str) -> i64 {
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletters/recipients?{query}",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    body["recipients"].as_i64().unwrap()
+}
+
+async fn publish_to_segment(app: &TestApp, segment_id: &str) -> reqwest::Response {
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "content": "Newsletter body as plain text",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "segment_id": segment_id,
+    }))
+    .await
+}
+
+/// Three confirmed subscribers: a French VIP, an English VIP who joined later, and
+/// somebody without tags or attributes.
+async fn seed_subscribers(app: &TestApp) {
+    let french =
+        insert_confirmed_subscriber(app, "french@example.com", "2024-01-05T10:00:00Z").await;
+    let english =
+        insert_confirmed_subscriber(app, "english@example.com", "2024-02-05T10:00:00Z").await;
+    insert_confirmed_subscriber(app, "plain@example.com", "2024-02-06T10:00:00Z").await;
+    post_attributes(app, french, "vip", "locale=fr").await;
+    post_attributes(app, english, "VIP, beta", "locale=en").await;
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_segments() {
+    let app = spawn_app().await;
+
+    let response = create_segment(&app, &[("name", "VIPs"), ("tag", "vip")]).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_admin_can_tag_a_subscriber() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id =
+        insert_confirmed_subscriber(&app, "ursula@example.com", "2024-01-05T10:00:00Z").await;
+
+    let response = post_attributes(&app, subscriber_id, "vip, Beta", "locale = fr").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscriber/{subscriber_id}"));
+
+    let html_page = app
+        .get_admin_page_html(&format!("/admin/subscriber/{subscriber_id}"))
+        .await;
+    assert!(html_page.contains("The tags and attributes have been saved."));
+    assert!(html_page.contains("beta, vip"));
+    assert!(html_page.contains("locale=fr"));
+
+    post_attributes(&app, subscriber_id, "vip!", "").await;
+    let html_page = app
+        .get_admin_page_html(&format!("/admin/subscriber/{subscriber_id}"))
+        .await;
+    assert!(html_page.contains("vip! is not a valid tag."));
+    assert!(html_page.contains("beta, vip"));
+}
+
+#[tokio::test]
+async fn an_admin_can_create_a_segment() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = create_segment(
+        &app,
+        &[
+            ("name", "French VIPs"),
+            ("subscribed_after", "2024-01-01"),
+            ("subscribed_before", ""),
+            ("tag", "vip"),
+            ("attribute_key", "locale"),
+            ("attribute_value", "fr"),
+        ],
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/segments");
+
+    let html_page = app.get_admin_page_html("/admin/segments").await;
+    assert!(html_page.contains("Created the French VIPs segment."));
+    assert!(html_page.contains("subscribed on or after 2024-01-01 and tagged vip and locale is fr"));
+}
+
+#[tokio::test]
+async fn segments_without_valid_conditions_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for (form, message) in [
+        (
+            vec![("name", "Everybody"), ("tag", "")],
+            "A segment needs at least one condition.",
+        ),
+        (
+            vec![("name", "Half"), ("attribute_key", "locale")],
+            "An attribute condition needs both a name and a value.",
+        ),
+        (
+            vec![
+                ("name", "Never"),
+                ("subscribed_after", "2024-02-01"),
+                ("subscribed_before", "2024-01-01"),
+            ],
+            "Nobody can subscribe both",
+        ),
+    ] {
+        let response = create_segment(&app, &form).await;
+        assert_is_redirect_to(&response, "/admin/segments");
+        let html_page = app.get_admin_page_html("/admin/segments").await;
+        assert!(html_page.contains(message), "{message}");
+    }
+
+    create_segment(&app, &[("name", "VIPs"), ("tag", "vip")]).await;
+    create_segment(&app, &[("name", "VIPs"), ("tag", "beta")]).await;
+    let html_page = app.get_admin_page_html("/admin/segments").await;
+    assert!(html_page.contains("A segment named VIPs already exists."));
+}
+
+#[tokio::test]
+async fn the_newsletter_form_previews_the_recipient_count() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    seed_subscribers(&app).await;
+    create_segment(&app, &[("name", "VIPs"), ("tag", "vip")]).await;
+    create_segment(
+        &app,
+        &[("name", "February"), ("subscribed_after", "2024-02-01")],
+    )
+    .await;
+    create_segment(
+        &app,
+        &[
+            ("name", "French"),
+            ("attribute_key", "locale"),
+            ("attribute_value", "fr"),
+        ],
+    )
+    .await;
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(r#"<strong id="recipients">3</strong>"#));
+    assert!(html_page.contains("VIPs (tagged vip)"));
+
+    assert_eq!(recipients(&app, "").await, 3);
+    let vips = segment_id(&app, "VIPs").await;
+    assert_eq!(recipients(&app, &format!("segment_id={vips}")).await, 2);
+    let february = segment_id(&app, "February").await;
+    assert_eq!(recipients(&app, &format!("segment_id={february}")).await, 2);
+    let french = segment_id(&app, "French").await;
+    assert_eq!(recipients(&app, &format!("segment_id={french}")).await, 1);
+}
+
+#[tokio::test]
+async fn segment_targeted_issues_only_reach_matching_subscribers() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    seed_subscribers(&app).await;
+    create_segment(
+        &app,
+        &[
+            ("name", "Recent VIPs"),
+            ("subscribed_after", "2024-02-01"),
+            ("tag", "vip"),
+        ],
+    )
+    .await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let segment_id = segment_id(&app, "Recent VIPs").await;
+    let response = publish_to_segment(&app, &segment_id.to_string()).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    app.dispatch_all_pending_emails().await;
+
+    let recipients = sqlx::query_scalar!("SELECT subscriber_email FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(recipients, vec!["english@example.com".to_string()]);
+    let stored_segment = sqlx::query_scalar!("SELECT segment_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored_segment, Some(segment_id));
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_segment_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = publish_to_segment(&app, &Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,163 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+
+/// A browser of its own, identified by `user_agent`, logged in as `user`.
+async fn logged_in_client(app: &TestApp, user: &TestUser, user_agent: &str) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent(user_agent)
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .form(&[
+            ("username", user.username.as_str()),
+            ("password", user.password.as_str()),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn get_dashboard(app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn session_id(app: &TestApp, user_agent: &str) -> Uuid {
+    sqlx::query_scalar!(
+        "SELECT session_id FROM user_sessions WHERE user_agent = $1",
+        user_agent
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn post_sessions(app: &TestApp, path: &str, form: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/security/sessions{path}", &app.address))
+        .form(form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn the_sessions_of_the_user_are_listed() {
+    let app = spawn_app().await;
+    logged_in_client(&app, &app.test_user, "Phone Browser/1.0").await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    logged_in_client(&app, &other_user, "Someone Else/1.0").await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_page_html("/admin/security/sessions").await;
+
+    assert!(html_page.contains("Phone Browser/1.0"));
+    assert!(html_page.contains("127.0.0.1"));
+    assert!(html_page.contains("This session"));
+    assert!(html_page.contains("Log out everywhere else"));
+    assert!(!html_page.contains("Someone Else/1.0"));
+}
+
+#[tokio::test]
+async fn a_session_can_be_logged_out_from_another_one() {
+    let app = spawn_app().await;
+    let phone = logged_in_client(&app, &app.test_user, "Phone Browser/1.0").await;
+    app.test_user.login(&app).await;
+
+    let session_id = session_id(&app, "Phone Browser/1.0").await.to_string();
+    let response = post_sessions(&app, "/revoke", &[("session_id", &session_id)]).await;
+    assert_is_redirect_to(&response, "/admin/security/sessions");
+
+    let html_page = app.get_admin_page_html("/admin/security/sessions").await;
+    assert!(html_page.contains("The session has been logged out."));
+    assert!(!html_page.contains("Phone Browser/1.0"));
+    assert_is_redirect_to(&get_dashboard(&app, &phone).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn users_cannot_log_out_the_sessions_of_others() {
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let other_client = logged_in_client(&app, &other_user, "Someone Else/1.0").await;
+    app.test_user.login(&app).await;
+
+    let session_id = session_id(&app, "Someone Else/1.0").await.to_string();
+    let response = post_sessions(&app, "/revoke", &[("session_id", &session_id)]).await;
+    assert_is_redirect_to(&response, "/admin/security/sessions");
+
+    let html_page = app.get_admin_page_html("/admin/security/sessions").await;
+    assert!(html_page.contains("This session has already ended."));
+    assert_eq!(
+        get_dashboard(&app, &other_client).await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn logging_out_everywhere_else_keeps_the_current_session() {
+    let app = spawn_app().await;
+    let phone = logged_in_client(&app, &app.test_user, "Phone Browser/1.0").await;
+    let laptop = logged_in_client(&app, &app.test_user, "Laptop Browser/1.0").await;
+    app.test_user.login(&app).await;
+
+    let response = post_sessions(&app, "/revoke-others", &[]).await;
+    assert_is_redirect_to(&response, "/admin/security/sessions");
+
+    let html_page = app.get_admin_page_html("/admin/security/sessions").await;
+    assert!(html_page.contains("2 other sessions have been logged out."));
+    for client in [&phone, &laptop] {
+        assert_is_redirect_to(&get_dashboard(&app, client).await, "/login");
+    }
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_other_sessions() {
+    let app = spawn_app().await;
+    let phone = logged_in_client(&app, &app.test_user, "Phone Browser/1.0").await;
+    app.test_user.login(&app).await;
+
+    let new_password = Uuid::new_v4().to_string();
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("Your other sessions have been logged out."));
+    assert_is_redirect_to(&get_dashboard(&app, &phone).await, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_logout().await;
+
+    let remaining = sqlx::query_scalar!(
+        r#"SELECT count(*) as "count!" FROM user_sessions WHERE user_id = $1"#,
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining, 0);
+}
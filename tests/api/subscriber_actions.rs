@@ -0,0 +1,374 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', now(), $3)",
+        id,
+        email,
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list(email).await;
+    id
+}
+
+async fn insert_token(app: &TestApp, subscriber_id: Uuid) {
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)",
+        Uuid::new_v4().simple().to_string(),
+        subscriber_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// Publishes an issue without dispatching it, leaving a queued delivery per confirmed
+/// subscriber.
+async fn queue_an_issue(app: &TestApp) {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": "Newsletter body as plain text",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+}
+
+async fn post_action(app: &TestApp, subscriber_id: Uuid, action: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscriber/{subscriber_id}/action",
+            &app.address
+        ))
+        .form(&[("action", action)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_edit(
+    app: &TestApp,
+    subscriber_id: Uuid,
+    email: &str,
+    name: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscriber/{subscriber_id}/edit",
+            &app.address
+        ))
+        .form(&[("email", email), ("name", name)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_bulk(app: &TestApp, body: String) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/subscribers/bulk", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn count(app: &TestApp, query: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>(query)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn status_of(app: &TestApp, subscriber_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_act_on_a_subscriber() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+
+    let response = post_action(&app, subscriber_id, "confirm").await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(status_of(&app, subscriber_id).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_detail_page_shows_the_subscriber() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "confirmed").await;
+
+    let html_page = app
+        .get_admin_page_html(&format!("/admin/subscriber/{subscriber_id}"))
+        .await;
+
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(html_page.contains(r#"<td id="status">confirmed</td>"#));
+}
+
+#[tokio::test]
+async fn an_unknown_subscriber_is_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscriber/{}",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_confirmed_manually() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+
+    let response = post_action(&app, subscriber_id, "confirm").await;
+
+    assert_is_redirect_to(&response, &format!("/admin/subscriber/{subscriber_id}"));
+    assert_eq!(status_of(&app, subscriber_id).await, "confirmed");
+    let html_page = app
+        .get_admin_page_html(&format!("/admin/subscriber/{subscriber_id}"))
+        .await;
+    assert!(html_page.contains("Confirmed 1 subscriber(s)."));
+}
+
+#[tokio::test]
+async fn unsubscribing_drops_queued_deliveries_and_tokens() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "confirmed").await;
+    insert_token(&app, subscriber_id).await;
+    queue_an_issue(&app).await;
+
+    post_action(&app, subscriber_id, "unsubscribe").await;
+
+    assert_eq!(status_of(&app, subscriber_id).await, "unsubscribed");
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM issue_delivery_queue").await,
+        0
+    );
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscription_tokens").await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn a_confirmation_email_can_be_resent() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    post_action(&app, subscriber_id, "resend_confirmation").await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(status_of(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn editing_a_subscriber_moves_their_queued_deliveries() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "confirmed").await;
+    queue_an_issue(&app).await;
+
+    let response = post_edit(&app, subscriber_id, "le.guin@example.com", "Le Guin").await;
+
+    assert_is_redirect_to(&response, &format!("/admin/subscriber/{subscriber_id}"));
+    let saved = sqlx::query!(
+        "SELECT email, name FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email, "le.guin@example.com");
+    assert_eq!(saved.name, "Le Guin");
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.subscriber_email, "le.guin@example.com");
+}
+
+#[tokio::test]
+async fn edits_are_validated() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "confirmed").await;
+    insert_subscriber(&app, "frank@example.com", "confirmed").await;
+    let detail_path = format!("/admin/subscriber/{subscriber_id}");
+
+    post_edit(&app, subscriber_id, "not-an-email", "Ursula").await;
+    let html_page = app.get_admin_page_html(&detail_path).await;
+    assert!(html_page.contains("not-an-email is not a valid subscriber email."));
+
+    post_edit(&app, subscriber_id, "frank@example.com", "Ursula").await;
+    let html_page = app.get_admin_page_html(&detail_path).await;
+    assert!(html_page.contains("Another subscriber already uses this email address."));
+
+    let saved = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email, "ursula@example.com");
+}
+
+#[tokio::test]
+async fn deleting_a_subscriber_removes_everything_pending_for_them() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_subscriber(&app, "ursula@example.com", "confirmed").await;
+    insert_token(&app, subscriber_id).await;
+    queue_an_issue(&app).await;
+
+    let response = post_action(&app, subscriber_id, "delete").await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 0);
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscription_tokens").await,
+        0
+    );
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM issue_delivery_queue").await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn actions_can_be_applied_in_bulk() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let first = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+    let second = insert_subscriber(&app, "frank@example.com", "pending_confirmation").await;
+    let untouched = insert_subscriber(&app, "octavia@example.com", "pending_confirmation").await;
+
+    let response = post_bulk(&app, format!("action=confirm&ids={first}&ids={second}")).await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(status_of(&app, first).await, "confirmed");
+    assert_eq!(status_of(&app, second).await, "confirmed");
+    assert_eq!(status_of(&app, untouched).await, "pending_confirmation");
+    let html_page = app.get_admin_page_html("/admin/subscribers").await;
+    assert!(html_page.contains("Confirmed 2 subscriber(s)."));
+}
+
+#[tokio::test]
+async fn confirming_in_bulk_leaves_unsubscribed_subscribers_alone() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let pending = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+    let unsubscribed = insert_subscriber(&app, "frank@example.com", "unsubscribed").await;
+    sqlx::query!(
+        "UPDATE list_memberships SET status = 'pending_confirmation' WHERE subscriber_id = $1",
+        unsubscribed,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = post_bulk(
+        &app,
+        format!("action=confirm&ids={pending}&ids={unsubscribed}"),
+    )
+    .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(status_of(&app, pending).await, "confirmed");
+    assert_eq!(status_of(&app, unsubscribed).await, "unsubscribed");
+    let membership = sqlx::query_scalar!(
+        "SELECT status FROM list_memberships WHERE subscriber_id = $1",
+        unsubscribed,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(membership, "pending_confirmation");
+    let html_page = app.get_admin_page_html("/admin/subscribers").await;
+    assert!(html_page.contains("Confirmed 1 subscriber(s)."));
+}
+
+#[tokio::test]
+async fn a_failed_confirmation_email_does_not_stop_the_others() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let first = insert_subscriber(&app, "ursula@example.com", "pending_confirmation").await;
+    let second = insert_subscriber(&app, "frank@example.com", "pending_confirmation").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_bulk(
+        &app,
+        format!("action=resend_confirmation&ids={first}&ids={second}"),
+    )
+    .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+    let html_page = app.get_admin_page_html("/admin/subscribers").await;
+    assert!(html_page.contains("Sent 1 confirmation email(s)."));
+}
+
+#[tokio::test]
+async fn a_bulk_action_needs_a_selection() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = post_bulk(&app, "action=delete".into()).await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = app.get_admin_page_html("/admin/subscribers").await;
+    assert!(html_page.contains("Select at least one subscriber."));
+}
@@ -0,0 +1,285 @@
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', now(), 'confirmed')",
+        id,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list(email).await;
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ('a-token', $1)",
+        id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id
+}
+
+async fn publish_issue(app: &TestApp, title: &str) {
+    let newsletter_request_body = serde_json::json!({
+        "title": title,
+        "content": "Newsletter body as plain text",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+}
+
+async fn post_data_request(app: &TestApp, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions/data", &app.address))
+        .form(&[("email", email)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Requests a data access link for `email` and returns the link it was sent.
+async fn data_access_link(app: &TestApp, email: &str) -> reqwest::Url {
+    let sent = app.email_server.received_requests().await.unwrap().len();
+    post_data_request(app, email).await;
+    let requests = app.wait_for_emails(sent + 1).await;
+    let email_request = requests
+        .iter()
+        .rev()
+        .find(|r| {
+            let body: Value = serde_json::from_slice(&r.body).unwrap();
+            body["subject"] == "Your data"
+        })
+        .expect("No data access email was sent");
+    app.get_confirmation_links(email_request)
+}
+
+fn token_of(link: &reqwest::Url) -> String {
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn count(app: &TestApp, query: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>(query)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn requesting_data_for_an_unknown_address_sends_nothing_but_looks_the_same() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_data_request(&app, "nobody@example.com").await;
+
+    assert_is_redirect_to(&response, "/subscriptions/data");
+    let html_page = app.get_admin_page_html("/subscriptions/data").await;
+    assert!(html_page.contains("If we hold data for this address"));
+}
+
+#[tokio::test]
+async fn email_failures_do_not_reveal_who_is_subscribed() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let mut pages = Vec::new();
+    for email in ["ursula@example.com", "nobody@example.com"] {
+        let response = post_data_request(&app, email).await;
+        assert_is_redirect_to(&response, "/subscriptions/data");
+        pages.push(app.get_admin_page_html("/subscriptions/data").await);
+    }
+
+    assert_eq!(pages[0], pages[1]);
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn links_are_sent_to_the_stored_address_whatever_its_case() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+
+    post_data_request(&app, "  Ursula@Example.COM ").await;
+
+    let requests = app.wait_for_emails(1).await;
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["to"][0]["email"], "ursula@example.com");
+}
+
+#[tokio::test]
+async fn a_subscriber_can_download_their_data() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    publish_issue(&app, "Delivered issue").await;
+    app.dispatch_all_pending_emails().await;
+    publish_issue(&app, "Queued issue").await;
+
+    let link = data_access_link(&app, "ursula@example.com").await;
+    let manage_page = app.api_client.get(link.clone()).send().await.unwrap();
+    assert_eq!(manage_page.status().as_u16(), 200);
+    assert!(manage_page
+        .text()
+        .await
+        .unwrap()
+        .contains("ursula@example.com"));
+
+    let export_url = format!(
+        "{}/subscriptions/data/export?token={}",
+        &app.address,
+        token_of(&link)
+    );
+    let response = app.api_client.get(export_url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let data: Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["id"], subscriber_id.to_string());
+    assert_eq!(data["subscription"]["email"], "ursula@example.com");
+    assert_eq!(data["subscription_tokens"][0], "a-token");
+    assert_eq!(data["delivery_history"][0]["title"], "Delivered issue");
+    assert_eq!(data["delivery_history"][0]["outcome"], "sent");
+    assert_eq!(data["queued_deliveries"][0]["title"], "Queued issue");
+    assert_eq!(
+        count(
+            &app,
+            "SELECT COUNT(*) FROM subscriber_data_requests WHERE kind = 'export'"
+        )
+        .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn a_subscriber_can_erase_their_data() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    publish_issue(&app, "Delivered issue").await;
+    app.dispatch_all_pending_emails().await;
+    publish_issue(&app, "Queued issue").await;
+    let import_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriber_imports (import_id, imported_status) VALUES ($1, 'confirmed')",
+        import_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO subscriber_import_rejections (import_id, row_number, email, name, reason)
+        VALUES ($1, 2, ' Ursula@Example.com', 'Ursula', 'Already subscribed')",
+        import_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let token = token_of(&data_access_link(&app, "ursula@example.com").await);
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/data/erase", &app.address))
+        .form(&[("token", &token)])
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/subscriptions/data");
+    assert_eq!(count(&app, "SELECT COUNT(*) FROM subscriptions").await, 0);
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscription_tokens").await,
+        0
+    );
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM issue_delivery_queue").await,
+        0
+    );
+    assert_eq!(
+        count(
+            &app,
+            "SELECT COUNT(*) FROM issue_delivery_log WHERE subscriber_email LIKE 'erased-%'"
+        )
+        .await,
+        1
+    );
+    assert_eq!(
+        count(
+            &app,
+            "SELECT COUNT(*) FROM subscriber_import_rejections
+            WHERE email LIKE 'erased-%' AND name = ''"
+        )
+        .await,
+        1
+    );
+    assert_eq!(
+        count(
+            &app,
+            "SELECT COUNT(*) FROM subscriber_data_requests WHERE kind = 'erasure'"
+        )
+        .await,
+        1
+    );
+
+    let manage_url = format!("{}/subscriptions/data/manage?token={token}", &app.address);
+    let response = app.api_client.get(manage_url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_tampered_link_is_rejected() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = token_of(&data_access_link(&app, "ursula@example.com").await);
+    let (payload, _) = token.split_once('.').unwrap();
+
+    for token in [format!("{payload}.AAAA"), "garbage".to_string()] {
+        let url = format!("{}/subscriptions/data/export?token={token}", &app.address);
+        let response = app.api_client.get(url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert_eq!(
+        count(&app, "SELECT COUNT(*) FROM subscriber_data_requests").await,
+        0
+    );
+}
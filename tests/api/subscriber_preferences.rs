@@ -0,0 +1,528 @@
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Ursula', now(), 'confirmed')",
+        id,
+        email,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list(email).await;
+    id
+}
+
+async fn post_link_request(app: &TestApp, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/link", &app.address))
+        .form(&[("email", email)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Requests a preferences link for `email` and returns its token.
+async fn preferences_token(app: &TestApp, email: &str) -> String {
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let sent = app.email_server.received_requests().await.unwrap().len();
+    post_link_request(app, email).await;
+    let requests = app.wait_for_emails(sent + 1).await;
+    let email_request = requests.last().unwrap();
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["subject"], "Your subscription preferences");
+    let link = app.get_confirmation_links(email_request);
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn post_preferences(
+    app: &TestApp,
+    token: &str,
+    name: &str,
+    list_ids: &[Uuid],
+    digest_frequency: &str,
+    pause: &str,
+) -> reqwest::Response {
+    let mut body = serde_urlencoded::to_string([
+        ("token", token),
+        ("name", name),
+        ("digest_frequency", digest_frequency),
+        ("pause", pause),
+    ])
+    .unwrap();
+    for list_id in list_ids {
+        body.push_str(&format!("&list_ids={list_id}"));
+    }
+    app.api_client
+        .post(format!("{}/subscriptions/preferences", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn default_list_id(app: &TestApp) -> Uuid {
+    sqlx::query_scalar!("SELECT list_id FROM lists WHERE is_default")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+async fn publish_issue(app: &TestApp) {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": "Newsletter body as plain text",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+}
+
+#[tokio::test]
+async fn requesting_a_link_for_an_unknown_address_sends_nothing_but_looks_the_same() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_link_request(&app, "nobody@example.com").await;
+
+    assert_is_redirect_to(&response, "/subscriptions/preferences/link");
+    let html_page = app
+        .get_admin_page_html("/subscriptions/preferences/link")
+        .await;
+    assert!(html_page.contains("If this address is subscribed"));
+}
+
+#[tokio::test]
+async fn email_failures_do_not_reveal_who_is_subscribed() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let mut pages = Vec::new();
+    for email in ["ursula@example.com", "nobody@example.com"] {
+        let response = post_link_request(&app, email).await;
+        assert_is_redirect_to(&response, "/subscriptions/preferences/link");
+        pages.push(
+            app.get_admin_page_html("/subscriptions/preferences/link")
+                .await,
+        );
+    }
+
+    assert_eq!(pages[0], pages[1]);
+    app.wait_for_emails(1).await;
+}
+
+#[tokio::test]
+async fn the_preferences_page_rejects_invalid_tokens() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/preferences?token=not-a-token",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_subscriber_can_change_their_name_and_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.api_client
+        .post(format!("{}/admin/lists", &app.address))
+        .form(&[("name", "Release notes"), ("slug", "releases")])
+        .send()
+        .await
+        .unwrap();
+    let releases = sqlx::query_scalar!("SELECT list_id FROM lists WHERE slug = 'releases'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+
+    let page = app
+        .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+        .await;
+    assert!(page.contains("Release notes"));
+
+    let response = post_preferences(&app, &token, "Ursula K.", &[releases], "immediate", "").await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+
+    let page = app
+        .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+        .await;
+    assert!(page.contains("Your preferences have been saved."));
+    let name = sqlx::query_scalar!(
+        "SELECT name FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(name, "Ursula K.");
+    let memberships = sqlx::query!(
+        "SELECT l.slug, m.status FROM list_memberships m JOIN lists l USING (list_id)
+        WHERE m.subscriber_id = $1 ORDER BY l.slug",
+        subscriber_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let memberships: Vec<_> = memberships
+        .into_iter()
+        .map(|r| (r.slug, r.status))
+        .collect();
+    assert_eq!(
+        memberships,
+        vec![
+            ("newsletter".to_string(), "unsubscribed".to_string()),
+            ("releases".to_string(), "confirmed".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_rejected() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    let list_id = default_list_id(&app).await;
+
+    for (name, digest_frequency, pause) in [
+        ("Ursula <script>", "immediate", ""),
+        ("Ursula", "hourly", ""),
+        ("Ursula", "immediate", "forever"),
+    ] {
+        let response =
+            post_preferences(&app, &token, name, &[list_id], digest_frequency, pause).await;
+        assert_is_redirect_to(
+            &response,
+            &format!("/subscriptions/preferences?token={token}"),
+        );
+    }
+
+    let r = sqlx::query!(
+        "SELECT name, digest_frequency, paused_until FROM subscriptions WHERE id = $1",
+        subscriber_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(r.name, "Ursula");
+    assert_eq!(r.digest_frequency, "immediate");
+    assert!(r.paused_until.is_none());
+}
+
+#[tokio::test]
+async fn paused_subscribers_are_skipped_until_they_resume() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    let list_id = default_list_id(&app).await;
+
+    post_preferences(&app, &token, "Ursula", &[list_id], "immediate", "1w").await;
+    let page = app
+        .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+        .await;
+    assert!(page.contains("Emails are paused until"));
+    publish_issue(&app).await;
+    let n_queued = sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_queued, 0);
+
+    post_preferences(&app, &token, "Ursula", &[list_id], "immediate", "").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn digest_subscribers_get_issues_at_their_next_digest() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    let list_id = default_list_id(&app).await;
+    post_preferences(&app, &token, "Ursula", &[list_id], "weekly", "").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let held_back = sqlx::query_scalar!(
+        r#"SELECT execute_after > now() as "held_back!"
+        FROM issue_delivery_queue"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(held_back);
+}
+
+#[tokio::test]
+async fn issues_due_for_a_digest_are_sent_in_one_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    let list_id = default_list_id(&app).await;
+    post_preferences(&app, &token, "Ursula", &[list_id], "daily", "").await;
+    publish_issue(&app).await;
+    publish_issue(&app).await;
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["subject"], "Your daily digest: 2 new issues");
+    let html = body["htmlContent"].as_str().unwrap();
+    assert_eq!(html.matches("<h2>Newsletter title</h2>").count(), 2);
+    let outcomes = sqlx::query_scalar!("SELECT outcome FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(outcomes, ["sent", "sent"]);
+}
+
+async fn post_email_change(app: &TestApp, token: &str, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/email", &app.address))
+        .form(&[("token", token), ("email", email)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn subscriber_email(app: &TestApp, subscriber_id: Uuid) -> String {
+    sqlx::query_scalar!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn an_email_change_takes_effect_once_the_new_address_is_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    publish_issue(&app).await;
+
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = post_email_change(&app, &token, "ursula.k@example.com").await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], "ursula.k@example.com");
+    assert_eq!(
+        subscriber_email(&app, subscriber_id).await,
+        "ursula@example.com"
+    );
+
+    let confirmation_link = app.get_confirmation_links(&email_request);
+    let response = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        subscriber_email(&app, subscriber_id).await,
+        "ursula.k@example.com"
+    );
+    let queued = sqlx::query_scalar!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued, vec!["ursula.k@example.com".to_string()]);
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_invalid_new_address_is_rejected() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_email_change(&app, &token, "not-an-email").await;
+
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={token}"),
+    );
+    let page = app
+        .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+        .await;
+    assert!(page.contains("not-an-email"));
+}
+
+#[tokio::test]
+async fn a_subscriber_cannot_change_to_a_blocked_or_disposable_address() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    sqlx::query!("INSERT INTO blocked_senders (entry) VALUES ('spam.example')")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for (email, error) in [
+        (
+            "ursula@spam.example",
+            "This email address cannot subscribe.",
+        ),
+        (
+            "ursula@mailinator.com",
+            "Addresses from disposable email providers cannot subscribe.",
+        ),
+    ] {
+        let response = post_email_change(&app, &token, email).await;
+        assert_is_redirect_to(
+            &response,
+            &format!("/subscriptions/preferences?token={token}"),
+        );
+        let page = app
+            .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+            .await;
+        assert!(page.contains(error), "{email}");
+    }
+    let requests = sqlx::query_scalar!(
+        r#"SELECT count(*) as "count!" FROM email_change_requests WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(requests, 0);
+}
+
+#[tokio::test]
+async fn changing_to_an_address_already_subscribed_sends_nothing() {
+    let app = spawn_app().await;
+    insert_confirmed_subscriber(&app, "ursula@example.com").await;
+    insert_confirmed_subscriber(&app, "frank@example.com").await;
+    let token = preferences_token(&app, "ursula@example.com").await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    post_email_change(&app, &token, "frank@example.com").await;
+
+    let page = app
+        .get_admin_page_html(&format!("/subscriptions/preferences?token={token}"))
+        .await;
+    assert!(page.contains("We have sent a confirmation link to your new address."));
+}
+
+#[tokio::test]
+async fn unknown_email_change_links_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/subscriptions/email/confirm?token=unknown",
+            &app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
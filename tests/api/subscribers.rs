@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str, status: &str, at: &str) {
+    let subscribed_at: DateTime<Utc> = at.parse().unwrap();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5)",
+        Uuid::new_v4(),
+        email,
+        name,
+        subscribed_at,
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn link_labelled(html_page: &str, label: &str) -> Option<String> {
+    let end = html_page.find(&format!("\">{label}"))?;
+    let start = html_page[..end].rfind("href=\"")? + "href=\"".len();
+    Some(html_page[start..end].replace("&amp;", "&"))
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers_list() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated_newest_first() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for day in 1..=5 {
+        insert_subscriber(
+            &app,
+            &format!("user{day}@example.com"),
+            &format!("User {day}"),
+            "confirmed",
+            &format!("2024-01-0{day}T12:00:00Z"),
+        )
+        .await;
+    }
+
+    let first_page = app.get_admin_page_html("/admin/subscribers?limit=2").await;
+    assert!(first_page.contains("user5@") && first_page.contains("user4@"));
+    assert!(!first_page.contains("user3@"));
+
+    let next = link_labelled(&first_page, "Next &rarr;").unwrap();
+    let second_page = app.get_admin_page_html(&next).await;
+    assert!(second_page.contains("user3@") && second_page.contains("user2@"));
+    assert!(!second_page.contains("user4@") && !second_page.contains("user1@"));
+
+    let prev = link_labelled(&second_page, "&larr; Previous").unwrap();
+    let first_page_again = app.get_admin_page_html(&prev).await;
+    assert!(first_page_again.contains("user5@") && first_page_again.contains("user4@"));
+    assert!(!first_page_again.contains("user3@"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_date_range() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "early@example.com",
+        "Early",
+        "confirmed",
+        "2024-01-01T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "inside@example.com",
+        "Inside",
+        "confirmed",
+        "2024-01-10T23:30:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "Pending",
+        "pending_confirmation",
+        "2024-01-10T09:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "late@example.com",
+        "Late",
+        "confirmed",
+        "2024-01-11T00:00:00Z",
+    )
+    .await;
+
+    let html_page = app
+        .get_admin_page_html("/admin/subscribers?status=confirmed&from=2024-01-05&to=2024-01-10&q=")
+        .await;
+
+    assert!(html_page.contains("inside@example.com"));
+    assert!(!html_page.contains("early@example.com"));
+    assert!(!html_page.contains("pending@example.com"));
+    assert!(!html_page.contains("late@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name_ignoring_case() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Le Guin",
+        "confirmed",
+        "2024-01-01T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "frank@example.com",
+        "Herbert",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "Butler",
+        "confirmed",
+        "2024-01-03T08:00:00Z",
+    )
+    .await;
+
+    let by_email = app.get_admin_page_html("/admin/subscribers?q=URSULA").await;
+    assert!(by_email.contains("ursula@example.com"));
+    assert!(!by_email.contains("frank@example.com"));
+
+    let by_name = app.get_admin_page_html("/admin/subscribers?q=herb").await;
+    assert!(by_name.contains("frank@example.com"));
+    assert!(!by_name.contains("octavia@example.com"));
+}
+
+#[tokio::test]
+async fn search_wildcards_are_matched_literally() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "a_b@example.com",
+        "Underscore",
+        "confirmed",
+        "2024-01-01T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "axb@example.com",
+        "Letter",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+
+    let html_page = app.get_admin_page_html("/admin/subscribers?q=a_b").await;
+
+    assert!(html_page.contains("a_b@example.com"));
+    assert!(!html_page.contains("axb@example.com"));
+}
+
+#[tokio::test]
+async fn counts_per_status_are_shown() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "one@example.com",
+        "One",
+        "confirmed",
+        "2024-01-01T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "two@example.com",
+        "Two",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "three@example.com",
+        "Three",
+        "pending_confirmation",
+        "2024-01-03T08:00:00Z",
+    )
+    .await;
+
+    let html_page = app.get_admin_page_html("/admin/subscribers?q=one").await;
+
+    assert!(html_page.contains("<strong>Total:</strong> 3"));
+    assert!(html_page.contains("<strong>confirmed:</strong> 2"));
+    assert!(html_page.contains("<strong>pending_confirmation:</strong> 1"));
+}
+
+#[tokio::test]
+async fn an_invalid_date_filter_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/subscribers?from=yesterday", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_subscriber(app: &TestApp, email: &str, name: &str, status: &str, at: &str) {
+    let subscribed_at: DateTime<Utc> = at.parse().unwrap();
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5)",
+        Uuid::new_v4(),
+        email,
+        name,
+        subscribed_at,
+        status,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn export(app: &TestApp, path: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/subscribers/{path}", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    let app = spawn_app().await;
+
+    for path in ["export.csv", "export.json"] {
+        let response = export(&app, path).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn csv_export_honours_the_list_filters() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Le Guin, Ursula",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "frank@example.com",
+        "Frank",
+        "confirmed",
+        "2024-01-03T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "Pending",
+        "pending_confirmation",
+        "2024-01-04T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "early@example.com",
+        "Early",
+        "confirmed",
+        "2023-12-01T08:00:00Z",
+    )
+    .await;
+
+    let response = export(&app, "export.csv?status=confirmed&from=2024-01-01").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let body = response.text().await.unwrap();
+    let rows: Vec<&str> = body.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0], "id,email,name,status,subscribed_at");
+    assert!(rows[1].contains(",frank@example.com,Frank,confirmed,2024-01-03T08:00:00Z"));
+    assert!(rows[2].contains(r#",ursula@example.com,"Le Guin, Ursula",confirmed,"#));
+}
+
+#[tokio::test]
+async fn csv_cells_a_spreadsheet_would_run_as_formulas_are_quoted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "=HYPERLINK(\"http://evil.example\")",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+
+    let body = export(&app, "export.csv").await.text().await.unwrap();
+
+    let rows: Vec<&str> = body.lines().collect();
+    assert!(rows[1]
+        .contains(r#",ursula@example.com,"'=HYPERLINK(""http://evil.example"")",confirmed,"#));
+}
+
+#[tokio::test]
+async fn json_export_honours_the_search() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        "confirmed",
+        "2024-01-02T08:00:00Z",
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "frank@example.com",
+        "Frank",
+        "confirmed",
+        "2024-01-03T08:00:00Z",
+    )
+    .await;
+
+    let response = export(&app, "export.json?q=URSULA").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    let subscribers = body.as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "ursula@example.com");
+    assert_eq!(subscribers[0]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn an_empty_json_export_is_an_empty_array() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = export(&app, "export.json").await;
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, serde_json::json!([]));
+}
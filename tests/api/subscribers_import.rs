@@ -0,0 +1,163 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscribers_import("confirmed", false, "email,name\n")
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn valid_rows_are_imported_and_the_rest_reported() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES (gen_random_uuid(), 'existing@example.com', 'Existing', now(), 'confirmed')",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.add_to_default_list("existing@example.com").await;
+    let csv = "Name,Email,Source\n\
+        Ursula,ursula@example.com,old tool\n\
+        \"Herbert, Frank\",frank@example.com,old tool\n\
+        Existing,existing@example.com,old tool\n\
+        Ursula again,ursula@example.com,old tool\n\
+        No email,not-an-email,old tool\n\
+        ,nameless@example.com,old tool\n";
+
+    let response = app.post_subscribers_import("confirmed", false, csv).await;
+
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(location.starts_with("/admin/subscribers/import/"));
+    let report_html = app.get_admin_page_html(location).await;
+    assert!(report_html.contains("The import has completed."));
+    assert!(report_html.contains(r#"<td id="nImported">2</td>"#));
+    assert!(report_html.contains(r#"<td id="nDuplicates">2</td>"#));
+    assert!(report_html.contains(r#"<td id="nRejected">2</td>"#));
+
+    let imported =
+        sqlx::query!("SELECT name, status FROM subscriptions WHERE email = 'frank@example.com'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(imported.name, "Herbert, Frank");
+    assert_eq!(imported.status, "confirmed");
+
+    let report = app
+        .api_client
+        .get(format!("{}{location}/rejected.csv", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(report.status().as_u16(), 200);
+    assert!(report.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let report = report.text().await.unwrap();
+    let rows: Vec<&str> = report.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0], "row,email,name,reason");
+    assert!(rows[1].starts_with("6,not-an-email,No email,"));
+    assert!(rows[2].starts_with("7,nameless@example.com,,"));
+}
+
+#[tokio::test]
+async fn pending_imports_can_be_sent_confirmation_emails() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let csv = "email,name\nursula@example.com,Ursula\nfrank@example.com,Frank";
+
+    let response = app
+        .post_subscribers_import("pending_confirmation", true, csv)
+        .await;
+
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    // The emails are sent in the background, and counted once they are all sent
+    app.wait_for_emails(2).await;
+    let mut report_html = String::new();
+    for _ in 0..100 {
+        report_html = app.get_admin_page_html(location).await;
+        if report_html.contains(r#"<td id="nConfirmationEmails">2</td>"#) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(report_html.contains(r#"<td id="nConfirmationEmails">2</td>"#));
+    let n_tokens = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 2);
+    let n_pending = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM subscriptions WHERE status = 'pending_confirmation'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_pending, 2);
+}
+
+#[tokio::test]
+async fn confirmed_imports_are_not_sent_confirmation_emails() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscribers_import("confirmed", true, "email,name\nursula@example.com,Ursula\n")
+        .await;
+}
+
+#[tokio::test]
+async fn a_file_without_the_expected_header_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_subscribers_import("confirmed", false, "ursula@example.com,Ursula\n")
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+    let html_page = app.get_admin_page_html("/admin/subscribers/import").await;
+    assert!(html_page.contains("The CSV header must contain `email` and `name` columns."));
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
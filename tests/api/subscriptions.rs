@@ -4,7 +4,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -125,7 +125,7 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
         assert_eq!(links.len(), 1);
         links[0].as_str().to_owned()
     };
-    let _ = get_link(&body["htmlContent"].as_str().unwrap());
+    let _ = get_link(body["htmlContent"].as_str().unwrap());
 }
 
 #[tokio::test]
@@ -142,3 +142,71 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_normalises_the_email_address() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=%20Ursula_Le_Guin%40GMail.com%20";
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let saved = sqlx::query!("select email from subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+}
+
+#[tokio::test]
+async fn addresses_that_only_differ_by_case_are_one_subscriber() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    app.post_subscriptions("name=le%20guin&email=URSULA_LE_GUIN%40Gmail.com".into())
+        .await;
+
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 1);
+}
+
+#[tokio::test]
+async fn local_parts_are_case_sensitive_unless_lowercased() {
+    let app =
+        spawn_app_with(|c| c.application.email_normalisation.lowercase_local_part = false).await;
+
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    app.post_subscriptions("name=le%20guin&email=URSULA_LE_GUIN%40Gmail.com".into())
+        .await;
+
+    let emails = sqlx::query_scalar!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        emails,
+        ["URSULA_LE_GUIN@gmail.com", "ursula_le_guin@gmail.com"]
+    );
+}
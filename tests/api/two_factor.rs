@@ -0,0 +1,213 @@
+use chrono::Utc;
+use zerotoprod::authentication::Totp;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn post_code(app: &TestApp, path: &str, code: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}{path}", &app.address))
+        .form(&[("code", code)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn current_code(totp: &Totp) -> String {
+    totp.code(Totp::step(Utc::now()))
+}
+
+/// Enables two-factor authentication for the logged in test user. Returns their
+/// authenticator and recovery codes.
+async fn enrol(app: &TestApp) -> (Totp, Vec<String>) {
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    let secret = html_page
+        .split(r#"<code id="totpSecret">"#)
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("No TOTP secret on the page");
+    let totp = Totp::from_base32(secret).unwrap();
+
+    let response = post_code(app, "/admin/security/enable", &current_code(&totp)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let codes = html_page
+        .split("<li>")
+        .skip(1)
+        .map(|item| item.split("</li>").next().unwrap().trim().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(codes.len(), 10);
+    (totp, codes)
+}
+
+/// Logs out and gives the test user's password again.
+async fn log_in_again(app: &TestApp) -> reqwest::Response {
+    app.post_logout().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_two_factor_authentication() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/security", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn enrolment_shows_a_qr_code_and_needs_a_valid_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("<svg"));
+    assert!(html_page.contains("otpauth://totp/Newsletter:"));
+
+    let response = post_code(&app, "/admin/security/enable", "000000").await;
+    assert_is_redirect_to(&response, "/admin/security");
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("The code is incorrect."));
+    let enabled = sqlx::query_scalar!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(enabled.is_none());
+
+    enrol(&app).await;
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+    assert!(html_page.contains("10 unused"));
+    let n_hashes = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM recovery_codes"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_hashes, 10);
+}
+
+#[tokio::test]
+async fn the_password_alone_does_not_log_in_a_user_with_two_factor_authentication() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (totp, _) = enrol(&app).await;
+
+    let response = log_in_again(&app).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let response = app
+        .api_client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    let response = post_code(&app, "/login/two-factor", "000000").await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html_page = app.get_admin_page_html("/login/two-factor").await;
+    assert!(html_page.contains("Authentication failed"));
+
+    // The code used for enrolment cannot be used again, a fresh one is needed
+    let last_step = sqlx::query_scalar!(
+        "SELECT totp_last_step FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .unwrap();
+    let response = post_code(&app, "/login/two-factor", &totp.code(last_step)).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let response = post_code(&app, "/login/two-factor", &totp.code(last_step + 1)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn recovery_codes_can_be_used_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, codes) = enrol(&app).await;
+
+    log_in_again(&app).await;
+    let response = post_code(&app, "/login/two-factor", &codes[0].to_uppercase()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    log_in_again(&app).await;
+    let response = post_code(&app, "/login/two-factor", &codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let response = post_code(&app, "/login/two-factor", &codes[1]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("8 unused"));
+}
+
+#[tokio::test]
+async fn two_factor_authentication_can_be_disabled_with_a_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, codes) = enrol(&app).await;
+
+    let response = post_code(&app, "/admin/security/disable", &codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/security");
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("Two-factor authentication has been disabled."));
+
+    let response = log_in_again(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn changing_the_account_email_needs_a_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, codes) = enrol(&app).await;
+    let post_email = |code: String| {
+        app.api_client
+            .post(format!("{}/admin/security/email", &app.address))
+            .form(&[
+                ("email", "admin@example.com".to_string()),
+                ("current_password", app.test_user.password.clone()),
+                ("code", code),
+            ])
+            .send()
+    };
+
+    let response = post_email("000000".into()).await.unwrap();
+    assert_is_redirect_to(&response, "/admin/security");
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains("The code is incorrect."));
+    assert!(!html_page.contains(r#"value="admin@example.com""#));
+
+    post_email(codes[0].clone()).await.unwrap();
+    let html_page = app.get_admin_page_html("/admin/security").await;
+    assert!(html_page.contains(r#"value="admin@example.com""#));
+}
+
+#[tokio::test]
+async fn the_second_step_needs_a_correct_password_first() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login/two-factor", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    let response = post_code(&app, "/login/two-factor", "123456").await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,344 @@
+use serde_json::Value;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+
+/// Stores a second user with `role` and logs the app's client in as them.
+async fn login_as(app: &TestApp, role: &str) -> TestUser {
+    let user = TestUser::generate();
+    user.store_with_role(&app.db_pool, role).await;
+    user.login(app).await;
+    user
+}
+
+async fn post_admin(app: &TestApp, path: &str, form: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin{path}", &app.address))
+        .form(form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_admin(app: &TestApp, path: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin{path}", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn newsletter() -> Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })
+}
+
+/// Invites `email` as the logged in owner and returns the invitation's token.
+async fn invite(app: &TestApp, email: &str, role: &str) -> String {
+    let _mock_guard = Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = post_admin(app, "/users/invite", &[("email", email), ("role", role)]).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email_request = requests.last().unwrap();
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(
+        body["subject"],
+        "You have been invited to the newsletter admin"
+    );
+    assert_eq!(body["to"][0]["email"], email);
+    let link = app.get_confirmation_links(email_request);
+    assert_eq!(link.path(), "/login/invitation");
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn accept(app: &TestApp, token: &str, username: &str, password: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login/invitation", &app.address))
+        .form(&[
+            ("token", token),
+            ("username", username),
+            ("password", password),
+            ("password_check", password),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn viewers_can_look_but_not_change_anything() {
+    let app = spawn_app().await;
+    let viewer = login_as(&app, "viewer").await;
+
+    for page in ["/dashboard", "/issues", "/subscribers", "/newsletters"] {
+        assert_eq!(get_admin(&app, page).await.status().as_u16(), 200, "{page}");
+    }
+    let response = app.post_publish_newsletter(&newsletter()).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = post_admin(&app, "/lists", &[("name", "Weekly")]).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(get_admin(&app, "/users").await.status().as_u16(), 403);
+
+    // Their own account is still theirs to manage
+    let form = [
+        ("email", "viewer@example.com"),
+        ("current_password", &viewer.password),
+    ];
+    let response = post_admin(&app, "/security/email", &form).await;
+    assert_is_redirect_to(&response, "/admin/security");
+}
+
+#[tokio::test]
+async fn editors_can_publish_but_not_manage_users() {
+    let app = spawn_app().await;
+    login_as(&app, "editor").await;
+
+    let response = app.post_publish_newsletter(&newsletter()).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    assert_eq!(get_admin(&app, "/users").await.status().as_u16(), 403);
+    assert_eq!(get_admin(&app, "/lockouts").await.status().as_u16(), 403);
+    let response = post_admin(
+        &app,
+        "/users/invite",
+        &[("email", "someone@example.com"), ("role", "owner")],
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Your role is editor."));
+    assert!(!html_page.contains("/admin/users"));
+}
+
+#[tokio::test]
+async fn an_invited_user_can_create_an_account_with_the_chosen_role() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "editor@example.com", "editor").await;
+    let html_page = app.get_admin_page_html("/admin/users").await;
+    assert!(html_page.contains("An invitation has been sent to editor@example.com."));
+    assert!(html_page.contains("Pending invitations"));
+    app.post_logout().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login/invitation?token={token}", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("editor@example.com"));
+
+    let username = Uuid::new_v4().to_string();
+    let password = Uuid::new_v4().to_string();
+    let response = accept(&app, &token, &username, &password).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Your account has been created. Please log in."));
+
+    let response = app
+        .post_login(&serde_json::json!({ "username": username, "password": password }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app
+        .get_admin_dashboard_html()
+        .await
+        .contains("Your role is editor."));
+    let email = sqlx::query_scalar!("SELECT email FROM users WHERE username = $1", username)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(email.as_deref(), Some("editor@example.com"));
+
+    // The invitation cannot be used twice
+    let response = accept(&app, &token, &Uuid::new_v4().to_string(), &password).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_invitation_with_a_taken_username_can_be_retried() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = invite(&app, "viewer@example.com", "viewer").await;
+    app.post_logout().await;
+
+    let password = Uuid::new_v4().to_string();
+    let response = accept(&app, &token, &app.test_user.username, &password).await;
+    assert_is_redirect_to(&response, &format!("/login/invitation?token={token}"));
+
+    let response = accept(&app, &token, &Uuid::new_v4().to_string(), &password).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_invitation_that_could_not_be_sent_is_not_kept() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/v3/smtp/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_admin(
+        &app,
+        "/users/invite",
+        &[("email", "viewer@example.com"), ("role", "viewer")],
+    )
+    .await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_page_html("/admin/users").await;
+    assert!(html_page.contains("The invitation could not be sent to viewer@example.com."));
+    let n_invitations = sqlx::query_scalar!(r#"SELECT COUNT(*) as "n!" FROM user_invitations"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_invitations, 0);
+}
+
+#[tokio::test]
+async fn invitation_tokens_are_encoded_when_redirecting_back_to_the_form() {
+    let app = spawn_app().await;
+
+    let response = accept(&app, "bad\r\ntoken", " ", &Uuid::new_v4().to_string()).await;
+
+    assert_is_redirect_to(&response, "/login/invitation?token=bad%0D%0Atoken");
+}
+
+#[tokio::test]
+async fn deactivated_users_are_logged_out_and_cannot_log_in() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate();
+    viewer.store_with_role(&app.db_pool, "viewer").await;
+    let viewer_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let login = |client: &reqwest::Client| {
+        client
+            .post(format!("{}/login", &app.address))
+            .form(&[
+                ("username", viewer.username.as_str()),
+                ("password", viewer.password.as_str()),
+            ])
+            .send()
+    };
+    let response = login(&viewer_client).await.unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    app.test_user.login(&app).await;
+    let action_path = format!("/users/{}/action", viewer.user_id);
+    let response = post_admin(&app, &action_path, &[("action", "deactivate")]).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_page_html("/admin/users").await;
+    assert!(html_page.contains(&format!("{} has been deactivated.", viewer.username)));
+
+    let dashboard = format!("{}/admin/dashboard", &app.address);
+    let response = viewer_client.get(&dashboard).send().await.unwrap();
+    assert_is_redirect_to(&response, "/login");
+    let response = login(&viewer_client).await.unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    let response = post_admin(&app, &action_path, &[("action", "reactivate")]).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let response = login(&viewer_client).await.unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn owners_can_change_roles_and_delete_users() {
+    let app = spawn_app().await;
+    let user = TestUser::generate();
+    user.store_with_role(&app.db_pool, "viewer").await;
+    app.test_user.login(&app).await;
+
+    let response = post_admin(
+        &app,
+        &format!("/users/{}/role", user.user_id),
+        &[("role", "editor")],
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let role = sqlx::query_scalar!("SELECT role FROM users WHERE user_id = $1", user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(role, "editor");
+
+    let response = post_admin(
+        &app,
+        &format!("/users/{}/action", user.user_id),
+        &[("action", "delete")],
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_page_html("/admin/users").await;
+    assert!(html_page.contains(&format!("{} has been deleted.", user.username)));
+    let remaining = sqlx::query_scalar!(
+        r#"SELECT count(*) as "count!" FROM users WHERE user_id = $1"#,
+        user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+#[tokio::test]
+async fn owners_cannot_demote_or_remove_themselves() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let user_id = app.test_user.user_id;
+
+    post_admin(
+        &app,
+        &format!("/users/{user_id}/role"),
+        &[("role", "viewer")],
+    )
+    .await;
+    let response = post_admin(
+        &app,
+        &format!("/users/{user_id}/action"),
+        &[("action", "deactivate")],
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let html_page = app.get_admin_page_html("/admin/users").await;
+    assert!(html_page.contains("You cannot change your own account here."));
+    let row = sqlx::query!(
+        "SELECT role, deactivated_at FROM users WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(row.role, "owner");
+    assert!(row.deactivated_at.is_none());
+}